@@ -0,0 +1,71 @@
+# Deutsche Meldungen von pt
+
+## Aufgaben
+
+task-added = Aufgabe { $id } hinzugefügt.
+task-not-found = Aufgabe { $id } nicht gefunden.
+task-checked = Aufgabe { $id } erledigt.
+task-unchecked = Aufgabe { $id } wieder offen.
+task-archived = Aufgabe { $id } archiviert.
+task-unarchived = Aufgabe { $id } aus dem Archiv geholt.
+task-due = Aufgabe { $id } ist am { $date } fällig.
+task-not-due = Aufgabe { $id } hat kein Fälligkeitsdatum mehr.
+no-tasks = Keine Aufgaben gefunden.
+
+## Zeiten in der Aufgabenliste
+
+list-started = gestartet { $when }
+list-last-worked = zuletzt bearbeitet { $when }
+list-due = fällig { $when }
+time-now = gerade eben
+time-minutes-ago = vor { $count } Min.
+time-hours-ago = vor { $count } Std.
+time-days-ago = vor { $count } T.
+time-in-days = in { $count } T.
+list-clock-went-back = die Uhr wurde zurückgestellt, prüfe die Zeiten mit pt --edit-times
+
+## Pomodoros und Pausen
+
+pomodoro-started = Pomodoro für Aufgabe { $id } gestartet.
+pomodoro-already-active = Für Aufgabe { $id } läuft bereits ein Pomodoro.
+pomodoro-finished = Pomodoro für Aufgabe { $id } beendet.
+pomodoro-not-active = Für Aufgabe { $id } läuft kein Pomodoro.
+pomodoro-none = Aufgabe { $id } hat keine Pomodoros.
+pomodoro-clock-went-back = Die Uhr steht vor dem Start des Pomodoros von Aufgabe { $id }, er endet daher bei seinem Start. Prüfe seine Zeiten mit pt --edit-times { $id }.
+pomodoro-tracked = { $minutes ->
+    [one] 1 Minute
+   *[other] { $minutes } Minuten
+} für Aufgabe { $id } erfasst.
+break-started = Pause für { $minutes ->
+    [one] 1 Minute
+   *[other] { $minutes } Minuten
+} gestartet.
+alert-snoozed = Erinnerung für Aufgabe { $id } bis { $time } verschoben.
+alert-nothing-to-snooze = Es gibt keine Erinnerung zum Verschieben.
+alert-acknowledged = Erinnerung für Aufgabe { $id } bestätigt.
+alert-nothing-to-acknowledge = Es gibt keine Erinnerung zum Bestätigen.
+
+## Benachrichtigungen
+
+notification-pomodoro-title = Pomodoro für Aufgabe { $id } beendet.
+notification-pomodoro-body = { $description }
+notification-start-title = Pomodoro für Aufgabe { $id } gestartet.
+notification-start-body = { $description } bis { $end }.
+notification-due-title = Aufgabe { $id } ist { $due } fällig.
+notification-due-body = { $description }
+due-today = heute
+due-tomorrow = morgen
+due-yesterday = gestern
+due-on = am { $date }
+notification-break-title = Pause beendet.
+notification-break-body = Die Pause endete um { $end }.
+notification-test-title = Das ist eine Testbenachrichtigung
+notification-test-body = Hier stehen Informationen zu dieser Testbenachrichtigung
+
+## Knöpfe der Benachrichtigungen
+
+action-break = Pause starten
+action-snooze = Später erinnern
+action-acknowledge = Bestätigen
+action-pomodoro = Nächsten Pomodoro starten
+action-done = Als erledigt markieren
@@ -0,0 +1,72 @@
+# Messages of pt. Translations are files like de.ftl with the same IDs, see the Readme.
+# Placeables like { $id } are filled in by pt.
+
+## Tasks
+
+task-added = Task { $id } added.
+task-not-found = Task { $id } not found.
+task-checked = Task { $id } checked.
+task-unchecked = Task { $id } unchecked.
+task-archived = Task { $id } moved to archive.
+task-unarchived = Task { $id } moved out of archive.
+task-due = Task { $id } is due on { $date }.
+task-not-due = Task { $id } has no due date anymore.
+no-tasks = No tasks found.
+
+## Times in the task list, { $when } is e.g. "12m ago" or "in 3d"
+
+list-started = started { $when }
+list-last-worked = last worked { $when }
+list-due = due { $when }
+time-now = just now
+time-minutes-ago = { $count }m ago
+time-hours-ago = { $count }h ago
+time-days-ago = { $count }d ago
+time-in-days = in { $count }d
+list-clock-went-back = the clock went back, check the times with pt --edit-times
+
+## Pomodoros and breaks
+
+pomodoro-started = Pomodoro started for task { $id }.
+pomodoro-already-active = Pomodoro already active for task { $id }.
+pomodoro-finished = Pomodoro finished for task { $id }.
+pomodoro-not-active = No pomodoro active for task { $id }.
+pomodoro-none = No pomodoros found for task { $id }.
+pomodoro-clock-went-back = The clock is earlier than the start of the pomodoro of task { $id }, it ends when it started. Check its times with pt --edit-times { $id }.
+pomodoro-tracked = Tracked { $minutes ->
+    [one] 1 minute
+   *[other] { $minutes } minutes
+} for task { $id }.
+break-started = Break started for { $minutes ->
+    [one] 1 minute
+   *[other] { $minutes } minutes
+}.
+alert-snoozed = Alert for task { $id } snoozed until { $time }.
+alert-nothing-to-snooze = There is no alert to snooze.
+alert-acknowledged = Alert for task { $id } acknowledged.
+alert-nothing-to-acknowledge = There is no alert to acknowledge.
+
+## Notifications. The templates of the config file take precedence.
+
+notification-pomodoro-title = Pomodoro finished for task { $id }.
+notification-pomodoro-body = { $description }
+notification-start-title = Pomodoro started for task { $id }.
+notification-start-body = { $description } until { $end }.
+notification-due-title = Task { $id } due { $due }.
+notification-due-body = { $description }
+due-today = today
+due-tomorrow = tomorrow
+due-yesterday = yesterday
+due-on = on { $date }
+notification-break-title = Break finished.
+notification-break-body = The break ended at { $end }.
+notification-test-title = This is a test notification
+notification-test-body = Here is some information about this test notification
+
+## Buttons of notifications
+
+action-break = Start break
+action-snooze = Snooze
+action-acknowledge = Acknowledge
+action-pomodoro = Start next pomodoro
+action-done = Mark done
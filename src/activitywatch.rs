@@ -0,0 +1,84 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use chrono::SecondsFormat;
+use serde_json::{json, Value};
+
+use crate::config::ActivityWatchConfig;
+use crate::event::Event;
+use crate::http;
+use crate::logged::Logged;
+use crate::Task;
+
+// Remembers the pomodoros that were sent to ActivityWatch, so none is added twice
+const ACTIVITYWATCH_STATE_FILE: &str = "activitywatch.json";
+const BUCKET_TYPE: &str = "pomodoro";
+
+// Buckets of watchers are named after the machine, like aw-watcher-window_<hostname>
+fn hostname() -> String {
+    env::var("HOSTNAME")
+        .or_else(|_| env::var("COMPUTERNAME"))
+        .ok()
+        .or_else(|| fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+fn create_bucket(config: &ActivityWatchConfig, bucket: &str, hostname: &str) -> Result<(), String> {
+    let url = format!("{}/api/0/buckets/{}", config.url.trim_end_matches('/'), http::percent_encode(bucket));
+    let body = json!({ "client": "pt", "type": BUCKET_TYPE, "hostname": hostname });
+    match http::agent().post(&url).send_json(body) {
+        // 304 means the bucket exists already
+        Ok(_) | Err(ureq::Error::Status(304, _)) => Ok(()),
+        Err(e) => Err(http::error_message(e)),
+    }
+}
+
+// Adds an event for each finished pomodoro that wasn't added yet, returns their number
+pub fn push(config: &ActivityWatchConfig, list_dir: &Path, list_name: &str, tasks: &[Task]) -> Result<usize, String> {
+    let hostname = hostname();
+    let bucket = config.bucket.clone().unwrap_or_else(|| format!("aw-watcher-pt_{}", hostname));
+    let mut logged = Logged::load(list_dir, ACTIVITYWATCH_STATE_FILE);
+    let mut events: Vec<Value> = Vec::new();
+    let mut added = Vec::new();
+    for task in tasks {
+        for (pomodoro, end_time) in logged.pending(task) {
+            events.push(json!({
+                "timestamp": pomodoro.start_time.to_rfc3339_opts(SecondsFormat::Millis, true),
+                "duration": (end_time - pomodoro.start_time).num_milliseconds() as f64 / 1000.0,
+                "data": {
+                    "label": task.description,
+                    "task_id": task.id,
+                    "list": list_name,
+                    "projects": task.projects,
+                    "tags": task.tags,
+                },
+            }));
+            added.push((task, pomodoro));
+        }
+    }
+    if events.is_empty() {
+        return Ok(0);
+    }
+    create_bucket(config, &bucket, &hostname)?;
+    let url = format!("{}/api/0/buckets/{}/events", config.url.trim_end_matches('/'), http::percent_encode(&bucket));
+    http::agent().post(&url).send_json(Value::Array(events)).map_err(http::error_message)?;
+    for (task, pomodoro) in &added {
+        logged.add(task, pomodoro, None);
+    }
+    logged.save()?;
+    Ok(added.len())
+}
+
+// Adds finished pomodoros right away if enabled. ActivityWatch runs on the same machine, so it's
+// usually there; if not, the pomodoros are added the next time one finishes.
+pub fn update(config: &ActivityWatchConfig, list_dir: &Path, list_name: &str, events: &[Event], tasks: &[Task]) {
+    if !config.enabled || !events.iter().any(|event| matches!(event, Event::PomodoroFinished { .. })) {
+        return;
+    }
+    if let Err(e) = push(config, list_dir, list_name, tasks) {
+        println!("Failed to add pomodoros to ActivityWatch: {}", e);
+    }
+}
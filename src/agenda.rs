@@ -0,0 +1,77 @@
+use chrono::{DateTime, Duration, Local, NaiveDate};
+
+use crate::state::State;
+use crate::{Task, POMODORO_DURATION};
+
+// Something with a time on the agenda, sorted by its start
+struct Entry {
+    start: DateTime<Local>,
+    end: Option<DateTime<Local>>,
+    text: String,
+}
+
+fn time_range(entry: &Entry) -> String {
+    match entry.end {
+        Some(end) => format!("{}-{}", entry.start.format("%H:%M"), end.format("%H:%M")),
+        None => format!("{:<11}", entry.start.format("%H:%M")),
+    }
+}
+
+// Pomodoros, breaks and snoozed alerts of the day
+fn entries(tasks: &[Task], state: &State, day: NaiveDate) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    for task in tasks.iter().filter(|task| !task.archived) {
+        for pomodoro in &task.pomodoros {
+            let start = pomodoro.start_time.with_timezone(&Local);
+            let (end, kind) = match pomodoro.end_time {
+                Some(end) => (end.with_timezone(&Local), "Pomodoro"),
+                None => (start + Duration::minutes(POMODORO_DURATION), "Pomodoro running"),
+            };
+            if start.date_naive() == day || end.date_naive() == day {
+                entries.push(Entry { start, end: Some(end), text: format!("{}  {:0>3} {}", kind, task.id, task.description) });
+            }
+        }
+    }
+    if let Some(break_end) = state.break_end.map(|end| end.with_timezone(&Local)).filter(|end| end.date_naive() == day) {
+        entries.push(Entry { start: break_end, end: None, text: "Break ends".to_string() });
+    }
+    for snooze in &state.snoozed {
+        let until = snooze.until.with_timezone(&Local);
+        if until.date_naive() == day {
+            entries.push(Entry { start: until, end: None, text: format!("Snoozed alert  {:0>3} {}", snooze.alert.task_id, snooze.alert.description) });
+        }
+    }
+    entries.sort_by_key(|entry| entry.start);
+    entries
+}
+
+// Active tasks due on the day, on the first day also the overdue ones
+fn due_tasks(tasks: &[Task], day: NaiveDate, first: bool) -> Vec<String> {
+    tasks.iter()
+        .filter(|task| !task.done && !task.archived)
+        .filter_map(|task| task.due.map(|due| (task, due)))
+        .filter(|(_, due)| *due == day || (first && *due < day))
+        .map(|(task, due)| match due < day {
+            true => format!("Due    {:0>3} {} (overdue since {})", task.id, task.description, due),
+            false => format!("Due    {:0>3} {}", task.id, task.description),
+        })
+        .collect()
+}
+
+// Overview of today and tomorrow: due tasks first, then everything with a time in chronological order
+pub fn render(tasks: &[Task], state: &State) -> String {
+    let today = Local::now().date_naive();
+    let mut lines = Vec::new();
+    for (offset, name) in [(0, "Today"), (1, "Tomorrow")] {
+        let day = today + Duration::days(offset);
+        lines.push(format!("{}, {}", name, day.format("%a %Y-%m-%d")));
+        let due = due_tasks(tasks, day, offset == 0);
+        let entries = entries(tasks, state, day);
+        if due.is_empty() && entries.is_empty() {
+            lines.push("  Nothing planned.".to_string());
+        }
+        lines.extend(due.into_iter().map(|line| format!("  {}", line)));
+        lines.extend(entries.iter().map(|entry| format!("  {}  {}", time_range(entry), entry.text)));
+    }
+    lines.join("\n")
+}
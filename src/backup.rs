@@ -0,0 +1,148 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, Utc};
+
+use serde::{Serialize, Deserialize};
+
+use crate::Task;
+use crate::crypto;
+use crate::schema;
+use crate::storage::{ARCHIVE_FILE, JOURNAL_FILE, SYNC_STATE_FILE, TASK_FILE};
+
+/// A backup is a single JSON file holding the contents of every text file in the data directory.
+/// The tasks are stored in tasks.json and archive.json, independent of the storage mode.
+/// Binary files such as the alarm sound, the journal and the sync state are not part of the backup.
+#[derive(Serialize)]
+#[derive(Deserialize)]
+pub struct Backup {
+    pub created: DateTime<Utc>,
+    pub files: BTreeMap<String, String>,
+}
+
+impl Backup {
+    /// Parses the task and archive file contained in the backup
+    pub fn tasks(&self) -> Result<Vec<Task>, String> {
+        let content = self.files.get(TASK_FILE)
+            .ok_or(format!("backup contains no {}", TASK_FILE))?;
+        let mut tasks = parse_entry(TASK_FILE, content)?;
+        if let Some(content) = self.files.get(ARCHIVE_FILE) {
+            for task in parse_entry(ARCHIVE_FILE, content)? {
+                if !tasks.iter().any(|t| t.id == task.id) {
+                    tasks.push(task);
+                }
+            }
+        }
+        Ok(tasks)
+    }
+}
+
+fn parse_entry(name: &str, content: &str) -> Result<Vec<Task>, String> {
+    if content.is_empty() {
+        return Ok(Vec::new());
+    }
+    schema::parse_tasks(content).map_err(|e| format!("{} is invalid: {}", name, e))
+}
+
+/// Snapshots all text files of the data directory, replacing the task file with the in-memory tasks.
+/// If the archive was not loaded, the archived tasks are taken from the archive file.
+pub fn create_backup(data_dir: &Path, tasks: &[Task]) -> io::Result<Backup> {
+    let mut files = BTreeMap::new();
+    for entry in fs::read_dir(data_dir)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name == JOURNAL_FILE || name == SYNC_STATE_FILE {
+            continue;
+        }
+        // Encrypted files are stored decrypted, the backup as a whole is encrypted instead
+        if let Ok(content) = fs::read(entry.path()).map_err(|e| e.to_string()).and_then(crypto::decode) {
+            files.insert(name, content);
+        }
+    }
+    files.insert(TASK_FILE.to_string(), schema::serialize_tasks(tasks));
+
+    Ok(Backup {
+        created: Utc::now(),
+        files,
+    })
+}
+
+pub fn write_backup(backup: &Backup, path: &Path) -> io::Result<()> {
+    let serialized = serde_json::to_string_pretty(backup).expect("Failed to serialize backup.");
+    let content = crypto::encode(serialized).map_err(io::Error::other)?;
+    fs::write(path, content)
+}
+
+/// Reads a backup and checks that it can be restored before anything is overwritten
+pub fn read_backup(path: &Path) -> Result<Backup, String> {
+    let content = crypto::decode(fs::read(path).map_err(|e| e.to_string())?)?;
+    let backup: Backup = serde_json::from_str(&content).map_err(|e| format!("not a backup file: {}", e))?;
+    for name in backup.files.keys() {
+        if name.contains('/') || name.contains('\\') || name == ".." {
+            return Err(format!("invalid file name {} in backup", name));
+        }
+    }
+    backup.tasks()?;
+    Ok(backup)
+}
+
+/// Writes all files of the backup except the task file into the data directory.
+/// The tasks are saved by the caller through the storage, which holds the task file open and locked.
+pub fn restore_files(backup: &Backup, data_dir: &Path) -> io::Result<()> {
+    for (name, content) in &backup.files {
+        if name == TASK_FILE || name == ARCHIVE_FILE || name == JOURNAL_FILE {
+            continue;
+        }
+        fs::write(data_dir.join(name), content)?;
+    }
+    Ok(())
+}
+
+// Directory inside the data directory holding the automatic daily backups
+const AUTO_BACKUP_DIR: &str = "backups";
+const AUTO_BACKUP_COUNT: usize = 7;
+
+fn auto_backups(data_dir: &Path) -> Vec<PathBuf> {
+    let mut backups: Vec<PathBuf> = match fs::read_dir(data_dir.join(AUTO_BACKUP_DIR)) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    // Backups are named by date, so sorting by name sorts them chronologically
+    backups.sort();
+    backups
+}
+
+/// Writes at most one backup per day into the backup directory and removes the oldest ones
+pub fn auto_backup(data_dir: &Path, tasks: &[Task]) -> io::Result<()> {
+    let backup_dir = data_dir.join(AUTO_BACKUP_DIR);
+    let path = backup_dir.join(format!("{}.json", Utc::now().format("%Y-%m-%d")));
+    if path.exists() {
+        return Ok(());
+    }
+    fs::create_dir_all(&backup_dir)?;
+    write_backup(&create_backup(data_dir, tasks)?, &path)?;
+
+    let backups = auto_backups(data_dir);
+    if backups.len() > AUTO_BACKUP_COUNT {
+        for old in &backups[..backups.len() - AUTO_BACKUP_COUNT] {
+            fs::remove_file(old)?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns the most recent automatic backup that can be restored
+pub fn latest_auto_backup(data_dir: &Path) -> Option<Backup> {
+    auto_backups(data_dir)
+        .iter()
+        .rev()
+        .find_map(|path| read_backup(path).ok())
+}
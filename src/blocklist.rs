@@ -0,0 +1,97 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::config::{expand_home, BlocklistConfig};
+use crate::event::Event;
+use crate::{shell_command, Task};
+
+// Lines pt manages in the hosts file, everything outside them is kept
+const BEGIN_MARKER: &str = "# BEGIN pt blocklist";
+const END_MARKER: &str = "# END pt blocklist";
+
+// Replaces the section of pt in the hosts file, or removes it without sites
+fn write_hosts(path: &Path, sites: &[String]) -> Result<(), String> {
+    let content = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let mut lines: Vec<&str> = Vec::new();
+    let mut inside = false;
+    for line in content.lines() {
+        match line.trim() {
+            BEGIN_MARKER => inside = true,
+            END_MARKER => inside = false,
+            _ if !inside => lines.push(line),
+            _ => (),
+        }
+    }
+    let mut content = lines.join("\n");
+    content.push('\n');
+    if !sites.is_empty() {
+        content.push_str(BEGIN_MARKER);
+        content.push('\n');
+        for site in sites {
+            // Blocking example.com also blocks www.example.com, which most sites redirect to
+            content.push_str(&format!("0.0.0.0 {}\n::1 {}\n", site, site));
+            if !site.starts_with("www.") {
+                content.push_str(&format!("0.0.0.0 www.{}\n::1 www.{}\n", site, site));
+            }
+        }
+        content.push_str(END_MARKER);
+        content.push('\n');
+    }
+    replace_file(path, &content).map_err(|e| e.to_string())
+}
+
+// Writes a temporary file next to the hosts file and renames it over, so name resolution never sees
+// a half written file. The directory of /etc/hosts is usually only writable by root, in that case
+// the file is overwritten in place.
+fn replace_file(path: &Path, content: &str) -> io::Result<()> {
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".pt-tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+    let permissions = fs::metadata(path)?.permissions();
+    match fs::write(&tmp_path, content) {
+        Ok(_) => (),
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => return fs::write(path, content),
+        Err(e) => return Err(e),
+    }
+    fs::set_permissions(&tmp_path, permissions)
+        .and_then(|_| fs::rename(&tmp_path, path))
+        .inspect_err(|_| { let _ = fs::remove_file(&tmp_path); })
+}
+
+// Blocks the sites, or gives access to them again
+fn set(config: &BlocklistConfig, block: bool) {
+    let action = if block { "block" } else { "unblock" };
+    if let Some(path) = &config.hosts_file {
+        let path = expand_home(path);
+        let sites = if block { config.sites.as_slice() } else { &[] };
+        if let Err(e) = write_hosts(&path, sites) {
+            println!("Failed to {} sites in {}: {}", action, path.display(), e);
+        }
+    }
+    let command = if block { &config.block_command } else { &config.unblock_command };
+    if let Some(command) = command {
+        let mut command = shell_command(command);
+        command.env("PT_SITES", config.sites.join(" "));
+        match command.status() {
+            Ok(status) if status.success() => (),
+            Ok(status) => println!("Failed to {} sites: the command failed with {}.", action, status),
+            Err(e) => println!("Failed to {} sites: {}", action, e),
+        }
+    }
+}
+
+// Blocks the sites when a pomodoro starts and gives access to them again when the last running
+// pomodoro is finished or a break is started
+pub fn update(config: &BlocklistConfig, events: &[Event], tasks: &[Task], break_started: bool) {
+    if config.sites.is_empty() {
+        return;
+    }
+    let started = events.iter().any(|event| matches!(event, Event::PomodoroStarted { .. }));
+    let finished = events.iter().any(|event| matches!(event, Event::PomodoroFinished { .. }));
+    if started {
+        set(config, true);
+    } else if (finished || break_started) && !tasks.iter().any(|task| task.pomodoro_active()) {
+        set(config, false);
+    }
+}
@@ -0,0 +1,509 @@
+use std::fs;
+use std::path::Path;
+
+use chrono::{NaiveDate, Utc};
+use serde::{Serialize, Deserialize};
+
+use crate::config::CaldavConfig;
+use crate::crypto;
+use crate::{http, next_task_id, storage, Task};
+
+// Tasks are synced with the to-dos (VTODO) of a CalDAV task list, e.g. Nextcloud Tasks. The state
+// of each linked task as of the last sync is kept in this file, to tell which side changed a field.
+const STATE_FILE: &str = "caldav.json";
+const PRODID: &str = "-//pt//pt//EN";
+// Lines of iCalendar files are folded after this many bytes
+const MAX_LINE_LENGTH: usize = 75;
+const QUERY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<c:calendar-query xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+  <d:prop><d:getetag/><c:calendar-data/></d:prop>
+  <c:filter><c:comp-filter name="VCALENDAR"><c:comp-filter name="VTODO"/></c:comp-filter></c:filter>
+</c:calendar-query>"#;
+
+#[derive(Clone, Copy, PartialEq)]
+#[derive(Serialize)]
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Status {
+    Open,
+    Completed,
+    // Archived tasks that are not done
+    Cancelled,
+}
+
+// Fields that are synced
+#[derive(Clone, PartialEq)]
+#[derive(Serialize)]
+#[derive(Deserialize)]
+struct Todo {
+    summary: String,
+    status: Status,
+    due: Option<NaiveDate>,
+}
+
+#[derive(Serialize)]
+#[derive(Deserialize)]
+struct Link {
+    task_id: u32,
+    uid: String,
+    href: String,
+    etag: Option<String>,
+    // Fields as of the last sync
+    base: Todo,
+}
+
+#[derive(Default)]
+#[derive(Serialize)]
+#[derive(Deserialize)]
+struct SyncState {
+    links: Vec<Link>,
+}
+
+struct Remote {
+    href: String,
+    etag: Option<String>,
+    uid: String,
+    todo: Todo,
+    calendar: String,
+}
+
+#[derive(Default)]
+pub struct SyncReport {
+    pub added_local: usize,
+    pub changed_local: usize,
+    pub added_remote: usize,
+    pub changed_remote: usize,
+}
+
+fn collection_url(config: &CaldavConfig, list_name: &str) -> Result<String, String> {
+    let url = config.url.as_ref().ok_or("No CalDAV URL configured. Set caldav.url in the config file.")?;
+    let url = if url.contains("{list}") {
+        url.replace("{list}", list_name)
+    } else if list_name == storage::DEFAULT_LIST {
+        url.clone()
+    } else {
+        return Err("To sync named task lists, caldav.url must contain {list}.".to_string());
+    };
+    Ok(format!("{}/", url.trim_end_matches('/')))
+}
+
+fn with_auth(request: ureq::Request, config: &CaldavConfig) -> ureq::Request {
+    match &config.username {
+        Some(username) => request.set("Authorization", &http::basic_auth(username, config.password.as_deref().unwrap_or(""))),
+        None => request,
+    }
+}
+
+// Hrefs in responses are usually absolute paths on the server
+fn resolve(collection: &str, href: &str) -> String {
+    if href.starts_with("http://") || href.starts_with("https://") {
+        return href.to_string();
+    }
+    let host_end = collection.find("://")
+        .and_then(|scheme_end| collection[scheme_end + 3..].find('/').map(|i| scheme_end + 3 + i))
+        .unwrap_or(collection.len());
+    match href.starts_with('/') {
+        true => format!("{}{}", &collection[..host_end], href),
+        false => format!("{}{}", collection, href),
+    }
+}
+
+// Contents of all elements with the given name, whatever their namespace prefix
+fn elements<'a>(xml: &'a str, name: &str) -> Vec<&'a str> {
+    let mut found = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+        let tag_end = match rest.find('>') {
+            Some(tag_end) => tag_end,
+            None => break,
+        };
+        let tag = &rest[..tag_end];
+        let tag_name = tag.split(|c: char| c.is_whitespace() || c == '/').next().unwrap_or("");
+        let local_name = tag_name.rsplit(':').next().unwrap_or("");
+        if local_name != name || tag.starts_with('/') || tag.ends_with('/') {
+            continue;
+        }
+        rest = &rest[tag_end + 1..];
+        let end = rest.find(&format!("</{}>", tag_name)).unwrap_or(rest.len());
+        found.push(&rest[..end]);
+        rest = &rest[end..];
+    }
+    found
+}
+
+fn unescape_xml(text: &str) -> String {
+    let text = text.trim();
+    if let Some(data) = text.strip_prefix("<![CDATA[").and_then(|text| text.strip_suffix("]]>")) {
+        return data.to_string();
+    }
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&#13;", "\r")
+        .replace("&#xD;", "\r")
+        .replace("&#10;", "\n")
+        .replace("&#xA;", "\n")
+        .replace("&amp;", "&")
+}
+
+fn unescape_text(value: &str) -> String {
+    let mut text = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => text.push('\n'),
+            Some(c) => text.push(c),
+            None => {},
+        }
+    }
+    text
+}
+
+fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\").replace(';', "\\;").replace(',', "\\,").replace('\n', "\\n")
+}
+
+fn unfold(calendar: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in calendar.lines().map(|line| line.trim_end_matches('\r')) {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continuation), Some(last)) => last.push_str(continuation),
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+fn fold(line: &str) -> String {
+    let mut folded = String::new();
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > MAX_LINE_LENGTH {
+            folded.push_str("\r\n ");
+            length = 1;
+        }
+        folded.push(c);
+        length += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
+
+// Splits a content line into its property name and value, leaving out the parameters
+fn property(line: &str) -> Option<(&str, &str)> {
+    let (head, value) = line.split_once(':')?;
+    Some((head.split(';').next()?, value))
+}
+
+// Properties of the to-do itself, not of nested components like alarms
+fn todo_properties(lines: &[String]) -> Vec<(&str, &str)> {
+    let mut depth = 0;
+    let mut in_todo = false;
+    let mut properties = Vec::new();
+    for line in lines {
+        match property(line) {
+            Some(("BEGIN", "VTODO")) if !in_todo => in_todo = true,
+            Some(("END", "VTODO")) if depth == 0 => in_todo = false,
+            Some(("BEGIN", _)) if in_todo => depth += 1,
+            Some(("END", _)) if in_todo => depth -= 1,
+            Some(property) if in_todo && depth == 0 => properties.push(property),
+            _ => {},
+        }
+    }
+    properties
+}
+
+fn parse_todo(calendar: &str) -> Option<(String, Todo)> {
+    let lines = unfold(calendar);
+    let properties = todo_properties(&lines);
+    let value = |name: &str| properties.iter().find(|(property, _)| *property == name).map(|(_, value)| *value);
+    let uid = value("UID")?.to_string();
+    let status = match value("STATUS") {
+        Some("COMPLETED") => Status::Completed,
+        Some("CANCELLED") => Status::Cancelled,
+        Some(_) => Status::Open,
+        None if value("COMPLETED").is_some() => Status::Completed,
+        None => Status::Open,
+    };
+    // Due times are dropped, pt only knows due dates
+    let due = value("DUE").and_then(|due| NaiveDate::parse_from_str(due.get(..8)?, "%Y%m%d").ok());
+    Some((uid, Todo { summary: unescape_text(value("SUMMARY").unwrap_or("")), status, due }))
+}
+
+fn timestamp() -> String {
+    Utc::now().format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+// Properties for the fields that differ from the old ones, all of them if there are none
+fn changed_properties(todo: &Todo, old: Option<&Todo>) -> (Vec<&'static str>, Vec<String>) {
+    let mut removed = vec!["DTSTAMP", "LAST-MODIFIED"];
+    let mut added = vec![format!("DTSTAMP:{}", timestamp()), format!("LAST-MODIFIED:{}", timestamp())];
+    if old.map(|old| old.summary != todo.summary).unwrap_or(true) {
+        removed.push("SUMMARY");
+        added.push(format!("SUMMARY:{}", escape_text(&todo.summary)));
+    }
+    if old.map(|old| old.status != todo.status).unwrap_or(true) {
+        removed.extend(["STATUS", "COMPLETED", "PERCENT-COMPLETE"]);
+        match todo.status {
+            Status::Open => added.push("STATUS:NEEDS-ACTION".to_string()),
+            Status::Completed => added.extend([
+                "STATUS:COMPLETED".to_string(),
+                format!("COMPLETED:{}", timestamp()),
+                "PERCENT-COMPLETE:100".to_string(),
+            ]),
+            Status::Cancelled => added.push("STATUS:CANCELLED".to_string()),
+        }
+    }
+    if old.map(|old| old.due != todo.due).unwrap_or(true) {
+        removed.push("DUE");
+        added.extend(todo.due.map(|due| format!("DUE;VALUE=DATE:{}", due.format("%Y%m%d"))));
+    }
+    (removed, added)
+}
+
+fn new_calendar(uid: &str, todo: &Todo) -> String {
+    let (_, properties) = changed_properties(todo, None);
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        format!("PRODID:{}", PRODID),
+        "BEGIN:VTODO".to_string(),
+        format!("UID:{}", uid),
+    ];
+    lines.extend(properties);
+    lines.extend(["END:VTODO".to_string(), "END:VCALENDAR".to_string()]);
+    lines.iter().map(|line| fold(line)).collect()
+}
+
+// Replaces the changed fields in the calendar data and keeps everything else, e.g. alarms or
+// notes added on the phone
+fn update_calendar(calendar: &str, old: &Todo, todo: &Todo) -> String {
+    let (removed, added) = changed_properties(todo, Some(old));
+    let mut lines = Vec::new();
+    let mut depth = 0;
+    let mut in_todo = false;
+    for line in unfold(calendar) {
+        match property(&line) {
+            Some(("BEGIN", "VTODO")) if !in_todo => in_todo = true,
+            Some(("END", "VTODO")) if in_todo && depth == 0 => {
+                in_todo = false;
+                lines.extend(added.iter().cloned());
+            },
+            Some(("BEGIN", _)) if in_todo => depth += 1,
+            Some(("END", _)) if in_todo => depth -= 1,
+            Some((name, _)) if in_todo && depth == 0 && removed.contains(&name) => continue,
+            _ => {},
+        }
+        lines.push(line);
+    }
+    lines.iter().map(|line| fold(line)).collect()
+}
+
+fn local_todo(task: &Task) -> Todo {
+    let status = match (task.done, task.archived) {
+        (true, _) => Status::Completed,
+        (false, true) => Status::Cancelled,
+        (false, false) => Status::Open,
+    };
+    Todo { summary: task.description.clone(), status, due: task.due }
+}
+
+fn apply(task: &mut Task, todo: &Todo) {
+    task.description = todo.summary.clone();
+    task.due = todo.due;
+    match todo.status {
+        Status::Open => {
+            task.done = false;
+            task.archived = false;
+        },
+        Status::Completed => task.done = true,
+        Status::Cancelled => {
+            task.done = false;
+            task.archived = true;
+        },
+    }
+}
+
+// Takes each field from the side that changed it, from pt if both did
+fn merge(base: &Todo, local: &Todo, remote: &Todo) -> Todo {
+    Todo {
+        summary: if local.summary != base.summary { local.summary.clone() } else { remote.summary.clone() },
+        status: if local.status != base.status { local.status } else { remote.status },
+        due: if local.due != base.due { local.due } else { remote.due },
+    }
+}
+
+fn fetch(config: &CaldavConfig, collection: &str) -> Result<Vec<Remote>, String> {
+    let response = with_auth(http::agent().request("REPORT", collection), config)
+        .set("Depth", "1")
+        .set("Content-Type", "application/xml; charset=utf-8")
+        .send_string(QUERY)
+        .map_err(http::error_message)?
+        .into_string()
+        .map_err(|e| e.to_string())?;
+    let mut todos = Vec::new();
+    for response in elements(&response, "response") {
+        let (href, calendar) = match (elements(response, "href").first(), elements(response, "calendar-data").first()) {
+            (Some(href), Some(calendar)) => (resolve(collection, &unescape_xml(href)), unescape_xml(calendar)),
+            _ => continue,
+        };
+        let etag = elements(response, "getetag").first().map(|etag| unescape_xml(etag));
+        if let Some((uid, todo)) = parse_todo(&calendar) {
+            todos.push(Remote { href, etag, uid, todo, calendar });
+        }
+    }
+    Ok(todos)
+}
+
+// Uploads a to-do, failing if it changed on the server since it was fetched. Returns the new ETag.
+fn put(config: &CaldavConfig, href: &str, calendar: &str, etag: Option<&str>) -> Result<Option<String>, String> {
+    let request = with_auth(http::agent().put(href), config)
+        .set("Content-Type", "text/calendar; charset=utf-8");
+    let request = match etag {
+        Some(etag) => request.set("If-Match", etag),
+        None => request.set("If-None-Match", "*"),
+    };
+    match request.send_string(calendar) {
+        Ok(response) => Ok(response.header("ETag").map(str::to_string)),
+        Err(ureq::Error::Status(412, _)) => Err(format!("{} changed during the sync, please try again", href)),
+        Err(e) => Err(http::error_message(e)),
+    }
+}
+
+fn delete(config: &CaldavConfig, href: &str, etag: Option<&str>) -> Result<(), String> {
+    let mut request = with_auth(http::agent().delete(href), config);
+    if let Some(etag) = etag {
+        request = request.set("If-Match", etag);
+    }
+    match request.call() {
+        Ok(_) | Err(ureq::Error::Status(404, _)) => Ok(()),
+        Err(e) => Err(http::error_message(e)),
+    }
+}
+
+fn load_state(list_dir: &Path) -> SyncState {
+    fs::read(list_dir.join(STATE_FILE))
+        .ok()
+        .and_then(|content| crypto::decode(content).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+// The state includes the summaries of the tasks, so it is encrypted like the task file
+fn save_state(list_dir: &Path, state: &SyncState) -> Result<(), String> {
+    let content = crypto::encode(serde_json::to_string(state).expect("Failed to serialize CalDAV sync state."))?;
+    fs::write(list_dir.join(STATE_FILE), content).map_err(|e| e.to_string())
+}
+
+// Syncs a task that was linked before, returns false if the link is gone
+fn sync_link(config: &CaldavConfig, link: &mut Link, remotes: &mut Vec<Remote>, tasks: &mut [Task], report: &mut SyncReport) -> Result<bool, String> {
+    let remote = remotes.iter().position(|remote| remote.uid == link.uid).map(|i| remotes.remove(i));
+    let task = tasks.iter_mut().find(|task| task.id == link.task_id);
+    let (remote, task) = match (remote, task) {
+        (Some(remote), Some(task)) => (remote, task),
+        (None, Some(task)) => {
+            if !task.archived {
+                task.archived = true;
+                println!("Task {} was deleted on the CalDAV server and moved to archive.", task.id);
+                report.changed_local += 1;
+            }
+            return Ok(false);
+        },
+        (Some(remote), None) => {
+            delete(config, &remote.href, remote.etag.as_deref())?;
+            report.changed_remote += 1;
+            return Ok(false);
+        },
+        (None, None) => return Ok(false),
+    };
+    let local = local_todo(task);
+    let merged = merge(&link.base, &local, &remote.todo);
+    if merged != local {
+        apply(task, &merged);
+        report.changed_local += 1;
+    }
+    link.etag = match merged != remote.todo {
+        true => {
+            let calendar = update_calendar(&remote.calendar, &remote.todo, &merged);
+            report.changed_remote += 1;
+            put(config, &remote.href, &calendar, remote.etag.as_deref())?
+        },
+        false => remote.etag,
+    };
+    link.href = remote.href;
+    link.base = merged;
+    Ok(true)
+}
+
+fn sync_links(config: &CaldavConfig, collection: &str, list_name: &str, state: &mut SyncState, tasks: &mut Vec<Task>, last_id: u32, report: &mut SyncReport) -> Result<(), String> {
+    let mut remotes = fetch(config, collection)?;
+
+    let mut links = std::mem::take(&mut state.links).into_iter();
+    while let Some(mut link) = links.next() {
+        match sync_link(config, &mut link, &mut remotes, tasks, report) {
+            Ok(true) => state.links.push(link),
+            Ok(false) => {},
+            Err(e) => {
+                // The links that weren't synced yet are kept for the next sync
+                state.links.push(link);
+                state.links.extend(links);
+                return Err(e);
+            },
+        }
+    }
+
+    // To-dos added on the server
+    for remote in remotes {
+        // A task uploaded by an earlier sync that failed before saving its link
+        let uploaded = tasks.iter().find(|task| uid(list_name, task) == remote.uid && task.description == remote.todo.summary
+            && !state.links.iter().any(|link| link.task_id == task.id));
+        let task_id = match uploaded {
+            Some(task) => task.id,
+            None => {
+                let mut task = Task::new(next_task_id(tasks, last_id), String::new());
+                apply(&mut task, &remote.todo);
+                println!("Task {} added from the CalDAV server.", task.id);
+                report.added_local += 1;
+                let task_id = task.id;
+                tasks.push(task);
+                task_id
+            },
+        };
+        state.links.push(Link { task_id, uid: remote.uid, href: remote.href, etag: remote.etag, base: remote.todo });
+    }
+
+    // Tasks added in pt. Archived tasks are left out, to not upload the whole history on the first sync.
+    for task in tasks.iter().filter(|task| !task.archived) {
+        if state.links.iter().any(|link| link.task_id == task.id) {
+            continue;
+        }
+        let uid = uid(list_name, task);
+        let href = format!("{}{}.ics", collection, http::percent_encode(&uid));
+        let todo = local_todo(task);
+        let etag = put(config, &href, &new_calendar(&uid, &todo), None)?;
+        state.links.push(Link { task_id: task.id, uid, href, etag, base: todo });
+        report.added_remote += 1;
+    }
+    Ok(())
+}
+
+fn uid(list_name: &str, task: &Task) -> String {
+    format!("pt-{}-{}", list_name, task.id)
+}
+
+// Synchronizes the tasks with the to-dos of the CalDAV task list in both directions. The sync state
+// is saved even if it fails halfway, so the tasks have to be saved as well.
+pub fn sync(config: &CaldavConfig, list_name: &str, list_dir: &Path, tasks: &mut Vec<Task>, last_id: u32) -> Result<SyncReport, String> {
+    let collection = collection_url(config, list_name)?;
+    let mut state = load_state(list_dir);
+    let mut report = SyncReport::default();
+    let result = sync_links(config, &collection, list_name, &mut state, tasks, last_id, &mut report);
+    save_state(list_dir, &state)?;
+    result.map(|_| report)
+}
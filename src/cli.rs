@@ -0,0 +1,123 @@
+use clap::{Parser, Subcommand};
+use clap::error::ErrorKind;
+
+// The subcommands like pt start 3. They are shortcuts for the most common commands and are
+// translated to their flags, e.g. --pomodoro 3, which main handles like all other commands.
+#[derive(Parser)]
+#[command(name = "pt", about = "Track tasks and pomodoros", disable_version_flag = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Add a new task, e.g. pt add Write the report
+    Add {
+        /// Ask for the description, projects, tags, estimate, due date and priority one after the other
+        #[arg(short, long)]
+        interactive: bool,
+        #[arg(required_unless_present = "interactive", trailing_var_arg = true, allow_hyphen_values = true)]
+        description: Vec<String>,
+    },
+    /// Start a pomodoro for the tasks, or pick the task by its description
+    Start {
+        #[arg(value_name = "TASK_ID")]
+        ids: Vec<u32>,
+    },
+    /// Finish the running pomodoro of the tasks, or pick the task by its description
+    Finish {
+        #[arg(value_name = "TASK_ID")]
+        ids: Vec<u32>,
+    },
+    /// Check the tasks, or pick the task by its description
+    Done {
+        #[arg(value_name = "TASK_ID")]
+        ids: Vec<u32>,
+    },
+    /// Set the due date of a task, or remove it without a date
+    Due {
+        #[arg(value_name = "TASK_ID")]
+        id: u32,
+        /// Due date, e.g. tomorrow, friday, in 2 weeks or 2024-03-01
+        #[arg(trailing_var_arg = true)]
+        date: Vec<String>,
+    },
+    /// List the tasks
+    List {
+        /// List the archived tasks instead
+        #[arg(long)]
+        archived: bool,
+        /// Template of the lines, e.g. "{id} {status} {description} {time_today}"
+        #[arg(long)]
+        format: Option<String>,
+    },
+    /// Check the task files, config, notifications and sound and suggest fixes for problems
+    Doctor,
+    /// Print the pomodoros of a day per task
+    Report {
+        /// Day of the report, e.g. yesterday, last friday or 2024-03-01 (default: today)
+        #[arg(long)]
+        date: Option<String>,
+    },
+}
+
+const SUBCOMMANDS: &[&str] = &["add", "start", "finish", "done", "due", "list", "doctor", "report", "help"];
+
+// Whether the arguments are a task description that starts with the name of a subcommand. Options
+// like --date and pt add without a description still get the error of clap.
+fn is_description(args: &[String], error: &clap::Error) -> bool {
+    let help = matches!(error.kind(), ErrorKind::DisplayHelp | ErrorKind::DisplayHelpOnMissingArgumentOrSubcommand | ErrorKind::DisplayVersion);
+    !help && args.len() > 2 && args[1] != "add" && !args[2..].iter().any(|arg| arg.starts_with('-'))
+}
+
+fn flag_with_ids(flag: &str, ids: Vec<u32>) -> Vec<String> {
+    let mut args = vec![flag.to_string()];
+    args.extend(ids.iter().map(u32::to_string));
+    args
+}
+
+// Replaces a subcommand after the global options with its flag. Other arguments are returned
+// unchanged, as are words that don't fit the subcommand, so pt list groceries still adds the task
+// "list groceries" like before there were subcommands. Fails with the help or the error message of
+// clap.
+pub fn translate(args: Vec<String>) -> Result<Vec<String>, clap::Error> {
+    if !args.get(1).is_some_and(|arg| SUBCOMMANDS.contains(&arg.as_str())) {
+        return Ok(args);
+    }
+    let cli = match Cli::try_parse_from(&args) {
+        Ok(cli) => cli,
+        Err(e) if is_description(&args, &e) => return Ok(args),
+        Err(e) => return Err(e),
+    };
+    let flags = match cli.command {
+        Command::Add { interactive, description } => {
+            let flag = if interactive { "--add-interactive" } else { "--add" };
+            let mut flags = vec![flag.to_string()];
+            flags.extend(description);
+            flags
+        },
+        Command::Start { ids } => flag_with_ids("--pomodoro", ids),
+        Command::Finish { ids } => flag_with_ids("--finish-pomodoro", ids),
+        Command::Done { ids } => flag_with_ids("--check", ids),
+        Command::Due { id, date } => {
+            let mut flags = vec!["--due".to_string(), id.to_string()];
+            flags.extend(date);
+            flags
+        },
+        Command::List { archived, format } => {
+            let mut flags = vec![if archived { "--list-archived" } else { "--list" }.to_string()];
+            if let Some(format) = format {
+                flags.extend(["--format".to_string(), format]);
+            }
+            flags
+        },
+        Command::Doctor => vec!["--doctor".to_string()],
+        Command::Report { date } => {
+            let mut flags = vec!["--report".to_string()];
+            flags.extend(date);
+            flags
+        },
+    };
+    Ok(args.into_iter().take(1).chain(flags).collect())
+}
@@ -0,0 +1,62 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::json;
+
+use crate::config::ClockifyConfig;
+use crate::http;
+use crate::logged::Logged;
+use crate::Task;
+
+// Remembers the pomodoros that became time entries, so none is added twice
+const CLOCKIFY_STATE_FILE: &str = "clockify.json";
+
+// Clockify ID mapped to the first project or tag of the task that has one
+fn mapped<'a>(map: &'a HashMap<String, String>, task: &Task) -> Option<&'a String> {
+    task.projects.iter().chain(&task.tags).find_map(|name| map.get(name))
+}
+
+fn add_time_entry(config: &ClockifyConfig, api_key: &str, workspace: &str, task: &Task, start_time: DateTime<Utc>, end_time: DateTime<Utc>) -> Result<String, String> {
+    let url = format!("{}/workspaces/{}/time-entries", config.api_url.trim_end_matches('/'), workspace);
+    let body = json!({
+        "start": start_time.to_rfc3339_opts(SecondsFormat::Secs, true),
+        "end": end_time.to_rfc3339_opts(SecondsFormat::Secs, true),
+        "description": task.description,
+        "projectId": mapped(&config.projects, task),
+        "taskId": mapped(&config.tasks, task),
+    });
+    let response: serde_json::Value = http::agent().post(&url)
+        .set("X-Api-Key", api_key)
+        .send_json(body)
+        .map_err(http::error_message)?
+        .into_json()
+        .map_err(|e| e.to_string())?;
+    response["id"].as_str().map(str::to_string).ok_or("Clockify returned no time entry ID".to_string())
+}
+
+// Adds a time entry for each finished pomodoro that wasn't added yet, returns their number
+pub fn push(config: &ClockifyConfig, list_dir: &Path, tasks: &[Task]) -> Result<usize, String> {
+    let api_key = config.api_key.as_ref().ok_or("No Clockify API key configured. Set clockify.api_key in the config file.")?;
+    let workspace = config.workspace_id.as_ref().ok_or("No Clockify workspace configured. Set clockify.workspace_id in the config file.")?;
+    let mut logged = Logged::load(list_dir, CLOCKIFY_STATE_FILE);
+    let mut count = 0;
+    for task in tasks {
+        for (pomodoro, end_time) in logged.pending(task) {
+            if config.since.is_some_and(|since| pomodoro.start_time.date_naive() < since) {
+                continue;
+            }
+            match add_time_entry(config, api_key, workspace, task, pomodoro.start_time, end_time) {
+                Ok(id) => logged.add(task, pomodoro, Some(id)),
+                Err(e) => {
+                    // Keep what was added so far
+                    logged.save()?;
+                    return Err(format!("failed to add a time entry for task {}: {}", task.id, e));
+                }
+            }
+            count += 1;
+        }
+    }
+    logged.save()?;
+    Ok(count)
+}
@@ -0,0 +1,88 @@
+use std::env;
+use std::io::{self, IsTerminal};
+use std::sync::OnceLock;
+
+use chrono::Local;
+
+use crate::config::{ColorMode, ThemeConfig};
+use crate::Task;
+
+// SGR parameters of the styles, e.g. "1;33", or None if the output has no colors
+struct Theme {
+    done: String,
+    active: String,
+    overdue: String,
+}
+
+static THEME: OnceLock<Option<Theme>> = OnceLock::new();
+
+fn code(word: &str) -> Option<u8> {
+    let colors = ["black", "red", "green", "yellow", "blue", "magenta", "cyan", "white"];
+    if let Some(index) = colors.iter().position(|color| *color == word) {
+        return Some(30 + index as u8);
+    }
+    if let Some(index) = colors.iter().position(|color| Some(*color) == word.strip_prefix("bright_")) {
+        return Some(90 + index as u8);
+    }
+    match word {
+        "bold" => Some(1),
+        "dim" => Some(2),
+        "italic" => Some(3),
+        "underline" => Some(4),
+        _ => None,
+    }
+}
+
+fn parameters(style: &str) -> String {
+    let mut codes = Vec::new();
+    for word in style.split_whitespace() {
+        match code(word) {
+            Some(code) => codes.push(code.to_string()),
+            None => println!("Unknown style {} in the theme, it is ignored.", word),
+        }
+    }
+    codes.join(";")
+}
+
+// NO_COLOR is honored when it is set to anything but an empty string, see no-color.org
+fn enabled(mode: ColorMode) -> bool {
+    match mode {
+        ColorMode::Always => true,
+        ColorMode::Never => false,
+        ColorMode::Auto => {
+            env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+                && env::var("TERM").map_or(true, |term| term != "dumb")
+                && io::stdout().is_terminal()
+        },
+    }
+}
+
+pub fn init(config: &ThemeConfig) {
+    THEME.get_or_init(|| enabled(config.color).then(|| Theme {
+        done: parameters(&config.done),
+        active: parameters(&config.active),
+        overdue: parameters(&config.overdue),
+    }));
+}
+
+// Colors the line of the task in the task list by its state
+pub fn task(task: &Task, line: String) -> String {
+    let theme = match THEME.get() {
+        Some(Some(theme)) => theme,
+        _ => return line,
+    };
+    let overdue = task.due.is_some_and(|due| due < Local::now().date_naive());
+    let style = if task.pomodoro_active() {
+        &theme.active
+    } else if task.done {
+        &theme.done
+    } else if overdue {
+        &theme.overdue
+    } else {
+        return line;
+    };
+    match style.is_empty() {
+        true => line,
+        false => format!("\x1b[{}m{}\x1b[0m", style, line),
+    }
+}
@@ -0,0 +1,91 @@
+use chrono::Duration;
+
+use crate::{Pomodoro, Task};
+
+// Entries shorter than this are merged with a neighbouring entry if there is no longer gap between them
+const TINY_ENTRY_SECONDS: i64 = 60;
+
+#[derive(Default)]
+pub struct CompactReport {
+    pub zero_length: usize,
+    pub merged: usize,
+    pub duplicate_pomodoros: usize,
+    pub duplicate_tasks: usize,
+}
+
+impl CompactReport {
+    pub fn total(&self) -> usize {
+        self.zero_length + self.merged + self.duplicate_pomodoros + self.duplicate_tasks
+    }
+}
+
+fn is_tiny(pomodoro: &Pomodoro) -> bool {
+    match pomodoro.end_time {
+        Some(end_time) => end_time - pomodoro.start_time < Duration::seconds(TINY_ENTRY_SECONDS),
+        None => false,
+    }
+}
+
+fn compact_pomodoros(pomodoros: &mut Vec<Pomodoro>, report: &mut CompactReport) {
+    let before = pomodoros.len();
+    pomodoros.retain(|p| p.end_time.is_none_or(|end_time| end_time > p.start_time));
+    report.zero_length += before - pomodoros.len();
+
+    // Sync mishaps can leave the same pomodoro twice
+    let mut unique: Vec<Pomodoro> = Vec::new();
+    for pomodoro in pomodoros.drain(..) {
+        if unique.contains(&pomodoro) {
+            report.duplicate_pomodoros += 1;
+        } else {
+            unique.push(pomodoro);
+        }
+    }
+
+    let mut merged: Vec<Pomodoro> = Vec::new();
+    for pomodoro in unique {
+        if let Some(previous) = merged.last_mut() {
+            if let Some(previous_end) = previous.end_time {
+                let gap = pomodoro.start_time - previous_end;
+                let adjacent = gap >= Duration::zero() && gap < Duration::seconds(TINY_ENTRY_SECONDS);
+                if adjacent && pomodoro.end_time.is_some() && (is_tiny(previous) || is_tiny(&pomodoro)) {
+                    previous.end_time = pomodoro.end_time;
+                    report.merged += 1;
+                    continue;
+                }
+            }
+        }
+        merged.push(pomodoro);
+    }
+    *pomodoros = merged;
+}
+
+// Tasks that only differ in their ID are duplicates, e.g. from importing or syncing twice
+fn same_task(a: &Task, b: &Task) -> bool {
+    a.description == b.description
+        && a.done == b.done
+        && a.archived == b.archived
+        && a.pomodoros == b.pomodoros
+        && a.priority == b.priority
+        && a.projects == b.projects
+        && a.tags == b.tags
+        && a.due == b.due
+}
+
+// Cleans up the tracked time of all tasks and removes duplicate tasks
+pub fn compact(tasks: &mut Vec<Task>) -> CompactReport {
+    let mut report = CompactReport::default();
+    for task in tasks.iter_mut() {
+        compact_pomodoros(&mut task.pomodoros, &mut report);
+    }
+
+    let mut unique: Vec<Task> = Vec::new();
+    for task in tasks.drain(..) {
+        if unique.iter().any(|other| same_task(other, &task)) {
+            report.duplicate_tasks += 1;
+        } else {
+            unique.push(task);
+        }
+    }
+    *tasks = unique;
+    report
+}
@@ -0,0 +1,698 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::{NaiveDate, NaiveTime, Weekday};
+use serde::Deserialize;
+
+use crate::error::Error;
+
+/// File in the data directory the settings are read from
+pub const CONFIG_FILE: &str = "config.json";
+
+#[derive(Clone, Copy, PartialEq)]
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageMode {
+    // All tasks are rewritten to tasks.json on every change
+    Json,
+    // Changes are appended to journal.jsonl, from which the tasks are reconstructed
+    Journal,
+}
+
+#[derive(Default)]
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct GitConfig {
+    /// Commit the data directory after every change
+    pub enabled: bool,
+    /// Push after every commit
+    pub push: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct S3Config {
+    /// Requests are signed for S3 compatible object storage if an access key is set
+    pub access_key: Option<String>,
+    pub secret_key: Option<String>,
+    pub region: String,
+}
+
+impl Default for S3Config {
+    fn default() -> S3Config {
+        S3Config {
+            access_key: None,
+            secret_key: None,
+            region: "us-east-1".to_string(),
+        }
+    }
+}
+
+#[derive(Default)]
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct SyncConfig {
+    /// URL of the remote task file, e.g. on a WebDAV server. {list} is replaced by the name of the task list.
+    pub url: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub s3: S3Config,
+}
+
+#[derive(Default)]
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct MarkdownConfig {
+    /// Markdown file the active tasks are mirrored to, e.g. in an Obsidian vault. {list} is replaced
+    /// by the name of the task list.
+    pub path: Option<String>,
+}
+
+#[derive(Clone, Copy)]
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PushService {
+    Ntfy,
+    Pushbullet,
+    // Sent by the bot of telegram.token to telegram.chat_id
+    Telegram,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct PushConfig {
+    /// Service that notifications are pushed to the phone with, off without it
+    pub service: Option<PushService>,
+    /// ntfy server, for self-hosted instances
+    pub url: String,
+    /// ntfy topic the phone is subscribed to
+    pub topic: Option<String>,
+    /// ntfy access token for protected topics, or the Pushbullet access token
+    pub token: Option<String>,
+    /// Kinds of notifications that are pushed: start, pomodoro, break and due
+    pub events: Vec<String>,
+}
+
+impl Default for PushConfig {
+    fn default() -> PushConfig {
+        PushConfig {
+            service: None,
+            url: "https://ntfy.sh".to_string(),
+            topic: None,
+            token: None,
+            events: vec!["pomodoro".to_string(), "due".to_string()],
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Urgency {
+    Low,
+    Normal,
+    // Stays open until it is closed on most desktops
+    Critical,
+}
+
+/// How the notification of one kind of event is displayed
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct EventNotificationConfig {
+    /// Seconds until the notification closes itself, 0 keeps it open until it is closed
+    pub timeout: u32,
+    pub urgency: Urgency,
+    /// Keep the notification after one of its buttons was clicked (Linux only)
+    pub resident: bool,
+    /// Templates for the text of the notification, placeholders like {description} are replaced
+    pub title: Option<String>,
+    pub body: Option<String>,
+}
+
+impl Default for EventNotificationConfig {
+    fn default() -> EventNotificationConfig {
+        EventNotificationConfig {
+            timeout: 0,
+            urgency: Urgency::Normal,
+            resident: false,
+            title: None,
+            body: None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct NotificationConfig {
+    /// Show notifications on the desktop, otherwise they are only printed, e.g. over SSH
+    pub desktop: bool,
+    /// Add buttons to start a break, start the next pomodoro or check the task to finished pomodoro
+    /// notifications. pt --notify then waits until they are clicked or closed.
+    pub actions: bool,
+    /// Show a notification when a pomodoro starts, e.g. from a keyboard shortcut
+    pub on_start: bool,
+    /// Repeat the alert of a finished pomodoro every this many minutes until it is acknowledged, 0 to disable
+    pub nag_minutes: u32,
+    /// Seconds in which a notification identical to one shown before is dropped
+    pub repeat_seconds: u32,
+    /// Keep a notification with the remaining time of the running pomodoro up to date
+    pub progress: bool,
+    /// Started pomodoros
+    pub start: EventNotificationConfig,
+    /// Finished pomodoros
+    pub pomodoro: EventNotificationConfig,
+    /// Finished breaks
+    #[serde(rename = "break")]
+    pub break_finished: EventNotificationConfig,
+    /// Remind of active tasks that are due once a day
+    pub due_reminders: bool,
+    /// Days before the due date from which reminders are shown, 0 starts on the due date
+    pub due_lead_days: i64,
+    /// Due date reminders
+    pub due: EventNotificationConfig,
+}
+
+impl Default for NotificationConfig {
+    fn default() -> NotificationConfig {
+        NotificationConfig {
+            desktop: true,
+            actions: false,
+            on_start: false,
+            progress: false,
+            nag_minutes: 0,
+            repeat_seconds: 20,
+            start: EventNotificationConfig::default(),
+            pomodoro: EventNotificationConfig::default(),
+            break_finished: EventNotificationConfig::default(),
+            due_reminders: true,
+            due_lead_days: 0,
+            due: EventNotificationConfig::default(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct SoundConfig {
+    /// Sound file played when a pomodoro ends, a bundled sound is used if it is not set
+    pub alarm: Option<String>,
+    /// Sound file played when a break ends, the alarm is used if it is not set
+    pub break_alarm: Option<String>,
+    /// Sound file played for due date reminders, the alarm is used if it is not set
+    pub due_alarm: Option<String>,
+    /// Volume in percent of the sound file's volume
+    pub volume: u32,
+    /// Never play sounds
+    pub silent: bool,
+    /// Read notifications aloud after the alarm
+    pub speak: bool,
+    /// Text-to-speech program and its arguments, the text is appended. espeak, or say on macOS, by default.
+    pub speech_command: Option<String>,
+}
+
+impl Default for SoundConfig {
+    fn default() -> SoundConfig {
+        SoundConfig {
+            alarm: None,
+            break_alarm: None,
+            due_alarm: None,
+            volume: 100,
+            silent: false,
+            speak: false,
+            speech_command: None,
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct EncryptionConfig {
+    /// Encrypt the task files with a passphrase
+    pub enabled: bool,
+    /// Command printing the passphrase, e.g. to read it from a keyring. Without it, the passphrase is
+    /// read from PT_PASSPHRASE or prompted for.
+    pub passphrase_command: Option<String>,
+}
+
+#[derive(Clone, Copy)]
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DndBackend {
+    Gnome,
+    Kde,
+    Dunst,
+    Mako,
+}
+
+#[derive(Default)]
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct DndConfig {
+    /// Desktop whose do-not-disturb mode is enabled while a pomodoro runs
+    pub backend: Option<DndBackend>,
+    /// Shell commands to enable and disable do-not-disturb on other desktops
+    pub on_command: Option<String>,
+    pub off_command: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorMode {
+    // Colors unless NO_COLOR is set or the output is not a terminal
+    Auto,
+    Always,
+    Never,
+}
+
+/// Styles of the task list, e.g. "bold yellow" or "dim". Colors are black, red, green, yellow,
+/// blue, magenta, cyan and white, also with bright_ in front.
+#[derive(Clone)]
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct ThemeConfig {
+    pub color: ColorMode,
+    /// Checked tasks
+    pub done: String,
+    /// Tasks with a running pomodoro
+    pub active: String,
+    /// Unchecked tasks past their due date
+    pub overdue: String,
+}
+
+impl Default for ThemeConfig {
+    fn default() -> ThemeConfig {
+        ThemeConfig {
+            color: ColorMode::Auto,
+            done: "dim".to_string(),
+            active: "bold yellow".to_string(),
+            overdue: "red".to_string(),
+        }
+    }
+}
+
+#[derive(Default)]
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct ListConfig {
+    /// Show dates and times in the task list instead of e.g. "12m ago" or "due in 3d"
+    pub absolute_times: bool,
+    /// Template of the lines of the task list, e.g. "{id} {status} {description} {time_today}"
+    pub format: Option<String>,
+}
+
+#[derive(Default)]
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct MediaConfig {
+    /// Pause media players when a pomodoro starts and resume them on the break (Linux only)
+    pub enabled: bool,
+}
+
+#[derive(Default)]
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct BlocklistConfig {
+    /// Domains that are blocked while a pomodoro runs, blocking is off without any
+    pub sites: Vec<String>,
+    /// Hosts file that pt adds the sites to, e.g. /etc/hosts if pt may write it
+    pub hosts_file: Option<String>,
+    /// Shell commands that block and unblock the sites instead, e.g. for a proxy. The sites are in PT_SITES.
+    pub block_command: Option<String>,
+    pub unblock_command: Option<String>,
+}
+
+#[derive(Clone, Copy)]
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpSecurity {
+    // TLS from the start, usually on port 465
+    Tls,
+    // Upgrade to TLS after connecting, usually on port 587
+    Starttls,
+    // Unencrypted, only for a relay on the same machine
+    None,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct EmailConfig {
+    /// SMTP server, e.g. smtp.example.com
+    pub server: Option<String>,
+    pub port: u16,
+    pub security: SmtpSecurity,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub from: Option<String>,
+    /// Recipient, the sender if it is not set
+    pub to: Option<String>,
+    /// Minutes after which pt --email check reports a pomodoro that is still running
+    pub unfinished_minutes: i64,
+}
+
+impl Default for EmailConfig {
+    fn default() -> EmailConfig {
+        EmailConfig {
+            server: None,
+            port: 465,
+            security: SmtpSecurity::Tls,
+            username: None,
+            password: None,
+            from: None,
+            to: None,
+            unfinished_minutes: 60,
+        }
+    }
+}
+
+/// Time window in which sounds are suppressed and notifications have low urgency
+#[derive(Deserialize)]
+pub struct QuietHours {
+    /// Local times like "22:00", the window may span midnight. Equal times cover the whole day.
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+    /// Weekdays like "sat" on which the window starts, all days if empty
+    #[serde(default)]
+    pub days: Vec<Weekday>,
+}
+
+#[derive(Deserialize)]
+pub struct WebhookConfig {
+    pub url: String,
+    /// Names of the events sent to the URL, e.g. pomodoro_started, pomodoro_finished or task_checked.
+    /// All if empty.
+    #[serde(default)]
+    pub events: Vec<String>,
+}
+
+#[derive(Default)]
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct PluginsConfig {
+    /// Names of plugins that get the events of every command, e.g. "waybar" for pt-waybar
+    pub listeners: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct ServerConfig {
+    /// Address pt --serve listens on, 0.0.0.0 makes it reachable from other devices
+    pub address: String,
+    pub port: u16,
+    /// Requests have to send it as `Authorization: Bearer <token>` if it is set
+    pub token: Option<String>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> ServerConfig {
+        ServerConfig {
+            address: "127.0.0.1".to_string(),
+            port: 7878,
+            token: None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct DiscordConfig {
+    /// ID of a Discord application, its name is shown as the game. Rich Presence is off without it.
+    pub client_id: Option<String>,
+    /// Show the description of the task, otherwise just "Focusing"
+    pub show_description: bool,
+}
+
+impl Default for DiscordConfig {
+    fn default() -> DiscordConfig {
+        DiscordConfig {
+            client_id: None,
+            show_description: true,
+        }
+    }
+}
+
+#[derive(Default)]
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct TelegramConfig {
+    /// Token of the bot from @BotFather
+    pub token: Option<String>,
+    /// Chat the bot answers and sends alerts to, pt --telegram prints it for new chats
+    pub chat_id: Option<i64>,
+}
+
+#[derive(Default)]
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct JiraConfig {
+    /// Base URL of the Jira site, e.g. <https://example.atlassian.net>
+    pub url: Option<String>,
+    /// Account email for Jira Cloud, which needs an API token. Without it, the token is sent as a
+    /// personal access token, like Jira Server expects.
+    pub username: Option<String>,
+    pub token: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct GithubConfig {
+    /// API of GitHub Enterprise instances is at `https://<host>/api/v3`
+    pub api_url: String,
+    /// Personal access token, GITHUB_TOKEN is used if it is not set
+    pub token: Option<String>,
+}
+
+impl Default for GithubConfig {
+    fn default() -> GithubConfig {
+        GithubConfig {
+            api_url: "https://api.github.com".to_string(),
+            token: None,
+        }
+    }
+}
+
+#[derive(Default)]
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct CaldavConfig {
+    /// URL of a task list on the CalDAV server, e.g.
+    /// <https://cloud.example.com/remote.php/dav/calendars/me/tasks/>. {list} is replaced by the name
+    /// of the task list.
+    pub url: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct ActivityWatchConfig {
+    /// Add each pomodoro when it is finished, instead of only with pt --sync activitywatch
+    pub enabled: bool,
+    pub url: String,
+    /// `aw-watcher-pt_<hostname>` by default
+    pub bucket: Option<String>,
+}
+
+impl Default for ActivityWatchConfig {
+    fn default() -> ActivityWatchConfig {
+        ActivityWatchConfig {
+            enabled: false,
+            url: "http://localhost:5600".to_string(),
+            bucket: None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct MqttConfig {
+    /// Broker the pomodoros are published to, e.g. the one of Home Assistant. Off without it.
+    pub host: Option<String>,
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Prefix of the topics, {list} is replaced by the name of the task list
+    pub topic: String,
+}
+
+impl Default for MqttConfig {
+    fn default() -> MqttConfig {
+        MqttConfig {
+            host: None,
+            port: 1883,
+            username: None,
+            password: None,
+            topic: "pt".to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct GitlabConfig {
+    /// Base URL of the instance, for self-hosted GitLab
+    pub url: String,
+    /// Personal access token with the api scope, GITLAB_TOKEN is used if it is not set
+    pub token: Option<String>,
+}
+
+impl Default for GitlabConfig {
+    fn default() -> GitlabConfig {
+        GitlabConfig {
+            url: "https://gitlab.com".to_string(),
+            token: None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct ClockifyConfig {
+    pub api_url: String,
+    pub api_key: Option<String>,
+    pub workspace_id: Option<String>,
+    /// Clockify project and task IDs by pt project or tag name
+    pub projects: HashMap<String, String>,
+    pub tasks: HashMap<String, String>,
+    /// Only pomodoros from this day on are added, so old ones don't flood the timesheet
+    pub since: Option<NaiveDate>,
+}
+
+impl Default for ClockifyConfig {
+    fn default() -> ClockifyConfig {
+        ClockifyConfig {
+            api_url: "https://api.clockify.me/api/v1".to_string(),
+            api_key: None,
+            workspace_id: None,
+            projects: HashMap::new(),
+            tasks: HashMap::new(),
+            since: None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct GoogleCalendarConfig {
+    /// OAuth client of type "Desktop app" from the Google Cloud console
+    pub client_id: Option<String>,
+    pub client_secret: Option<String>,
+    /// Printed by pt --google-auth, focus blocks are only created if it is set
+    pub refresh_token: Option<String>,
+    pub calendar_id: String,
+    /// Title of the focus blocks
+    pub title: String,
+    /// Add the task description to the focus blocks
+    pub show_description: bool,
+}
+
+impl Default for GoogleCalendarConfig {
+    fn default() -> GoogleCalendarConfig {
+        GoogleCalendarConfig {
+            client_id: None,
+            client_secret: None,
+            refresh_token: None,
+            calendar_id: "primary".to_string(),
+            title: "Focus".to_string(),
+            show_description: false,
+        }
+    }
+}
+
+/// Settings read from ~/.pt/config.json. Every field is optional and falls back to its default.
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Language of the messages and notifications, e.g. de, instead of the one of LANG
+    pub language: Option<String>,
+    pub storage: StorageMode,
+    /// Write tasks.json and archive.json without indentation, for long histories
+    pub compact_json: bool,
+    /// Log each change before writing tasks.json, so a save interrupted by a crash is finished on the next start
+    pub write_ahead_log: bool,
+    pub git: GitConfig,
+    pub sync: SyncConfig,
+    pub encryption: EncryptionConfig,
+    pub markdown: MarkdownConfig,
+    pub theme: ThemeConfig,
+    pub list: ListConfig,
+    pub sound: SoundConfig,
+    pub notification: NotificationConfig,
+    pub push: PushConfig,
+    pub webhooks: Vec<WebhookConfig>,
+    pub plugins: PluginsConfig,
+    pub dnd: DndConfig,
+    pub media: MediaConfig,
+    pub blocklist: BlocklistConfig,
+    pub quiet_hours: Vec<QuietHours>,
+    pub email: EmailConfig,
+    pub server: ServerConfig,
+    pub discord: DiscordConfig,
+    pub telegram: TelegramConfig,
+    pub jira: JiraConfig,
+    pub github: GithubConfig,
+    pub gitlab: GitlabConfig,
+    pub clockify: ClockifyConfig,
+    pub google_calendar: GoogleCalendarConfig,
+    pub caldav: CaldavConfig,
+    pub activitywatch: ActivityWatchConfig,
+    pub mqtt: MqttConfig,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            language: None,
+            storage: StorageMode::Json,
+            compact_json: false,
+            write_ahead_log: false,
+            git: GitConfig::default(),
+            sync: SyncConfig::default(),
+            encryption: EncryptionConfig::default(),
+            markdown: MarkdownConfig::default(),
+            theme: ThemeConfig::default(),
+            list: ListConfig::default(),
+            sound: SoundConfig::default(),
+            notification: NotificationConfig::default(),
+            push: PushConfig::default(),
+            webhooks: Vec::new(),
+            plugins: PluginsConfig::default(),
+            dnd: DndConfig::default(),
+            media: MediaConfig::default(),
+            blocklist: BlocklistConfig::default(),
+            quiet_hours: Vec::new(),
+            email: EmailConfig::default(),
+            server: ServerConfig::default(),
+            discord: DiscordConfig::default(),
+            telegram: TelegramConfig::default(),
+            jira: JiraConfig::default(),
+            github: GithubConfig::default(),
+            gitlab: GitlabConfig::default(),
+            clockify: ClockifyConfig::default(),
+            google_calendar: GoogleCalendarConfig::default(),
+            caldav: CaldavConfig::default(),
+            activitywatch: ActivityWatchConfig::default(),
+            mqtt: MqttConfig::default(),
+        }
+    }
+}
+
+/// Paths in the config file may start with ~/ (or ~\ on Windows) for the home directory
+pub fn expand_home(path: &str) -> PathBuf {
+    let rest = path.strip_prefix("~/").or_else(|| path.strip_prefix("~\\").filter(|_| cfg!(windows)));
+    match (rest, dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+pub fn load_config(data_dir: &Path) -> Result<Config, Error> {
+    let path = data_dir.join(CONFIG_FILE);
+    match fs::read_to_string(&path) {
+        Ok(content) => serde_json::from_str(&content)
+            .map_err(|e| Error::Other(format!("Failed to parse config file {}: {}", path.display(), e))),
+        Err(_) => Ok(Config::default()),
+    }
+}
@@ -0,0 +1,58 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use serde_json::{json, Value};
+
+use crate::config::Config;
+use crate::storage::{self, Storage};
+use crate::{crypto, data_dir, Error, GlobalOptions, Task};
+
+// Operations shared by the control socket, the HTTP API and the Telegram bot. The HTTP API and the
+// bot only read the task list and leave changes to separate pt processes.
+
+// Reads the current tasks without locking the task list. Fails e.g. while another pt holds the
+// lock for longer than --wait, the modes that keep running report it and try again later.
+pub fn loader<'a>(options: &'a GlobalOptions, project_file: &'a Option<PathBuf>, config: &'a Config) -> impl Fn() -> Result<Vec<Task>, Error> + 'a {
+    move || {
+        let mut storage = match project_file {
+            Some(path) => Storage::open_project(path, true, options.lock_timeout)?,
+            None => Storage::open(&storage::list_dir(&data_dir(), &options.list_name), config, true, options.lock_timeout)?,
+        };
+        storage.load(false).map(|loaded| loaded.tasks)
+    }
+}
+
+// Changes run pt again, so the task list is locked and saved like for any other command. Returns
+// the exit code of pt, None if it didn't run or was killed.
+pub fn run_pt(exe: &Path, list_args: &[String], args: &[String]) -> (Option<i32>, Value) {
+    let mut command = Command::new(exe);
+    command.args(list_args).args(args);
+    if let Some((name, value)) = crypto::passphrase_env() {
+        command.env(name, value);
+    }
+    match command.output() {
+        Ok(output) => {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            (output.status.code(), json!({ "ok": output.status.success(), "message": stdout.lines().next().unwrap_or("") }))
+        },
+        Err(e) => (None, json!({ "ok": false, "error": e.to_string() })),
+    }
+}
+
+pub fn error(e: &Error) -> Value {
+    json!({ "ok": false, "error": e.to_string() })
+}
+
+// Running pomodoros with their remaining time
+pub fn status(tasks: &[Task]) -> Value {
+    let active: Vec<Value> = tasks.iter()
+        .filter_map(|task| task.pomodoro_time_remaining().map(|remaining| (task, remaining)))
+        .map(|(task, remaining)| json!({ "task": task, "remaining_seconds": remaining.num_seconds().max(0) }))
+        .collect();
+    json!({ "ok": true, "active": active })
+}
+
+pub fn list(tasks: Vec<Task>) -> Value {
+    let tasks: Vec<Task> = tasks.into_iter().filter(|task| !task.archived).collect();
+    json!({ "ok": true, "tasks": tasks })
+}
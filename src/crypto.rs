@@ -0,0 +1,101 @@
+use std::iter;
+use std::env;
+use std::io::{Read, Write};
+use std::sync::OnceLock;
+
+use age::armor::{ArmoredReader, ArmoredWriter, Format};
+use age::secrecy::{ExposeSecret, SecretString};
+
+use crate::config::EncryptionConfig;
+use crate::shell_command;
+
+// Files are encrypted with age using a passphrase and ASCII armor, so they stay text files
+const ARMOR_HEADER: &str = "-----BEGIN AGE ENCRYPTED FILE-----";
+const PASSPHRASE_ENV: &str = "PT_PASSPHRASE";
+
+static SETTINGS: OnceLock<EncryptionConfig> = OnceLock::new();
+static PASSPHRASE: OnceLock<SecretString> = OnceLock::new();
+
+pub fn init(config: &EncryptionConfig) {
+    SETTINGS.get_or_init(|| config.clone());
+}
+
+fn settings() -> EncryptionConfig {
+    SETTINGS.get().cloned().unwrap_or_default()
+}
+
+pub fn enabled() -> bool {
+    settings().enabled
+}
+
+pub fn is_encrypted(content: &[u8]) -> bool {
+    content.starts_with(ARMOR_HEADER.as_bytes())
+}
+
+fn read_passphrase(confirm: bool) -> Result<SecretString, String> {
+    if let Ok(passphrase) = env::var(PASSPHRASE_ENV) {
+        return Ok(SecretString::from(passphrase));
+    }
+    if let Some(command) = settings().passphrase_command {
+        let output = shell_command(&command)
+            .output()
+            .map_err(|e| format!("failed to run passphrase command: {}", e))?;
+        if !output.status.success() {
+            return Err(format!("passphrase command failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+        }
+        let passphrase = String::from_utf8_lossy(&output.stdout).trim_end_matches('\n').to_string();
+        return Ok(SecretString::from(passphrase));
+    }
+    let passphrase = rpassword::prompt_password("Passphrase for the task file: ").map_err(|e| e.to_string())?;
+    // Typos would make the data unreadable, so a new passphrase has to be entered twice
+    if confirm && rpassword::prompt_password("Repeat passphrase: ").map_err(|e| e.to_string())? != passphrase {
+        return Err("passphrases do not match".to_string());
+    }
+    Ok(SecretString::from(passphrase))
+}
+
+// The passphrase is only requested once per run
+fn passphrase(confirm: bool) -> Result<&'static SecretString, String> {
+    if let Some(passphrase) = PASSPHRASE.get() {
+        return Ok(passphrase);
+    }
+    let passphrase = read_passphrase(confirm)?;
+    if passphrase.expose_secret().is_empty() {
+        return Err("the passphrase is empty".to_string());
+    }
+    Ok(PASSPHRASE.get_or_init(|| passphrase))
+}
+
+/// Environment variable with the passphrase that was entered, so child processes don't ask again
+pub fn passphrase_env() -> Option<(&'static str, String)> {
+    PASSPHRASE.get().map(|passphrase| (PASSPHRASE_ENV, passphrase.expose_secret().to_string()))
+}
+
+/// Returns the plain text of a file, decrypting it if it is encrypted
+pub fn decode(content: Vec<u8>) -> Result<String, String> {
+    if !is_encrypted(&content) {
+        return String::from_utf8(content).map_err(|e| e.to_string());
+    }
+    let identity = age::scrypt::Identity::new(passphrase(false)?.clone());
+    let decryptor = age::Decryptor::new_buffered(ArmoredReader::new(&content[..])).map_err(|e| e.to_string())?;
+    let mut reader = decryptor
+        .decrypt(iter::once(&identity as &dyn age::Identity))
+        .map_err(|e| format!("failed to decrypt: {}", e))?;
+    let mut plaintext = String::new();
+    reader.read_to_string(&mut plaintext).map_err(|e| e.to_string())?;
+    Ok(plaintext)
+}
+
+/// Encrypts the text if encryption is enabled
+pub fn encode(plaintext: String) -> Result<String, String> {
+    if !enabled() {
+        return Ok(plaintext);
+    }
+    let encryptor = age::Encryptor::with_user_passphrase(passphrase(true)?.clone());
+    let mut ciphertext = Vec::with_capacity(plaintext.len());
+    let armor = ArmoredWriter::wrap_output(&mut ciphertext, Format::AsciiArmor).map_err(|e| e.to_string())?;
+    let mut writer = encryptor.wrap_output(armor).map_err(|e| e.to_string())?;
+    writer.write_all(plaintext.as_bytes()).map_err(|e| e.to_string())?;
+    writer.finish().and_then(|armor| armor.finish()).map_err(|e| e.to_string())?;
+    Ok(String::from_utf8(ciphertext).expect("Armored output is not valid UTF-8."))
+}
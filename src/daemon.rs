@@ -0,0 +1,97 @@
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::thread;
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::config::Config;
+use crate::discord::Presence;
+use crate::mqtt::Publisher;
+use crate::state::{self, State};
+use crate::status;
+use crate::{control, crypto, data_dir, Error, GlobalOptions, Task};
+
+// Changes by other pt processes, e.g. a pomodoro that was started, are noticed within this many
+// seconds. Due date reminders and the progress notification are updated as often.
+const POLL_INTERVAL: i64 = 15;
+// Waking up slightly after an event makes sure it is due
+const EVENT_MARGIN: i64 = 200;
+
+// Time of the next notification --notify shows
+fn next_event(tasks: &[Task], state: &State) -> Option<DateTime<Utc>> {
+    let now = Utc::now();
+    let pomodoro_ends = tasks.iter().filter_map(|task| task.pomodoro_time_remaining()).map(|remaining| now + remaining);
+    let state_times = state.break_end.into_iter()
+        .chain(state.snoozed.iter().map(|snooze| snooze.until))
+        .chain(state.nag.as_ref().map(|nag| nag.until));
+    pomodoro_ends.chain(state_times).min()
+}
+
+// Runs --notify whenever a notification is due, so alarms go off exactly when pomodoros and
+// breaks end. Each run is a separate pt process that locks the task list like any other command,
+// in between the task list is only read. Global task lists can also be controlled through a Unix
+// socket in their directory. Only returns if it can't start.
+pub fn run(options: &GlobalOptions, project_file: &Option<PathBuf>, config: &Config) -> Result<(), String> {
+    let exe = env::current_exe().map_err(|e| format!("Failed to find the pt executable: {}", e))?;
+    let load = control::loader(options, project_file, config);
+    let list_args = options.list_args();
+    let mut args = list_args.clone();
+    if options.silent {
+        args.push("--silent".to_string());
+    }
+    if options.no_notify {
+        args.push("--no-notify".to_string());
+    }
+    if let Some(volume) = options.volume {
+        args.extend(["--volume".to_string(), volume.to_string()]);
+    }
+    args.push("--notify".to_string());
+
+    // Asks for the passphrase of an encrypted task list once, it is handed on to the --notify runs
+    load().map_err(|e| e.to_string())?;
+    println!("Watching pomodoros. Press Ctrl+C to stop.");
+    thread::scope(|scope| {
+        #[cfg(unix)]
+        if project_file.is_none() {
+            let path = crate::storage::list_dir(&data_dir(), &options.list_name).join(crate::socket::SOCKET_FILE);
+            scope.spawn(|| crate::socket::serve(path, &options.list_name));
+        }
+        watch(&exe, &args, &load, config, &options.list_name)
+    })
+}
+
+fn watch(exe: &Path, args: &[String], load: &dyn Fn() -> Result<Vec<Task>, Error>, config: &Config, list_name: &str) -> ! {
+    let mut presence = Presence::default();
+    let mut publisher = Publisher::default();
+    loop {
+        let mut command = Command::new(exe);
+        command.args(args);
+        if let Some((name, value)) = crypto::passphrase_env() {
+            command.env(name, value);
+        }
+        if let Err(e) = command.status() {
+            println!("Failed to run {}: {}", exe.display(), e);
+        }
+
+        let now = Utc::now();
+        let poll = now + Duration::seconds(POLL_INTERVAL);
+        let tasks = match load() {
+            Ok(tasks) => tasks,
+            // E.g. a pt command that keeps the task list locked while its editor is open
+            Err(e) => {
+                println!("{}", e);
+                thread::sleep((poll - now).to_std().unwrap_or_default());
+                continue;
+            }
+        };
+        let state = state::load_state(&data_dir());
+        presence.update(&status::activity(&tasks, &state), &config.discord);
+        publisher.update(&config.mqtt, list_name, &tasks, &state);
+        let wake = match next_event(&tasks, &state) {
+            Some(event) if event < poll => event + Duration::milliseconds(EVENT_MARGIN),
+            _ => poll,
+        };
+        thread::sleep((wake - now).to_std().unwrap_or_default());
+    }
+}
@@ -0,0 +1,142 @@
+use std::env;
+use std::io::{Read, Write};
+#[cfg(unix)]
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::process;
+#[cfg(unix)]
+use std::time::Duration;
+
+use chrono::Utc;
+use serde_json::{json, Value};
+
+use crate::config::DiscordConfig;
+use crate::status::Activity;
+
+// Discord's local IPC: each message is an opcode and a length, both little endian u32, and JSON
+const OP_HANDSHAKE: u32 = 0;
+const OP_FRAME: u32 = 1;
+const IPC_NAME: &str = "discord-ipc-";
+
+#[cfg(unix)]
+type Connection = UnixStream;
+#[cfg(windows)]
+type Connection = std::fs::File;
+
+#[cfg(unix)]
+fn connect() -> Option<Connection> {
+    let mut dirs: Vec<PathBuf> = ["XDG_RUNTIME_DIR", "TMPDIR", "TMP", "TEMP"].iter()
+        .filter_map(|name| env::var_os(name).map(PathBuf::from))
+        .collect();
+    dirs.push(PathBuf::from("/tmp"));
+    // Flatpak and Snap installs put the socket in a subdirectory
+    let dirs: Vec<PathBuf> = dirs.iter()
+        .flat_map(|dir| [dir.clone(), dir.join("app/com.discordapp.Discord"), dir.join("snap.discord")])
+        .collect();
+    let connection = dirs.iter()
+        .flat_map(|dir| (0..10).map(move |i| dir.join(format!("{}{}", IPC_NAME, i))))
+        .find_map(|path| UnixStream::connect(path).ok())?;
+    // A stuck Discord must not stop the daemon
+    connection.set_read_timeout(Some(Duration::from_secs(5))).ok()?;
+    Some(connection)
+}
+
+#[cfg(windows)]
+fn connect() -> Option<Connection> {
+    (0..10).find_map(|i| std::fs::OpenOptions::new().read(true).write(true).open(format!(r"\\.\pipe\{}{}", IPC_NAME, i)).ok())
+}
+
+fn send(connection: &mut Connection, opcode: u32, payload: &Value) -> Result<(), String> {
+    let payload = payload.to_string();
+    let mut message = Vec::with_capacity(payload.len() + 8);
+    message.extend_from_slice(&opcode.to_le_bytes());
+    message.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    message.extend_from_slice(payload.as_bytes());
+    connection.write_all(&message).map_err(|e| e.to_string())
+}
+
+fn receive(connection: &mut Connection) -> Result<Value, String> {
+    let mut header = [0; 8];
+    connection.read_exact(&mut header).map_err(|e| e.to_string())?;
+    let length = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+    let mut payload = vec![0; length];
+    connection.read_exact(&mut payload).map_err(|e| e.to_string())?;
+    let payload: Value = serde_json::from_slice(&payload).map_err(|e| e.to_string())?;
+    match payload["evt"].as_str() {
+        Some("ERROR") => Err(payload["data"]["message"].as_str().unwrap_or("unknown error").to_string()),
+        _ => Ok(payload),
+    }
+}
+
+// Rich Presence shown for the pomodoro or break, none while nothing runs
+fn presence(activity: &Activity, config: &DiscordConfig) -> Option<Value> {
+    let (details, state, remaining) = match activity {
+        Activity::Pomodoro(task, remaining) => {
+            let details = if config.show_description { task.description.clone() } else { "Focusing".to_string() };
+            (details, "Pomodoro", *remaining)
+        },
+        Activity::Break(remaining) => ("Taking a break".to_string(), "Break", *remaining),
+        Activity::Idle => return None,
+    };
+    Some(json!({
+        "details": details,
+        "state": state,
+        // Discord counts down to the end by itself
+        "timestamps": { "end": (Utc::now() + remaining).timestamp() },
+    }))
+}
+
+// Keeps the Rich Presence of the Discord client in sync while the daemon runs. Discord may be
+// started and closed at any time, so the connection is opened again when needed.
+#[derive(Default)]
+pub struct Presence {
+    connection: Option<Connection>,
+    shown: Option<Value>,
+}
+
+impl Presence {
+    fn open(client_id: &str) -> Result<Connection, String> {
+        let mut connection = connect().ok_or("Discord is not running")?;
+        send(&mut connection, OP_HANDSHAKE, &json!({ "v": 1, "client_id": client_id }))?;
+        receive(&mut connection)?;
+        Ok(connection)
+    }
+
+    pub fn update(&mut self, activity: &Activity, config: &DiscordConfig) {
+        let client_id = match &config.client_id {
+            Some(client_id) => client_id,
+            None => return,
+        };
+        let activity = presence(activity, config);
+        // The end time moves by a second now and then, which isn't worth an update
+        let unchanged = match (&activity, &self.shown) {
+            (Some(new), Some(shown)) => new["details"] == shown["details"] && new["state"] == shown["state"]
+                && new["timestamps"]["end"].as_i64().zip(shown["timestamps"]["end"].as_i64()).is_some_and(|(a, b)| (a - b).abs() < 5),
+            (None, None) => true,
+            _ => false,
+        };
+        if unchanged && self.connection.is_some() {
+            return;
+        }
+        let mut connection = match self.connection.take() {
+            Some(connection) => connection,
+            None => match Presence::open(client_id) {
+                Ok(connection) => connection,
+                // Discord is closed, it's tried again on the next update
+                Err(_) => return,
+            },
+        };
+        let request = json!({
+            "cmd": "SET_ACTIVITY",
+            "args": { "pid": process::id(), "activity": activity },
+            "nonce": Utc::now().timestamp_nanos_opt().unwrap_or_default().to_string(),
+        });
+        match send(&mut connection, OP_FRAME, &request).and_then(|_| receive(&mut connection)) {
+            Ok(_) => {
+                self.shown = activity;
+                self.connection = Some(connection);
+            },
+            Err(e) => println!("Failed to update the Discord status: {}", e),
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use std::process::Command;
+
+use chrono::{DateTime, Local, Utc};
+
+use crate::config::{DndBackend, DndConfig};
+use crate::event::Event;
+use crate::{shell_command, Task};
+
+fn backend_command(backend: DndBackend, until: Option<DateTime<Utc>>) -> Command {
+    let enable = until.is_some();
+    let mut command;
+    match backend {
+        DndBackend::Gnome => {
+            command = Command::new("gsettings");
+            command.args(["set", "org.gnome.desktop.notifications", "show-banners", if enable { "false" } else { "true" }]);
+        },
+        DndBackend::Kde => {
+            // Plasma ends do-not-disturb by itself at the given time, even if pt --notify isn't running
+            command = Command::new("kwriteconfig5");
+            command.args(["--file", "plasmanotifyrc", "--group", "DoNotDisturb", "--key", "Until"]);
+            match until {
+                Some(until) => command.arg(until.with_timezone(&Local).format("%Y,%-m,%-d,%-H,%-M,%-S").to_string()),
+                None => command.arg("--delete"),
+            };
+        },
+        DndBackend::Dunst => {
+            command = Command::new("dunstctl");
+            command.args(["set-paused", if enable { "true" } else { "false" }]);
+        },
+        DndBackend::Mako => {
+            command = Command::new("makoctl");
+            command.args(["mode", if enable { "-a" } else { "-r" }, "do-not-disturb"]);
+        },
+    }
+    command
+}
+
+// Enables do-not-disturb until the given time, or disables it without one
+fn set(config: &DndConfig, until: Option<DateTime<Utc>>) {
+    let custom = if until.is_some() { &config.on_command } else { &config.off_command };
+    let mut command = match (custom, config.backend) {
+        (Some(custom), _) => shell_command(custom),
+        (None, Some(backend)) => backend_command(backend, until),
+        (None, None) => return,
+    };
+    let action = if until.is_some() { "enable" } else { "disable" };
+    match command.status() {
+        Ok(status) if status.success() => (),
+        Ok(status) => println!("Failed to {} do-not-disturb mode: the command failed with {}.", action, status),
+        Err(e) => println!("Failed to {} do-not-disturb mode: {}", action, e),
+    }
+}
+
+// Enables do-not-disturb when a pomodoro started and disables it when the last running one finished
+pub fn update(config: &DndConfig, events: &[Event], tasks: &[Task]) {
+    let started = events.iter().find_map(|event| match event {
+        Event::PomodoroStarted { task } => task.pomodoro_time_remaining(),
+        _ => None,
+    });
+    let finished = events.iter().any(|event| matches!(event, Event::PomodoroFinished { .. }));
+    if let Some(remaining) = started {
+        set(config, Some(Utc::now() + remaining));
+    } else if finished && !tasks.iter().any(|task| task.pomodoro_active()) {
+        set(config, None);
+    }
+}
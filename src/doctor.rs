@@ -0,0 +1,147 @@
+use std::path::{Path, PathBuf};
+
+use crate::config::{self, Config};
+use crate::{crypto, notification, sound, storage};
+
+// A problem found by a check and how to fix it
+struct Problem {
+    message: String,
+    fix: &'static str,
+}
+
+type Check = Result<String, Problem>;
+
+fn problem(message: impl Into<String>, fix: &'static str) -> Problem {
+    Problem { message: message.into(), fix }
+}
+
+// Prints the result of a check. Returns whether it passed.
+fn report(name: &str, check: Check) -> bool {
+    match check {
+        Ok(detail) => {
+            println!("ok       {}: {}", name, detail);
+            true
+        },
+        Err(problem) => {
+            println!("PROBLEM  {}: {}", name, problem.message);
+            println!("         Fix: {}", problem.fix);
+            false
+        },
+    }
+}
+
+fn check_data_dir(data_dir: &Path) -> Check {
+    match data_dir.metadata() {
+        Ok(metadata) if !metadata.is_dir() => Err(problem(
+            format!("{} is not a directory.", data_dir.display()),
+            "Move the file away, pt creates the directory on the next change.",
+        )),
+        Ok(metadata) if metadata.permissions().readonly() => Err(problem(
+            format!("{} is not writable.", data_dir.display()),
+            "Make the directory writable for your user.",
+        )),
+        Ok(_) => Ok(data_dir.display().to_string()),
+        Err(_) => Ok(format!("{} does not exist yet, it is created on the first change", data_dir.display())),
+    }
+}
+
+fn check_config(data_dir: &Path) -> (Check, Config) {
+    let path = data_dir.join(config::CONFIG_FILE);
+    match config::load_config(data_dir) {
+        Ok(config) if path.exists() => (Ok(path.display().to_string()), config),
+        Ok(config) => (Ok("no config file, using the defaults".to_string()), config),
+        Err(e) => (
+            Err(problem(e.to_string(), "Fix the setting at the reported line, or rename the file to start over with the defaults.")),
+            Config::default(),
+        ),
+    }
+}
+
+fn check_task_file(path: &Path) -> Check {
+    if !path.exists() {
+        return Ok(format!("{} does not exist yet", path.display()));
+    }
+    let fix = "Run pt --validate to see every problem and correct the file, or go back to a backup with pt --restore.";
+    match storage::validate_file(path) {
+        Ok((count, problems)) if problems.is_empty() => Ok(format!("{}, {} tasks", path.display(), count)),
+        Ok((_, problems)) => Err(problem(format!("{} has {} problems, e.g. {}", path.display(), problems.len(), problems[0]), fix)),
+        Err(e) => Err(problem(format!("{}: {}", path.display(), e), fix)),
+    }
+}
+
+fn check_lock(path: &Path) -> Check {
+    match storage::is_locked(path) {
+        Ok(false) => Ok("not locked".to_string()),
+        Ok(true) => Err(problem(
+            format!("{} is locked by another process.", path.display()),
+            "Wait until the other pt command finishes, e.g. one that waits for a passphrase or an editor, or stop it.",
+        )),
+        Err(e) => Err(problem(e.to_string(), "Check the permissions of the task file.")),
+    }
+}
+
+fn check_notifications(config: &Config) -> Check {
+    if !config.notification.desktop {
+        return Ok("disabled by notification.desktop".to_string());
+    }
+    notification::server().map_err(|e| problem(
+        format!("No notification server found: {}", e),
+        "Start a notification daemon like dunst or mako, or set notification.desktop to false to print notifications instead.",
+    ))
+}
+
+fn check_audio(config: &Config) -> Check {
+    if config.sound.silent {
+        return Ok("disabled by sound.silent".to_string());
+    }
+    if !sound::AVAILABLE {
+        return Ok("pt was built without the audio feature, only notifications are shown".to_string());
+    }
+    sound::output_device().map_err(|e| problem(
+        format!("No audio output: {}", e),
+        "Connect or select an audio device, or set sound.silent to true, e.g. on a server.",
+    ))
+}
+
+fn check_alarms(config: &Config) -> Vec<Check> {
+    let alarms = sound::check_alarms(&config.sound);
+    if alarms.is_empty() {
+        return vec![Ok("bundled alarm".to_string())];
+    }
+    alarms.into_iter()
+        .map(|(path, result)| match result {
+            Ok(()) => Ok(path.display().to_string()),
+            Err(e) => Err(problem(
+                format!("{} can't be played, the bundled alarm is used instead: {}", path.display(), e),
+                "Correct the path in the sound settings or convert the file to MP3, WAV, FLAC or Ogg Vorbis.",
+            )),
+        })
+        .collect()
+}
+
+// Checks the setup that most problems come from and suggests fixes. Returns whether everything
+// is fine.
+pub fn run(data_dir: &Path, list_name: &str, project_file: Option<PathBuf>) -> bool {
+    let mut ok = report("Data directory", check_data_dir(data_dir));
+    let (check, config) = check_config(data_dir);
+    ok &= report("Config file", check);
+    crypto::init(&config.encryption);
+    let (task_file, archive_file) = match project_file {
+        Some(path) => (path.clone(), path.with_file_name(storage::PROJECT_ARCHIVE_FILE)),
+        None => {
+            let list_dir = storage::list_dir(data_dir, list_name);
+            (storage::task_path(&list_dir, config.storage), list_dir.join(storage::ARCHIVE_FILE))
+        },
+    };
+    ok &= report("Task file", check_task_file(&task_file));
+    if archive_file.exists() {
+        ok &= report("Archive", check_task_file(&archive_file));
+    }
+    ok &= report("Lock", check_lock(&task_file));
+    ok &= report("Notifications", check_notifications(&config));
+    ok &= report("Audio", check_audio(&config));
+    for check in check_alarms(&config) {
+        ok &= report("Alarm", check);
+    }
+    ok
+}
@@ -0,0 +1,209 @@
+use std::env;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::Path;
+use std::process;
+
+use chrono::{DateTime, Local, NaiveDateTime, Utc};
+use dialoguer::Confirm;
+
+use crate::{shell_command, Pomodoro, Task};
+
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+const DEFAULT_EDITOR: &str = if cfg!(windows) { "notepad" } else { "vi" };
+
+fn format_time(time: DateTime<Utc>) -> String {
+    time.with_timezone(&Local).format(TIME_FORMAT).to_string()
+}
+
+// The pomodoros as a table of local start and end times
+fn render(task: &Task) -> String {
+    let mut text = String::new();
+    text.push_str(&format!("# Pomodoros of task {}: {}\n", task.id, task.description));
+    text.push_str("# One pomodoro per line with its start and end, e.g. 2024-05-01 09:00:00  2024-05-01 09:25:00.\n");
+    text.push_str("# Delete a line to delete the pomodoro. A running pomodoro has no end.\n");
+    for pomodoro in &task.pomodoros {
+        let end = pomodoro.end_time.map(format_time).unwrap_or_default();
+        text.push_str(format!("{}  {}", format_time(pomodoro.start_time), end).trim_end());
+        text.push('\n');
+    }
+    text
+}
+
+// Local time like 2024-05-01 09:00:00, the seconds may be left out
+fn parse_time(date: &str, time: &str) -> Result<DateTime<Utc>, String> {
+    let text = format!("{} {}", date, time);
+    let time = NaiveDateTime::parse_from_str(&text, TIME_FORMAT)
+        .or_else(|_| NaiveDateTime::parse_from_str(&text, "%Y-%m-%d %H:%M"))
+        .map_err(|_| format!("{} is not a time like 2024-05-01 09:00:00.", text))?;
+    time.and_local_timezone(Local).earliest()
+        .map(|time| time.with_timezone(&Utc))
+        .ok_or_else(|| format!("{} does not exist in the local time zone.", text))
+}
+
+// Pomodoros of the edited table, or the problems with it
+fn parse(text: &str) -> Result<Vec<Pomodoro>, Vec<String>> {
+    let mut pomodoros = Vec::new();
+    let mut problems = Vec::new();
+    let lines = text.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#'));
+    for line in lines {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let pomodoro = match fields.as_slice() {
+            [date, time] => parse_time(date, time).map(|start_time| Pomodoro::new(start_time, None)),
+            [start_date, start_time, end_date, end_time] => parse_time(start_date, start_time)
+                .and_then(|start_time| Ok(Pomodoro::new(start_time, Some(parse_time(end_date, end_time)?)))),
+            _ => Err("Expected a start and an optional end.".to_string()),
+        };
+        match pomodoro {
+            Ok(pomodoro) => pomodoros.push(pomodoro),
+            Err(e) => problems.push(format!("{}: {}", line, e)),
+        }
+    }
+    if problems.is_empty() {
+        problems = check(&mut pomodoros);
+    }
+    match problems.is_empty() {
+        true => Ok(pomodoros),
+        false => Err(problems),
+    }
+}
+
+// Sorts the pomodoros and returns what is wrong with them
+fn check(pomodoros: &mut [Pomodoro]) -> Vec<String> {
+    let mut problems = Vec::new();
+    pomodoros.sort_by_key(|pomodoro| pomodoro.start_time);
+    let now = Utc::now();
+    for pomodoro in pomodoros.iter() {
+        let end_time = pomodoro.end_time.unwrap_or(now);
+        if end_time <= pomodoro.start_time {
+            problems.push(format!("The pomodoro started at {} ends before it starts.", format_time(pomodoro.start_time)));
+        }
+        if end_time > now || pomodoro.start_time > now {
+            problems.push(format!("The pomodoro started at {} is in the future.", format_time(pomodoro.start_time)));
+        }
+    }
+    for pair in pomodoros.windows(2) {
+        match pair[0].end_time {
+            Some(end_time) if end_time <= pair[1].start_time => (),
+            Some(_) => problems.push(format!("The pomodoros started at {} and {} overlap.", format_time(pair[0].start_time), format_time(pair[1].start_time))),
+            None => problems.push(format!("The pomodoro started at {} is running, only the last one may be.", format_time(pair[0].start_time))),
+        }
+    }
+    problems
+}
+
+// The table only has whole seconds, so unchanged lines keep the exact times of their pomodoros
+fn same_line(a: &Pomodoro, b: &Pomodoro) -> bool {
+    format_time(a.start_time) == format_time(b.start_time) && a.end_time.map(format_time) == b.end_time.map(format_time)
+}
+
+fn run_editor(path: &Path) -> Result<(), String> {
+    let editor = env::var("VISUAL").or_else(|_| env::var("EDITOR")).unwrap_or_else(|_| DEFAULT_EDITOR.to_string());
+    let status = shell_command(&format!("{} \"{}\"", editor, path.display()))
+        .status()
+        .map_err(|e| format!("Failed to run the editor {}: {}", editor, e))?;
+    match status.success() {
+        true => Ok(()),
+        false => Err(format!("The editor {} failed with {}.", editor, status)),
+    }
+}
+
+// Opens the pomodoros of the task in $VISUAL or $EDITOR until they are valid. Returns None if
+// nothing was changed or the user gave up.
+pub fn edit_times(task: &Task) -> Result<Option<Vec<Pomodoro>>, String> {
+    if !io::stdin().is_terminal() {
+        return Err("pt --edit-times needs a terminal for the editor.".to_string());
+    }
+    let path = env::temp_dir().join(format!("pt-times-{}.txt", process::id()));
+    let original = render(task);
+    let mut text = original.clone();
+    let result = loop {
+        if let Err(e) = fs::write(&path, &text).map_err(|e| e.to_string()).and_then(|_| run_editor(&path)) {
+            break Err(e);
+        }
+        let edited = match fs::read_to_string(&path) {
+            Ok(edited) => edited,
+            Err(e) => break Err(e.to_string()),
+        };
+        // Closing the editor counts as checking times that were marked because the clock went back
+        if edited == original {
+            let mut checked = task.pomodoros.clone();
+            checked.iter_mut().for_each(|pomodoro| pomodoro.clock_went_back = false);
+            break Ok(Some(checked).filter(|checked| *checked != task.pomodoros));
+        }
+        match parse(&edited) {
+            Ok(pomodoros) => {
+                let mut pomodoros: Vec<Pomodoro> = pomodoros.into_iter()
+                    .map(|pomodoro| task.pomodoros.iter().find(|original| same_line(original, &pomodoro)).cloned().unwrap_or(pomodoro))
+                    .collect();
+                pomodoros.iter_mut().for_each(|pomodoro| pomodoro.clock_went_back = false);
+                break Ok(Some(pomodoros).filter(|pomodoros| *pomodoros != task.pomodoros));
+            },
+            Err(problems) => {
+                for problem in &problems {
+                    println!("{}", problem);
+                }
+                let again = Confirm::new().with_prompt("Edit again?").default(true).interact().unwrap_or(false);
+                if !again {
+                    break Ok(None);
+                }
+                let lines = edited.lines().filter(|line| !line.starts_with("# Problem: ")).collect::<Vec<_>>().join("\n");
+                text = problems.iter().map(|problem| format!("# Problem: {}\n", problem)).collect::<String>() + &lines + "\n";
+            },
+        }
+    };
+    let _ = fs::remove_file(&path);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn problems(text: &str) -> Vec<String> {
+        parse(text).err().unwrap_or_default()
+    }
+
+    #[test]
+    fn parses_the_table() {
+        let text = "# A comment\n2024-05-01 09:30:00  2024-05-01 09:55:00\n\n2024-05-01 09:00  2024-05-01 09:25\n2024-05-01 10:00:00\n";
+        let pomodoros = parse(text).unwrap();
+        assert_eq!(pomodoros.len(), 3);
+        // Sorted by their start
+        assert_eq!(format_time(pomodoros[0].start_time), "2024-05-01 09:00:00");
+        assert_eq!(pomodoros[1].end_time.map(format_time).as_deref(), Some("2024-05-01 09:55:00"));
+        assert_eq!(pomodoros[2].end_time, None);
+    }
+
+    #[test]
+    fn rejects_invalid_lines() {
+        let problems = problems("2024-05-01\n2024-05-01 9am\n");
+        assert_eq!(problems.len(), 2);
+        assert!(problems[0].contains("Expected a start and an optional end."));
+        assert!(problems[1].contains("is not a time like"));
+    }
+
+    #[test]
+    fn rejects_pomodoros_that_end_before_they_start() {
+        let problems = problems("2024-05-01 09:25:00  2024-05-01 09:00:00\n");
+        assert_eq!(problems, ["The pomodoro started at 2024-05-01 09:25:00 ends before it starts."]);
+    }
+
+    #[test]
+    fn rejects_future_times() {
+        let problems = problems("2999-05-01 09:00:00  2999-05-01 09:25:00\n");
+        assert_eq!(problems, ["The pomodoro started at 2999-05-01 09:00:00 is in the future."]);
+    }
+
+    #[test]
+    fn rejects_overlapping_pomodoros() {
+        let problems = problems("2024-05-01 09:00:00  2024-05-01 09:30:00\n2024-05-01 09:25:00  2024-05-01 09:50:00\n");
+        assert_eq!(problems, ["The pomodoros started at 2024-05-01 09:00:00 and 2024-05-01 09:25:00 overlap."]);
+    }
+
+    #[test]
+    fn only_the_last_pomodoro_may_run() {
+        let problems = problems("2024-05-01 09:00:00\n2024-05-01 10:00:00  2024-05-01 10:25:00\n");
+        assert_eq!(problems, ["The pomodoro started at 2024-05-01 09:00:00 is running, only the last one may be."]);
+    }
+}
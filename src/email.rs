@@ -0,0 +1,28 @@
+use lettre::message::header::ContentType;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Message, SmtpTransport, Transport};
+
+use crate::config::{EmailConfig, SmtpSecurity};
+
+pub fn send(config: &EmailConfig, subject: &str, body: String) -> Result<(), String> {
+    let server = config.server.as_deref().ok_or("email.server is not set")?;
+    let from = config.from.as_deref().ok_or("email.from is not set")?;
+    let to = config.to.as_deref().unwrap_or(from);
+    let message = Message::builder()
+        .from(from.parse().map_err(|e| format!("invalid email.from: {}", e))?)
+        .to(to.parse().map_err(|e| format!("invalid email.to: {}", e))?)
+        .subject(subject)
+        .header(ContentType::TEXT_PLAIN)
+        .body(body)
+        .map_err(|e| e.to_string())?;
+    let mut builder = match config.security {
+        SmtpSecurity::Tls => SmtpTransport::relay(server).map_err(|e| e.to_string())?,
+        SmtpSecurity::Starttls => SmtpTransport::starttls_relay(server).map_err(|e| e.to_string())?,
+        SmtpSecurity::None => SmtpTransport::builder_dangerous(server),
+    }.port(config.port);
+    if let Some(username) = &config.username {
+        builder = builder.credentials(Credentials::new(username.clone(), config.password.clone().unwrap_or_default()));
+    }
+    builder.build().send(&message).map_err(|e| e.to_string())?;
+    Ok(())
+}
@@ -0,0 +1,61 @@
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::recovery::Damage;
+
+/// Failures of reading, writing or changing the task list. pt prints them as a message and exits with an
+/// exit code that depends on the kind of failure, instead of panicking with a backtrace.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// Reading or writing a file failed, action is e.g. "read" or "write"
+    Io { action: &'static str, path: PathBuf, source: io::Error },
+    /// Another process held the lock on the task file for longer than --wait
+    Locked(PathBuf),
+    /// The file can't be parsed, what is e.g. "task file"
+    Corrupt { what: &'static str, path: PathBuf, message: String },
+    /// The task or archive file can't be parsed and waits for the user to choose how to recover it,
+    /// see Storage::recover_with
+    Recoverable(Box<Damage>),
+    /// There is no task with the ID
+    NotFound(u32),
+    /// The task is not in the state the operation needs, e.g. its pomodoro is already running
+    Conflict(String),
+    /// The changes would break an invariant of the task list, e.g. two tasks with the same ID, so
+    /// they were not saved. Each problem names the broken task or pomodoro.
+    Invalid(Vec<String>),
+    Other(String),
+}
+
+impl Error {
+    /// For map_err, e.g. fs::read(path).map_err(Error::io("read", path))
+    pub fn io(action: &'static str, path: &Path) -> impl FnOnce(io::Error) -> Error {
+        let path = path.to_path_buf();
+        move |source| Error::Io { action, path, source }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io { action, path, source } => write!(f, "Failed to {} {}: {}", action, path.display(), source),
+            Error::Locked(path) => write!(f, "The task file {} is locked by another process. Use --wait [seconds] to wait longer.", path.display()),
+            Error::Corrupt { what, path, message } => write!(f, "The {} {} is corrupt: {}", what, path.display(), message),
+            Error::Recoverable(damage) => write!(f, "The task file {} is corrupt: {}", damage.path.display(), damage.message),
+            Error::NotFound(id) => write!(f, "Task {} not found.", id),
+            Error::Conflict(message) => f.write_str(message),
+            Error::Invalid(problems) => write!(f, "The changes were not saved, they would break the task list: {}.", problems.join(", ")),
+            Error::Other(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::Task;
+
+// Things that happened to the tasks during a command, found by comparing the tasks before and after it
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    PomodoroStarted { task: Task },
+    PomodoroFinished { task: Task },
+    TaskChecked { task: Task },
+}
+
+impl Event {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::PomodoroStarted { .. } => "pomodoro_started",
+            Event::PomodoroFinished { .. } => "pomodoro_finished",
+            Event::TaskChecked { .. } => "task_checked",
+        }
+    }
+
+    pub fn task(&self) -> &Task {
+        match self {
+            Event::PomodoroStarted { task } | Event::PomodoroFinished { task } | Event::TaskChecked { task } => task,
+        }
+    }
+
+    pub fn payload<'a>(&'a self, list: &'a str) -> Payload<'a> {
+        Payload { event: self, list, time: Utc::now() }
+    }
+}
+
+// JSON sent to webhooks and hooks
+#[derive(Serialize)]
+pub struct Payload<'a> {
+    #[serde(flatten)]
+    event: &'a Event,
+    list: &'a str,
+    time: DateTime<Utc>,
+}
+
+pub fn detect(before: &[Task], after: &[Task]) -> Vec<Event> {
+    let mut events = Vec::new();
+    for task in after {
+        let old = before.iter().find(|old| old.id == task.id);
+        let started = task.pomodoros.iter().any(|pomodoro| {
+            pomodoro.end_time.is_none() && !old.is_some_and(|old| old.pomodoros.iter().any(|p| p.start_time == pomodoro.start_time))
+        });
+        if started {
+            events.push(Event::PomodoroStarted { task: task.clone() });
+        }
+        let finished = task.pomodoros.iter().any(|pomodoro| {
+            pomodoro.end_time.is_some() && old.is_some_and(|old| old.pomodoros.iter().any(|p| p.start_time == pomodoro.start_time && p.end_time.is_none()))
+        });
+        if finished {
+            events.push(Event::PomodoroFinished { task: task.clone() });
+        }
+        if task.done && !old.is_some_and(|old| old.done) {
+            events.push(Event::TaskChecked { task: task.clone() });
+        }
+    }
+    events
+}
@@ -0,0 +1,96 @@
+use std::fs;
+use std::path::Path;
+use std::process::{Command, ExitStatus};
+
+use crate::journal::JournalEvent;
+use crate::Task;
+
+// Files in the data directory that are not worth versioning
+const GITIGNORE: &str = "backups/\n*.tmp\n*.corrupt-*\nstate.json\nstate.lock\n";
+// Key of the commit message trailers naming the tasks worked on
+const TRAILER_KEY: &str = "Task";
+
+fn run_git(repo_dir: &Path, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo_dir)
+        .args(args)
+        .output()
+        .map_err(|e| format!("failed to run git: {}", e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+fn commit_message(changes: &[JournalEvent]) -> String {
+    let descriptions: Vec<String> = changes.iter().map(JournalEvent::describe).collect();
+    let mut message = match descriptions.len() {
+        1 => descriptions[0].clone(),
+        n => format!("{} (+{} more)\n\n", descriptions[0], n - 1),
+    };
+    if descriptions.len() > 1 {
+        message.push_str(&descriptions.join("\n"));
+    }
+    message
+}
+
+// Commits all files of the data directory, creating the repository if necessary
+pub fn commit(repo_dir: &Path, changes: &[JournalEvent], push: bool) -> Result<(), String> {
+    if !repo_dir.join(".git").exists() {
+        run_git(repo_dir, &["init", "--quiet"])?;
+        fs::write(repo_dir.join(".gitignore"), GITIGNORE).map_err(|e| e.to_string())?;
+    }
+    run_git(repo_dir, &["add", "--all"])?;
+    if run_git(repo_dir, &["status", "--porcelain"])?.trim().is_empty() {
+        return Ok(());
+    }
+    run_git(repo_dir, &["commit", "--quiet", "-m", &commit_message(changes)])?;
+    if push {
+        run_git(repo_dir, &["push", "--quiet"])?;
+    }
+    Ok(())
+}
+
+// Name of the repository the directory is in and its checked out branch
+pub fn current_branch(dir: &Path) -> Result<(String, String), String> {
+    let toplevel = run_git(dir, &["rev-parse", "--show-toplevel"])?;
+    let repository = Path::new(toplevel.trim()).file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .ok_or("the repository has no name")?;
+    // Fails with a detached HEAD, but works before the first commit
+    let branch = run_git(dir, &["symbolic-ref", "--short", "HEAD"]).map_err(|_| "no branch is checked out")?;
+    Ok((repository, branch.trim().to_string()))
+}
+
+// Trailers like "Task: #12 Write report" for the tasks with a running pomodoro
+pub fn task_trailers(tasks: &[Task]) -> Vec<String> {
+    tasks.iter()
+        .filter(|task| task.pomodoro_active())
+        .map(|task| format!("{}: #{} {}", TRAILER_KEY, task.id, task.description))
+        .collect()
+}
+
+// Adds the trailers to a commit message file, unless it has them already, e.g. when amending
+pub fn add_trailers(message_file: &Path, trailers: &[String]) -> Result<(), String> {
+    let mut command = Command::new("git");
+    command.args(["interpret-trailers", "--in-place", "--if-exists", "addIfDifferent"]);
+    for trailer in trailers {
+        command.arg("--trailer").arg(trailer);
+    }
+    let output = command.arg(message_file).output().map_err(|e| format!("failed to run git: {}", e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(())
+}
+
+// Runs git with the given arguments in the data directory, e.g. to pull or to inspect the history
+pub fn passthrough(repo_dir: &Path, args: &[String]) -> Result<ExitStatus, String> {
+    Command::new("git")
+        .arg("-C")
+        .arg(repo_dir)
+        .args(args)
+        .status()
+        .map_err(|e| format!("failed to run git: {}", e))
+}
@@ -0,0 +1,58 @@
+use serde::Deserialize;
+
+use crate::config::GithubConfig;
+use crate::http;
+use crate::issues::{self, ImportReport, Issue};
+use crate::Task;
+
+const PAGE_SIZE: usize = 100;
+
+#[derive(Deserialize)]
+struct User {
+    login: String,
+}
+
+#[derive(Deserialize)]
+struct GithubIssue {
+    title: String,
+    html_url: String,
+    state: String,
+    // Pull requests are issues too in the API
+    pull_request: Option<serde_json::Value>,
+}
+
+fn get<T: serde::de::DeserializeOwned>(config: &GithubConfig, token: &str, path: &str) -> Result<T, String> {
+    http::agent().get(&format!("{}{}", config.api_url.trim_end_matches('/'), path))
+        .set("Authorization", &format!("Bearer {}", token))
+        .set("Accept", "application/vnd.github+json")
+        .call()
+        .map_err(http::error_message)?
+        .into_json()
+        .map_err(|e| e.to_string())
+}
+
+// Imports the issues of the repository that are assigned to the user
+pub fn import(config: &GithubConfig, repo: &str, last_id: u32, tasks: &mut Vec<Task>) -> Result<ImportReport, String> {
+    if repo.split('/').count() != 2 {
+        return Err(format!("{} is not a repository, use owner/repo", repo));
+    }
+    let token = config.token.clone()
+        .or_else(|| std::env::var("GITHUB_TOKEN").ok())
+        .ok_or("No GitHub token configured. Set github.token in the config file or GITHUB_TOKEN.")?;
+    let user: User = get(config, &token, "/user")?;
+    let mut issues = Vec::new();
+    for page in 1.. {
+        let path = format!("/repos/{}/issues?assignee={}&state=all&per_page={}&page={}", repo, user.login, PAGE_SIZE, page);
+        let batch: Vec<GithubIssue> = get(config, &token, &path)?;
+        let last_page = batch.len() < PAGE_SIZE;
+        issues.extend(batch.into_iter().filter(|issue| issue.pull_request.is_none()));
+        if last_page {
+            break;
+        }
+    }
+
+    let issues = issues.into_iter()
+        .map(|issue| Issue { closed: issue.state == "closed", title: issue.title, url: issue.html_url })
+        .collect();
+    Ok(issues::apply(issues, repo.split('/').nth(1).unwrap_or(repo), last_id, tasks))
+}
@@ -0,0 +1,106 @@
+use std::path::Path;
+
+use chrono::{DateTime, Local, Utc};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::config::GitlabConfig;
+use crate::http;
+use crate::issues::{self, ImportReport, Issue};
+use crate::logged::Logged;
+use crate::Task;
+
+const PAGE_SIZE: usize = 100;
+// Remembers the pomodoros whose time was spent on their issue, so it is never added twice
+const GITLAB_STATE_FILE: &str = "gitlab.json";
+
+#[derive(Deserialize)]
+struct GitlabIssue {
+    title: String,
+    web_url: String,
+    state: String,
+}
+
+fn token(config: &GitlabConfig) -> Result<String, String> {
+    config.token.clone()
+        .or_else(|| std::env::var("GITLAB_TOKEN").ok())
+        .ok_or("No GitLab token configured. Set gitlab.token in the config file or GITLAB_TOKEN.".to_string())
+}
+
+fn api_url(config: &GitlabConfig, path: &str) -> String {
+    format!("{}/api/v4{}", config.url.trim_end_matches('/'), path)
+}
+
+// Projects are addressed by their path with the slashes encoded
+fn project_id(project: &str) -> String {
+    project.replace('/', "%2F")
+}
+
+// Imports the issues of the project that are assigned to the user
+pub fn import(config: &GitlabConfig, project: &str, last_id: u32, tasks: &mut Vec<Task>) -> Result<ImportReport, String> {
+    let token = token(config)?;
+    let mut issues = Vec::new();
+    for page in 1.. {
+        let path = format!("/projects/{}/issues?scope=assigned_to_me&state=all&per_page={}&page={}", project_id(project), PAGE_SIZE, page);
+        let batch: Vec<GitlabIssue> = http::agent().get(&api_url(config, &path))
+            .set("PRIVATE-TOKEN", &token)
+            .call()
+            .map_err(http::error_message)?
+            .into_json()
+            .map_err(|e| e.to_string())?;
+        let last_page = batch.len() < PAGE_SIZE;
+        issues.extend(batch.into_iter().map(|issue| Issue { closed: issue.state == "closed", title: issue.title, url: issue.web_url }));
+        if last_page {
+            break;
+        }
+    }
+    Ok(issues::apply(issues, project.rsplit('/').next().unwrap_or(project), last_id, tasks))
+}
+
+// Project path and issue number from the web URL of an issue, e.g.
+// https://gitlab.com/group/project/-/issues/12
+fn parse_issue_url<'a>(config: &GitlabConfig, url: &'a str) -> Option<(&'a str, &'a str)> {
+    let path = url.strip_prefix(config.url.trim_end_matches('/'))?.trim_start_matches('/');
+    let (project, iid) = path.split_once("/-/issues/")?;
+    Some((project, iid))
+}
+
+fn spend(config: &GitlabConfig, token: &str, project: &str, iid: &str, minutes: i64, start_time: DateTime<Utc>) -> Result<(), String> {
+    let path = format!("/projects/{}/issues/{}/notes", project_id(project), iid);
+    let date = start_time.with_timezone(&Local).date_naive();
+    http::agent().post(&api_url(config, &path))
+        .set("PRIVATE-TOKEN", token)
+        .send_json(json!({ "body": format!("/spend {}m {}", minutes, date) }))
+        .map_err(http::error_message)?;
+    Ok(())
+}
+
+// Adds the time of finished pomodoros of imported issues as /spend quick actions, returns the
+// number of pomodoros
+pub fn push_spent_time(config: &GitlabConfig, list_dir: &Path, tasks: &[Task]) -> Result<usize, String> {
+    let token = token(config)?;
+    let mut logged = Logged::load(list_dir, GITLAB_STATE_FILE);
+    let mut count = 0;
+    for task in tasks {
+        let (project, iid) = match task.url.as_deref().and_then(|url| parse_issue_url(config, url)) {
+            Some(issue) => issue,
+            None => continue,
+        };
+        for (pomodoro, end_time) in logged.pending(task) {
+            // GitLab counts whole minutes
+            let minutes = ((end_time - pomodoro.start_time).num_seconds() + 30) / 60;
+            if minutes == 0 {
+                continue;
+            }
+            if let Err(e) = spend(config, &token, project, iid, minutes, pomodoro.start_time) {
+                // Keep what was added so far
+                logged.save()?;
+                return Err(format!("failed to add spent time to {}: {}", task.url.as_deref().unwrap_or_default(), e));
+            }
+            logged.add(task, pomodoro, None);
+            count += 1;
+        }
+    }
+    logged.save()?;
+    Ok(count)
+}
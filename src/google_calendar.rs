@@ -0,0 +1,182 @@
+use std::path::Path;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+use serde_json::json;
+use tiny_http::{Response, Server};
+
+use crate::config::GoogleCalendarConfig;
+use crate::event::Event;
+use crate::state::{self, CalendarEvent};
+use crate::{http, POMODORO_DURATION};
+
+const AUTH_URL: &str = "https://accounts.google.com/o/oauth2/v2/auth";
+const TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
+const API_URL: &str = "https://www.googleapis.com/calendar/v3";
+const SCOPE: &str = "https://www.googleapis.com/auth/calendar.events";
+// Focus blocks of pomodoros cancelled within this time are deleted instead of shortened
+const MIN_BLOCK_SECONDS: i64 = 60;
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    refresh_token: Option<String>,
+}
+
+fn credentials(config: &GoogleCalendarConfig) -> Option<(&str, &str)> {
+    Some((config.client_id.as_deref()?, config.client_secret.as_deref()?))
+}
+
+// Lets the user allow pt to access the calendar in the browser and returns the refresh token.
+// Google redirects to a server on localhost with the authorization code.
+pub fn authorize(config: &GoogleCalendarConfig) -> Result<String, String> {
+    let (client_id, client_secret) = credentials(config)
+        .ok_or("Set google_calendar.client_id and google_calendar.client_secret in the config file first.")?;
+    let server = Server::http("127.0.0.1:0").map_err(|e| e.to_string())?;
+    let port = server.server_addr().to_ip().ok_or("failed to listen on localhost")?.port();
+    let redirect_uri = format!("http://127.0.0.1:{}", port);
+    let url = format!("{}?client_id={}&redirect_uri={}&response_type=code&scope={}&access_type=offline&prompt=consent",
+        AUTH_URL, http::percent_encode(client_id), http::percent_encode(&redirect_uri), http::percent_encode(SCOPE));
+    println!("Open this page to allow pt to add events to your calendar:");
+    println!("{}", url);
+    let _ = crate::open_url(&url);
+
+    let request = server.recv().map_err(|e| e.to_string())?;
+    let code = request.url().split(['?', '&'])
+        .find_map(|parameter| parameter.strip_prefix("code="))
+        .map(http::percent_decode);
+    let _ = request.respond(Response::from_string(match code {
+        Some(_) => "pt is allowed to access your calendar, you can close this page.",
+        None => "pt was not allowed to access your calendar.",
+    }));
+    let code = code.ok_or("access was denied")?;
+    let response: TokenResponse = http::agent().post(TOKEN_URL)
+        .send_form(&[
+            ("code", &code),
+            ("client_id", client_id),
+            ("client_secret", client_secret),
+            ("redirect_uri", &redirect_uri),
+            ("grant_type", "authorization_code"),
+        ])
+        .map_err(http::error_message)?
+        .into_json()
+        .map_err(|e| e.to_string())?;
+    response.refresh_token.ok_or("Google returned no refresh token".to_string())
+}
+
+fn access_token(config: &GoogleCalendarConfig, refresh_token: &str) -> Result<String, String> {
+    let (client_id, client_secret) = credentials(config).ok_or("google_calendar.client_id or client_secret is not set")?;
+    let response: TokenResponse = http::agent().post(TOKEN_URL)
+        .send_form(&[
+            ("refresh_token", refresh_token),
+            ("client_id", client_id),
+            ("client_secret", client_secret),
+            ("grant_type", "refresh_token"),
+        ])
+        .map_err(http::error_message)?
+        .into_json()
+        .map_err(|e| e.to_string())?;
+    Ok(response.access_token)
+}
+
+fn events_url(config: &GoogleCalendarConfig) -> String {
+    format!("{}/calendars/{}/events", API_URL, http::percent_encode(&config.calendar_id))
+}
+
+fn create_block(config: &GoogleCalendarConfig, token: &str, description: &str, start_time: DateTime<Utc>) -> Result<String, String> {
+    let end_time = start_time + Duration::minutes(POMODORO_DURATION);
+    let mut body = json!({
+        "summary": config.title,
+        "start": { "dateTime": start_time.to_rfc3339() },
+        "end": { "dateTime": end_time.to_rfc3339() },
+        "transparency": "opaque",
+    });
+    if config.show_description {
+        body["description"] = json!(description);
+    }
+    let response: serde_json::Value = http::agent().post(&events_url(config))
+        .set("Authorization", &format!("Bearer {}", token))
+        .send_json(body)
+        .map_err(http::error_message)?
+        .into_json()
+        .map_err(|e| e.to_string())?;
+    response["id"].as_str().map(str::to_string).ok_or("Google returned no event ID".to_string())
+}
+
+// Shortens the block of a pomodoro that was finished early, or deletes it if it was cancelled right away
+fn end_block(config: &GoogleCalendarConfig, token: &str, block: &CalendarEvent, end_time: DateTime<Utc>) -> Result<(), String> {
+    if end_time >= block.start_time + Duration::minutes(POMODORO_DURATION) {
+        return Ok(());
+    }
+    let url = format!("{}/{}", events_url(config), block.event_id);
+    let authorization = format!("Bearer {}", token);
+    let result = if (end_time - block.start_time).num_seconds() < MIN_BLOCK_SECONDS {
+        http::agent().delete(&url).set("Authorization", &authorization).call()
+    } else {
+        http::agent().request("PATCH", &url)
+            .set("Authorization", &authorization)
+            .send_json(json!({ "end": { "dateTime": end_time.to_rfc3339() } }))
+    };
+    result.map(|_| ()).map_err(http::error_message)
+}
+
+// Blocks the time of started pomodoros in the calendar and updates the blocks of pomodoros that
+// were finished early
+pub fn update(config: &GoogleCalendarConfig, data_dir: &Path, events: &[Event], list_name: &str) {
+    let refresh_token = match &config.refresh_token {
+        Some(refresh_token) => refresh_token,
+        None => return,
+    };
+    if !events.iter().any(|event| matches!(event, Event::PomodoroStarted { .. } | Event::PomodoroFinished { .. })) {
+        return;
+    }
+    let token = match access_token(config, refresh_token) {
+        Ok(token) => token,
+        Err(e) => {
+            println!("Failed to access Google Calendar: {}", e);
+            return;
+        }
+    };
+    let _lock = state::lock(data_dir);
+    let mut state = state::load_state(data_dir);
+    for event in events {
+        match event {
+            Event::PomodoroStarted { task } => {
+                let pomodoro = match task.pomodoros.iter().find(|pomodoro| pomodoro.end_time.is_none()) {
+                    Some(pomodoro) => pomodoro,
+                    None => continue,
+                };
+                match create_block(config, &token, &task.description, pomodoro.start_time) {
+                    Ok(event_id) => state.calendar_events.push(CalendarEvent {
+                        list: list_name.to_string(),
+                        task_id: task.id,
+                        start_time: pomodoro.start_time,
+                        event_id,
+                    }),
+                    Err(e) => println!("Failed to add the focus block to Google Calendar: {}", e),
+                }
+            },
+            Event::PomodoroFinished { task } => {
+                for pomodoro in &task.pomodoros {
+                    let end_time = match pomodoro.end_time {
+                        Some(end_time) => end_time,
+                        None => continue,
+                    };
+                    let position = state.calendar_events.iter()
+                        .position(|block| block.list == list_name && block.task_id == task.id && block.start_time == pomodoro.start_time);
+                    if let Some(position) = position {
+                        let block = state.calendar_events.remove(position);
+                        if let Err(e) = end_block(config, &token, &block, end_time) {
+                            println!("Failed to update the focus block in Google Calendar: {}", e);
+                        }
+                    }
+                }
+            },
+            Event::TaskChecked { .. } => {},
+        }
+    }
+    // Blocks of pomodoros that were never finished by pt end by themselves
+    let now = Utc::now();
+    state.calendar_events.retain(|block| block.start_time + Duration::days(1) > now);
+    state::save_state(data_dir, &state);
+}
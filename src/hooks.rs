@@ -0,0 +1,63 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::event::Event;
+
+// User scripts in this subdirectory of the data directory run on events
+const HOOKS_DIR: &str = "hooks";
+
+fn hook_name(event: &Event) -> &'static str {
+    match event {
+        Event::PomodoroStarted { .. } => "on-pomodoro-start",
+        Event::PomodoroFinished { .. } => "on-pomodoro-end",
+        Event::TaskChecked { .. } => "on-task-done",
+    }
+}
+
+// The script may have an extension, e.g. on-task-done.bat on Windows
+fn find_hook(dir: &Path, name: &str) -> Option<PathBuf> {
+    fs::read_dir(dir).ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .find(|path| path.is_file() && path.file_stem().is_some_and(|stem| stem == name))
+}
+
+fn run_hook(path: &Path, event: &Event, list_name: &str) -> Result<(), String> {
+    let task = event.task();
+    let mut child = Command::new(path)
+        .env("PT_EVENT", event.name())
+        .env("PT_LIST", list_name)
+        .env("PT_TASK_ID", task.id.to_string())
+        .env("PT_TASK_DESCRIPTION", &task.description)
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| e.to_string())?;
+    let payload = serde_json::to_string(&event.payload(list_name)).expect("Failed to serialize event.");
+    if let Some(mut stdin) = child.stdin.take() {
+        // Scripts that don't read the event close stdin early, which is fine
+        let _ = stdin.write_all(payload.as_bytes());
+    }
+    let status = child.wait().map_err(|e| e.to_string())?;
+    match status.success() {
+        true => Ok(()),
+        false => Err(status.to_string()),
+    }
+}
+
+// Runs the hook script of each event, with the event as JSON on stdin and its details in
+// environment variables
+pub fn run(data_dir: &Path, events: &[Event], list_name: &str) {
+    let dir = data_dir.join(HOOKS_DIR);
+    if !dir.is_dir() {
+        return;
+    }
+    for event in events {
+        if let Some(path) = find_hook(&dir, hook_name(event)) {
+            if let Err(e) = run_hook(&path, event, list_name) {
+                println!("Hook {} failed: {}", path.display(), e);
+            }
+        }
+    }
+}
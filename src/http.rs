@@ -0,0 +1,79 @@
+use std::time::Duration;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+// HTTP client used by all integrations
+pub fn agent() -> ureq::Agent {
+    ureq::AgentBuilder::new()
+        .timeout(TIMEOUT)
+        .user_agent(concat!("pt/", env!("CARGO_PKG_VERSION")))
+        .build()
+}
+
+// Value of the Authorization header for HTTP basic authentication
+pub fn basic_auth(username: &str, password: &str) -> String {
+    format!("Basic {}", STANDARD.encode(format!("{}:{}", username, password)))
+}
+
+// Percent-encodes a query parameter, all characters but the unreserved ones of RFC 3986
+pub fn percent_encode(value: &str) -> String {
+    value.bytes().map(|b| match b {
+        b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (b as char).to_string(),
+        _ => format!("%{:02X}", b),
+    }).collect()
+}
+
+// Decodes a percent-encoded query parameter, invalid escapes are kept as they are
+pub fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escape = bytes.get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escape {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            },
+            None => {
+                decoded.push(if bytes[i] == b'+' { b' ' } else { bytes[i] });
+                i += 1;
+            },
+        }
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+// Turns a ureq error into a message that includes the response body, which usually explains the problem
+pub fn error_message(error: ureq::Error) -> String {
+    match error {
+        ureq::Error::Status(code, response) => {
+            let body = response.into_string().unwrap_or_default();
+            format!("server responded with status {}: {}", code, body.trim())
+        },
+        ureq::Error::Transport(transport) => transport.to_string(),
+    }
+}
+
+// Like error_message, but without the URL of the request, for URLs that contain a secret like the
+// token of a Telegram bot
+pub fn error_message_without_url(error: ureq::Error) -> String {
+    match error {
+        ureq::Error::Transport(transport) => {
+            let mut message = transport.kind().to_string();
+            if let Some(detail) = transport.message() {
+                message.push_str(&format!(": {}", detail));
+            }
+            if let Some(source) = std::error::Error::source(&transport) {
+                message.push_str(&format!(": {}", source));
+            }
+            message
+        },
+        error => error_message(error),
+    }
+}
@@ -0,0 +1,104 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use fluent_bundle::concurrent::FluentBundle;
+use fluent_bundle::{FluentArgs, FluentResource, FluentValue};
+use unic_langid::LanguageIdentifier;
+
+// Messages and notifications are looked up by ID in Fluent files, e.g. task-added. The
+// translations in locales/ are built in, ~/.pt/locales/<language>.ftl adds or overrides messages
+// without rebuilding pt. Missing messages fall back to English.
+const LOCALES_DIR: &str = "locales";
+const ENGLISH: &str = include_str!("../locales/en.ftl");
+const BUILT_IN: &[(&str, &str)] = &[
+    ("de", include_str!("../locales/de.ftl")),
+];
+
+// The bundle of the language and the English one it falls back to
+static BUNDLES: OnceLock<Vec<FluentBundle<FluentResource>>> = OnceLock::new();
+
+fn resource(source: String, name: &str) -> FluentResource {
+    FluentResource::try_new(source).unwrap_or_else(|(resource, errors)| {
+        println!("Skipped {} invalid entries of the translation {}.", errors.len(), name);
+        resource
+    })
+}
+
+fn bundle(language: &LanguageIdentifier, resources: Vec<FluentResource>) -> FluentBundle<FluentResource> {
+    let mut bundle = FluentBundle::new_concurrent(vec![language.clone()]);
+    // Unicode isolation marks around values would end up in terminals and status bars
+    bundle.set_use_isolating(false);
+    for resource in resources {
+        bundle.add_resource_overriding(resource);
+    }
+    bundle
+}
+
+// Language from LC_ALL, LC_MESSAGES or LANG, e.g. de_DE.UTF-8
+fn system_language() -> Option<String> {
+    ["LC_ALL", "LC_MESSAGES", "LANG"].iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .map(|value| value.split(['.', '@']).next().unwrap_or_default().replace('_', "-"))
+        .filter(|language| language != "C" && language != "POSIX")
+}
+
+// Translations of the language, built in or in the data directory, the more specific ones last
+fn translations(language: &LanguageIdentifier, data_dir: &Path) -> Vec<FluentResource> {
+    let code = language.language.as_str();
+    let mut resources = Vec::new();
+    if let Some((_, source)) = BUILT_IN.iter().find(|(built_in, _)| *built_in == code) {
+        resources.push(resource(source.to_string(), &format!("{}.ftl", code)));
+    }
+    let mut names = vec![code.to_string()];
+    if language.region.is_some() {
+        names.push(language.to_string());
+    }
+    for name in names {
+        let path = data_dir.join(LOCALES_DIR).join(format!("{}.ftl", name));
+        if let Ok(source) = fs::read_to_string(&path) {
+            resources.push(resource(source, &path.display().to_string()));
+        }
+    }
+    resources
+}
+
+// Selects the language, the one of the config file or else the one of the system
+pub fn init(language: Option<&str>, data_dir: &Path) {
+    BUNDLES.get_or_init(|| {
+        let english: LanguageIdentifier = "en".parse().expect("Invalid language identifier.");
+        let language = language.map(str::to_string).or_else(system_language)
+            .and_then(|language| language.parse::<LanguageIdentifier>().ok())
+            .unwrap_or_else(|| english.clone());
+        let mut english_resources = vec![resource(ENGLISH.to_string(), "en.ftl")];
+        let mut bundles = Vec::new();
+        if language.language == english.language {
+            english_resources.extend(translations(&language, data_dir));
+        } else {
+            bundles.push(bundle(&language, translations(&language, data_dir)));
+        }
+        bundles.push(bundle(&english, english_resources));
+        bundles
+    });
+}
+
+// The message in the selected language, e.g. text("task-added", &[("id", 3.into())])
+pub fn text(id: &str, values: &[(&str, FluentValue)]) -> String {
+    let bundles = BUNDLES.get_or_init(|| {
+        let english = "en".parse().expect("Invalid language identifier.");
+        vec![bundle(&english, vec![resource(ENGLISH.to_string(), "en.ftl")])]
+    });
+    let mut args = FluentArgs::new();
+    for (name, value) in values {
+        args.set(*name, value.clone());
+    }
+    for bundle in bundles {
+        if let Some(pattern) = bundle.get_message(id).and_then(|message| message.value()) {
+            let mut errors = Vec::new();
+            return bundle.format_pattern(pattern, Some(&args), &mut errors).to_string();
+        }
+    }
+    id.to_string()
+}
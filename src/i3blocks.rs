@@ -0,0 +1,90 @@
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use notify_rust::Notification;
+use serde::Deserialize;
+
+use crate::control::run_pt;
+use crate::status::{self, Format};
+use crate::{data_dir, pomodoros_today, state, Error, Task};
+
+const LEFT_BUTTON: u32 = 1;
+const RIGHT_BUTTON: u32 = 3;
+const UPDATE_INTERVAL: Duration = Duration::from_secs(1);
+
+// Click event i3blocks writes to stdin for blocks with format=json
+#[derive(Deserialize)]
+struct Click {
+    button: u32,
+}
+
+// Task a left click starts a pomodoro for: the one worked on last, or else the first active one
+fn next_task(tasks: &[Task]) -> Option<&Task> {
+    let active = || tasks.iter().filter(|task| !task.done && !task.archived);
+    active()
+        .filter_map(|task| task.pomodoros.last().map(|pomodoro| (task, pomodoro.start_time)))
+        .max_by_key(|(_, start_time)| *start_time)
+        .map(|(task, _)| task)
+        .or_else(|| active().next())
+}
+
+// Left click finishes the running pomodoros or starts one, right click shows the active tasks
+// unless notifications are disabled
+fn handle_click(click: Click, tasks: &[Task], exe: &Path, list_args: &[String], notify: bool) {
+    match click.button {
+        LEFT_BUTTON => {
+            let running: Vec<String> = tasks.iter().filter(|task| task.pomodoro_active()).map(|task| task.id.to_string()).collect();
+            if !running.is_empty() {
+                run_pt(exe, list_args, &[vec!["--finish-pomodoro".to_string()], running].concat());
+            } else if let Some(task) = next_task(tasks) {
+                run_pt(exe, list_args, &["--pomodoro".to_string(), task.id.to_string()]);
+            }
+        },
+        RIGHT_BUTTON if notify => {
+            let body: Vec<String> = tasks.iter()
+                .filter(|task| !task.done && !task.archived)
+                .map(|task| format!("{}: {}", task.id, task.display_description()))
+                .collect();
+            // stdout belongs to the bar, so there is no fallback
+            let _ = Notification::new().summary("Tasks").body(&body.join("\n")).appname("pt").show();
+        },
+        _ => {},
+    }
+}
+
+// Runs as a persistent i3blocks block (interval=persist, format=json), printing the status every
+// second and right after a click
+pub fn run(load: &dyn Fn() -> Result<Vec<Task>, Error>, exe: &Path, list_args: &[String], notify: bool) {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines().map_while(Result::ok) {
+            if let Ok(click) = serde_json::from_str::<Click>(&line) {
+                if sender.send(click).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+    let mut stdout = io::stdout();
+    let mut tasks = Vec::new();
+    loop {
+        // A task list that can't be read, e.g. because it is locked, keeps its last status until the next update
+        if let Ok(current) = load() {
+            tasks = current;
+        }
+        let line = status::render(&tasks, &state::load_state(&data_dir()), pomodoros_today(&tasks), Format::I3blocks);
+        // The bar was closed
+        if writeln!(stdout, "{}", line).and_then(|_| stdout.flush()).is_err() {
+            return;
+        }
+        match receiver.recv_timeout(UPDATE_INTERVAL) {
+            Ok(click) => handle_click(click, &tasks, exe, list_args, notify),
+            // Clicks can't arrive anymore, but the status still has to be updated
+            Err(RecvTimeoutError::Disconnected) => thread::sleep(UPDATE_INTERVAL),
+            Err(RecvTimeoutError::Timeout) => {},
+        }
+    }
+}
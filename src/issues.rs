@@ -0,0 +1,38 @@
+use crate::{next_task_id, Task};
+
+// Issue of an issue tracker, imported as a task
+pub struct Issue {
+    pub title: String,
+    pub url: String,
+    pub closed: bool,
+}
+
+pub struct ImportReport {
+    pub added: usize,
+    pub updated: usize,
+}
+
+// Adds the open issues as tasks of the project. Tasks imported before are matched by their URL
+// and checked or unchecked when their issue was closed or reopened.
+pub fn apply(issues: Vec<Issue>, project: &str, last_id: u32, tasks: &mut Vec<Task>) -> ImportReport {
+    let mut report = ImportReport { added: 0, updated: 0 };
+    for issue in issues {
+        match tasks.iter_mut().find(|task| task.url.as_deref() == Some(&issue.url)) {
+            Some(task) if task.done != issue.closed || task.description != issue.title => {
+                task.done = issue.closed;
+                task.description = issue.title;
+                report.updated += 1;
+            },
+            Some(_) => {},
+            None if !issue.closed => {
+                let mut task = Task::new(next_task_id(tasks, last_id), issue.title);
+                task.projects.push(project.to_string());
+                task.url = Some(issue.url);
+                tasks.push(task);
+                report.added += 1;
+            },
+            None => {},
+        }
+    }
+    report
+}
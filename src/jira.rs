@@ -0,0 +1,69 @@
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use serde_json::json;
+
+use crate::config::JiraConfig;
+use crate::http;
+use crate::logged::Logged;
+use crate::Task;
+
+// Remembers the pomodoros that were logged, so they are never logged twice
+const JIRA_STATE_FILE: &str = "jira.json";
+// Jira rejects shorter worklogs
+const MIN_WORKLOG_SECONDS: i64 = 60;
+
+// Jira Cloud uses the account email with an API token, Jira Server and Data Center a personal access token
+fn authorization(config: &JiraConfig, token: &str) -> String {
+    match &config.username {
+        Some(username) => http::basic_auth(username, token),
+        None => format!("Bearer {}", token),
+    }
+}
+
+fn add_worklog(config: &JiraConfig, url: &str, token: &str, issue: &str, task: &Task, start_time: DateTime<Utc>, seconds: i64) -> Result<String, String> {
+    let url = format!("{}/rest/api/2/issue/{}/worklog", url.trim_end_matches('/'), issue);
+    let body = json!({
+        "started": start_time.format("%Y-%m-%dT%H:%M:%S%.3f%z").to_string(),
+        "timeSpentSeconds": seconds,
+        "comment": format!("Pomodoro: {}", task.description),
+    });
+    let response: serde_json::Value = http::agent().post(&url)
+        .set("Authorization", &authorization(config, token))
+        .send_json(body)
+        .map_err(http::error_message)?
+        .into_json()
+        .map_err(|e| e.to_string())?;
+    response["id"].as_str().map(str::to_string).ok_or("Jira returned no worklog ID".to_string())
+}
+
+// Logs the finished pomodoros of tasks linked to an issue that weren't logged yet, returns their number
+pub fn push(config: &JiraConfig, list_dir: &Path, tasks: &[Task]) -> Result<usize, String> {
+    let url = config.url.as_ref().ok_or("No Jira URL configured. Set jira.url in the config file.")?;
+    let token = config.token.as_ref().ok_or("No Jira token configured. Set jira.token in the config file.")?;
+    let mut logged = Logged::load(list_dir, JIRA_STATE_FILE);
+    let mut count = 0;
+    for task in tasks {
+        let issue = match &task.jira {
+            Some(issue) => issue,
+            None => continue,
+        };
+        for (pomodoro, end_time) in logged.pending(task) {
+            let seconds = (end_time - pomodoro.start_time).num_seconds();
+            if seconds < MIN_WORKLOG_SECONDS {
+                continue;
+            }
+            match add_worklog(config, url, token, issue, task, pomodoro.start_time, seconds) {
+                Ok(worklog_id) => logged.add(task, pomodoro, Some(worklog_id)),
+                Err(e) => {
+                    // Keep what was logged so far
+                    logged.save()?;
+                    return Err(format!("failed to log work on {}: {}", issue, e));
+                }
+            }
+            count += 1;
+        }
+    }
+    logged.save()?;
+    Ok(count)
+}
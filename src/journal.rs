@@ -0,0 +1,174 @@
+use chrono::{DateTime, Utc};
+
+use serde::{Serialize, Deserialize};
+use serde_json::{Map, Value};
+
+use crate::{Pomodoro, Task};
+
+/// A single change to the task list. The journal is a sequence of these, one JSON object per line.
+#[derive(Clone)]
+#[derive(Serialize)]
+#[derive(Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum JournalEvent {
+    TaskAdded { task: Task },
+    // Holds all fields of the task except its ID and pomodoros
+    TaskChanged { id: u32, fields: Map<String, Value> },
+    TaskRemoved { id: u32 },
+    PomodoroStarted { task_id: u32, start_time: DateTime<Utc> },
+    PomodoroFinished { task_id: u32, end_time: DateTime<Utc> },
+    // Used for all other pomodoro changes, e.g. tracked time
+    PomodorosReplaced { task_id: u32, pomodoros: Vec<Pomodoro> },
+}
+
+#[derive(Serialize)]
+#[derive(Deserialize)]
+pub struct JournalEntry {
+    pub time: DateTime<Utc>,
+    #[serde(flatten)]
+    pub event: JournalEvent,
+}
+
+impl JournalEvent {
+    /// Short human readable description, e.g. for commit messages
+    pub fn describe(&self) -> String {
+        match self {
+            JournalEvent::TaskAdded { task } => format!("Add task {}: {}", task.id, task.description),
+            JournalEvent::TaskChanged { id, .. } => format!("Update task {}", id),
+            JournalEvent::TaskRemoved { id } => format!("Remove task {}", id),
+            JournalEvent::PomodoroStarted { task_id, .. } => format!("Start pomodoro for task {}", task_id),
+            JournalEvent::PomodoroFinished { task_id, .. } => format!("Finish pomodoro for task {}", task_id),
+            JournalEvent::PomodorosReplaced { task_id, .. } => format!("Update pomodoros of task {}", task_id),
+        }
+    }
+}
+
+fn task_fields(task: &Task) -> Map<String, Value> {
+    let mut fields = match serde_json::to_value(task).expect("Failed to serialize task.") {
+        Value::Object(fields) => fields,
+        _ => unreachable!(),
+    };
+    fields.remove("id");
+    fields.remove("pomodoros");
+    fields
+}
+
+fn diff_pomodoros(old: &[Pomodoro], new: &[Pomodoro], task_id: u32) -> Option<JournalEvent> {
+    if old == new {
+        return None;
+    }
+    let n = old.len();
+    if new.len() == n + 1 && new[..n] == old[..] && new[n].end_time.is_none() {
+        return Some(JournalEvent::PomodoroStarted { task_id, start_time: new[n].start_time });
+    }
+    if new.len() == n && n > 0 && new[..n - 1] == old[..n - 1]
+        && old[n - 1].end_time.is_none() && new[n - 1].start_time == old[n - 1].start_time && !new[n - 1].clock_went_back {
+        if let Some(end_time) = new[n - 1].end_time {
+            return Some(JournalEvent::PomodoroFinished { task_id, end_time });
+        }
+    }
+    Some(JournalEvent::PomodorosReplaced { task_id, pomodoros: new.to_vec() })
+}
+
+/// Computes the events that turn the old task list into the new one
+pub fn diff(old: &[Task], new: &[Task]) -> Vec<JournalEvent> {
+    let mut events = Vec::new();
+    for task in new {
+        match old.iter().find(|t| t.id == task.id) {
+            None => events.push(JournalEvent::TaskAdded { task: task.clone() }),
+            Some(old_task) => {
+                let fields = task_fields(task);
+                if fields != task_fields(old_task) {
+                    events.push(JournalEvent::TaskChanged { id: task.id, fields });
+                }
+                if let Some(event) = diff_pomodoros(&old_task.pomodoros, &task.pomodoros, task.id) {
+                    events.push(event);
+                }
+            }
+        }
+    }
+    for old_task in old {
+        if !new.iter().any(|t| t.id == old_task.id) {
+            events.push(JournalEvent::TaskRemoved { id: old_task.id });
+        }
+    }
+    events
+}
+
+/// Describes the changes between the task lists, with the new values of changed fields, e.g.
+/// "Update task 3: done = true"
+pub fn describe_diff(old: &[Task], new: &[Task]) -> Vec<String> {
+    diff(old, new).iter()
+        .map(|event| match event {
+            JournalEvent::TaskChanged { id, fields } => {
+                let old_fields = old.iter().find(|task| task.id == *id).map(task_fields).unwrap_or_default();
+                let changed: Vec<String> = fields.iter()
+                    .filter(|(key, value)| old_fields.get(*key) != Some(*value))
+                    .map(|(key, value)| format!("{} = {}", key, value))
+                    .collect();
+                format!("Update task {}: {}", id, changed.join(", "))
+            },
+            event => event.describe(),
+        })
+        .collect()
+}
+
+fn find_task(tasks: &mut [Task], id: u32) -> Result<&mut Task, String> {
+    tasks.iter_mut()
+        .find(|task| task.id == id)
+        .ok_or(format!("task {} does not exist", id))
+}
+
+/// Applies a single event to the task list
+pub fn apply(tasks: &mut Vec<Task>, event: JournalEvent) -> Result<(), String> {
+    match event {
+        JournalEvent::TaskAdded { task } => tasks.push(task),
+        JournalEvent::TaskChanged { id, fields } => {
+            let task = find_task(tasks, id)?;
+            let mut value = serde_json::to_value(&*task).map_err(|e| e.to_string())?;
+            for (key, field) in fields {
+                value[key] = field;
+            }
+            *task = serde_json::from_value(value).map_err(|e| e.to_string())?;
+        },
+        JournalEvent::TaskRemoved { id } => tasks.retain(|task| task.id != id),
+        JournalEvent::PomodoroStarted { task_id, start_time } => {
+            find_task(tasks, task_id)?.pomodoros.push(Pomodoro::new(start_time, None));
+        },
+        JournalEvent::PomodoroFinished { task_id, end_time } => {
+            let task = find_task(tasks, task_id)?;
+            match task.pomodoros.last_mut() {
+                Some(p) => p.end_time = Some(end_time),
+                None => return Err(format!("task {} has no pomodoro to finish", task_id)),
+            }
+        },
+        JournalEvent::PomodorosReplaced { task_id, pomodoros } => {
+            find_task(tasks, task_id)?.pomodoros = pomodoros;
+        },
+    }
+    Ok(())
+}
+
+/// Reconstructs the task list from the journal content
+pub fn replay(content: &str) -> Result<Vec<Task>, String> {
+    let mut tasks = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry: JournalEntry = serde_json::from_str(line).map_err(|e| format!("line {}: {}", i + 1, e))?;
+        apply(&mut tasks, entry.event).map_err(|e| format!("line {}: {}", i + 1, e))?;
+    }
+    Ok(tasks)
+}
+
+pub fn serialize_events(events: &[JournalEvent]) -> String {
+    let mut content = String::new();
+    let time = Utc::now();
+    for event in events {
+        let entry = JournalEntry { time, event: event.clone() };
+        content.push_str(&serde_json::to_string(&entry).expect("Failed to serialize journal entry."));
+        content.push('\n');
+    }
+    content
+}
@@ -0,0 +1,67 @@
+//! The tasks and pomodoros of pt, how they are stored and reported. The pt command line is a
+//! frontend to this library, GUI frontends, bots and status bars can use it the same way:
+//!
+//! ```no_run
+//! use pt_core::TaskStore;
+//!
+//! let store = TaskStore::open("default", true)?;
+//! for task in store.running() {
+//!     let remaining = task.pomodoro_time_remaining().unwrap_or_default();
+//!     println!("{} {}:{:02}", task.description, remaining.num_minutes(), remaining.num_seconds() % 60);
+//! }
+//! # Ok::<(), pt_core::Error>(())
+//! ```
+//!
+//! `TaskStore`, `Task`, `Pomodoro`, `Clock` and `Error` follow semantic versioning, they only change
+//! incompatibly with a new major version. `Task` and `Pomodoro` may gain fields in minor versions,
+//! so create them with `Task::new`, `Pomodoro::new` or `TaskStore::add`. The modules are the building blocks of the pt
+//! command line and may change with any version.
+
+use std::path::PathBuf;
+use std::process::Command;
+
+pub mod backup;
+pub mod clock;
+pub mod config;
+pub mod crypto;
+pub mod error;
+pub mod journal;
+pub mod recovery;
+pub mod report;
+pub mod schema;
+pub mod storage;
+mod store;
+pub mod task;
+pub mod when;
+
+pub use clock::{Clock, FakeClock, SystemClock};
+pub use error::Error;
+pub use store::TaskStore;
+pub use task::{next_task_id, Pomodoro, Task, POMODORO_DURATION};
+
+/// Directory in the home directory that pt keeps its data in, e.g. ~/.pt/tasks.json
+const DATA_DIR: &str = ".pt";
+
+/// Directory of the task lists and the config file of pt, None if there is no home directory
+#[cfg(not(windows))]
+pub fn data_dir() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(DATA_DIR))
+}
+
+/// Windows keeps application data in %APPDATA%\pt, an existing ~\.pt is still used
+#[cfg(windows)]
+pub fn data_dir() -> Option<PathBuf> {
+    let home = dirs::home_dir()?.join(DATA_DIR);
+    match dirs::data_dir() {
+        Some(data) if !home.exists() => Some(data.join("pt")),
+        _ => Some(home),
+    }
+}
+
+/// Runs a command from the config file through the shell of the platform
+pub fn shell_command(command: &str) -> Command {
+    let (shell, flag) = if cfg!(windows) { ("cmd", "/C") } else { ("sh", "-c") };
+    let mut shell = Command::new(shell);
+    shell.arg(flag).arg(command);
+    shell
+}
@@ -0,0 +1,61 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Deserialize};
+
+use crate::{Pomodoro, Task};
+
+#[derive(Serialize)]
+#[derive(Deserialize)]
+struct LoggedPomodoro {
+    task_id: u32,
+    // Identifies the pomodoro within the task
+    start_time: DateTime<Utc>,
+    // ID of the entry the service created, e.g. a Jira worklog
+    #[serde(default, alias = "worklog_id", skip_serializing_if = "Option::is_none")]
+    remote_id: Option<String>,
+}
+
+#[derive(Default)]
+#[derive(Serialize)]
+#[derive(Deserialize)]
+struct LoggedFile {
+    #[serde(alias = "worklogs", alias = "spent")]
+    pomodoros: Vec<LoggedPomodoro>,
+}
+
+// Remembers the pomodoros that were sent to a time tracking service, in a file next to the tasks,
+// so none is sent twice
+pub struct Logged {
+    path: PathBuf,
+    file: LoggedFile,
+}
+
+impl Logged {
+    pub fn load(list_dir: &Path, file_name: &str) -> Logged {
+        let path = list_dir.join(file_name);
+        let file = fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        Logged { path, file }
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let content = serde_json::to_string(&self.file).expect("Failed to serialize logged pomodoros.");
+        fs::write(&self.path, content).map_err(|e| e.to_string())
+    }
+
+    // Finished pomodoros of the task that weren't logged yet, with their end time
+    pub fn pending<'a>(&self, task: &'a Task) -> Vec<(&'a Pomodoro, DateTime<Utc>)> {
+        task.pomodoros.iter()
+            .filter_map(|pomodoro| pomodoro.end_time.map(|end_time| (pomodoro, end_time)))
+            .filter(|(pomodoro, _)| !self.file.pomodoros.iter().any(|logged| logged.task_id == task.id && logged.start_time == pomodoro.start_time))
+            .collect()
+    }
+
+    pub fn add(&mut self, task: &Task, pomodoro: &Pomodoro, remote_id: Option<String>) {
+        self.file.pomodoros.push(LoggedPomodoro { task_id: task.id, start_time: pomodoro.start_time, remote_id });
+    }
+}
@@ -1,257 +1,1276 @@
 use std::env;
-use std::fs::{OpenOptions, File};
-use fs2::FileExt;
-use std::io::{self, Write, Seek, SeekFrom};
-use std::path::Path;
-use chrono::{Duration, Utc, DateTime};
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use chrono::{Duration, Utc, DateTime, NaiveDate};
 
-use serde::{Serialize, Deserialize};
-use notify_rust::{Notification, Timeout};
-use rodio::{Decoder, OutputStream, Sink};
+use notification::{NotificationContent, NotificationKind};
+use pt_core::{backup, config, crypto, journal, recovery, report, schema, storage, when};
+use pt_core::{next_task_id, shell_command, Clock, Error, Pomodoro, SystemClock, Task, TaskStore, POMODORO_DURATION};
 
-// Will automatically add HOME to the path
-// Hence, the path will be /home/username/.pt/tasks.json and /home/username/.pt/alarm.mp3
-const TASK_FILE: &str = ".pt/tasks.json";
-const ALARM_FILE: &str = ".pt/alarm.mp3";
-const POMODORO_DURATION: i64 = 25;
+mod activitywatch;
+mod agenda;
+mod blocklist;
+mod caldav;
+mod cli;
+mod clockify;
+mod color;
+mod compact;
+mod control;
+mod daemon;
+mod doctor;
+mod discord;
+mod dnd;
+mod edit;
+mod email;
+mod event;
+mod git;
+mod github;
+mod gitlab;
+mod google_calendar;
+mod hooks;
+mod http;
+mod i18n;
+mod i3blocks;
+mod issues;
+mod jira;
+mod logged;
+mod markdown;
+mod media;
+mod merge;
+mod mqtt;
+mod notification;
+mod org;
+mod output;
+mod pager;
+mod plugins;
+mod progress;
+mod push;
+mod relative;
+mod s3;
+mod state;
+mod taskwarrior;
+mod sound;
+mod select;
+mod server;
+mod service;
+#[cfg(unix)]
+mod socket;
+mod status;
+mod sync;
+mod table;
+mod telegram;
+mod todoist;
+mod todotxt;
+mod watch;
+mod webhook;
+mod wizard;
 
-struct NotificationContent {
-    title: String,
-    body: String,
+const BREAK_DURATION: i64 = 5;
+const SNOOZE_DURATION: i64 = 5;
+// Exit codes for scripts. pt --notify --quiet exits with 0 if it showed notifications
+const EXIT_ERROR: i32 = 1;
+const EXIT_NOTHING_DUE: i32 = 2;
+// Missing or invalid arguments, e.g. a task ID that is not a number
+const EXIT_USAGE: i32 = 3;
+const EXIT_NOT_FOUND: i32 = 4;
+// The task is not in the state the command needs, e.g. its pomodoro is already running
+const EXIT_CONFLICT: i32 = 5;
+// The task list stayed locked by another process for longer than --wait
+const EXIT_LOCKED: i32 = 6;
+// Commands that only change the task list, so --dry-run can show everything they would do
+const DRY_RUN_COMMANDS: &[&str] = &[
+    "--add", "--add-interactive", "--list", "-l", "--pomodoro", "-p", "--finish-pomodoro", "-f", "--track", "-t", "--here", "--check", "-c",
+    "--uncheck", "-u", "--archive", "-a", "--unarchive", "--archive-checked", "--due", "--jira", "--import", "--compact",
+];
+// Commands that never change the task list. They only take a shared lock, so they don't wait for
+// each other or for a status bar that lists the tasks every second.
+const READ_ONLY_COMMANDS: &[&str] = &["--list", "-l", "--list-archived", "--open", "--export", "--report", "--test-notification", "--help", "-h"];
+// Commands that print their result as JSON with --json
+const JSON_COMMANDS: &[&str] = &[
+    "--add", "--list", "-l", "--list-archived", "--pomodoro", "-p", "--finish-pomodoro", "-f", "--track", "-t", "--here",
+    "--check", "-c", "--uncheck", "-u", "--archive", "-a", "--unarchive", "--archive-checked", "--due", "--jira", "--report", "--status",
+];
+
+// Options that can be given before the command
+struct GlobalOptions {
+    list_name: String,
+    read_only: bool,
+    lock_timeout: std::time::Duration,
+    // Ignore project task files and use the task lists in the data directory
+    global: bool,
+    silent: bool,
+    // Print notifications instead of showing them on the desktop
+    no_notify: bool,
+    volume: Option<u32>,
+    // Print the result of the command as JSON
+    json: bool,
+    verbosity: output::Verbosity,
+    // Run the command but only print the changes instead of saving them, implies read_only
+    dry_run: bool,
+    no_pager: bool,
+    // Dates and times instead of relative ones in the task list
+    absolute: bool,
 }
 
-#[derive(Clone)]
-#[derive(Serialize)]
-#[derive(Deserialize)]
-struct Pomodoro {
-    start_time: DateTime<Utc>,
-    end_time: Option<DateTime<Utc>>,
+impl GlobalOptions {
+    // Arguments that select the same task list, for running pt again
+    fn list_args(&self) -> Vec<String> {
+        match self.global {
+            true => vec!["--list-name".to_string(), self.list_name.clone()],
+            false => Vec::new(),
+        }
+    }
 }
 
-#[derive(Clone)]
-#[derive(Serialize)]
-#[derive(Deserialize)]
-struct Task {
-    id: u32,
-    description: String,
-    done: bool,
-    archived: bool,
-    pomodoros: Vec<Pomodoro>,
+// Removes the global options from the front of the arguments
+fn parse_global_options(args: &mut Vec<String>) -> Result<GlobalOptions, String> {
+    let mut options = GlobalOptions {
+        list_name: storage::DEFAULT_LIST.to_string(),
+        read_only: false,
+        lock_timeout: storage::DEFAULT_LOCK_TIMEOUT,
+        global: false,
+        silent: false,
+        no_notify: false,
+        volume: None,
+        json: false,
+        verbosity: output::Verbosity::Normal,
+        dry_run: false,
+        no_pager: false,
+        absolute: false,
+    };
+    while args.len() > 1 {
+        match args[1].as_str() {
+            "--list-name" | "-n" => {
+                if args.len() < 3 {
+                    return Err("No list name specified.".to_string());
+                }
+                if !storage::valid_list_name(&args[2]) {
+                    return Err(format!("Invalid list name {}. Use letters, digits, - and _ only.", args[2]));
+                }
+                options.list_name = args[2].clone();
+                options.global = true;
+                args.drain(1..3);
+            },
+            "--wait" => {
+                let seconds = match args.get(2).map(|arg| arg.parse::<u64>()) {
+                    Some(Ok(seconds)) => seconds,
+                    _ => return Err("Invalid wait time. Specify the number of seconds to wait for the task file.".to_string()),
+                };
+                options.lock_timeout = std::time::Duration::from_secs(seconds);
+                args.drain(1..3);
+            },
+            "--global" | "-g" => {
+                options.global = true;
+                args.remove(1);
+            },
+            "--silent" | "--no-sound" => {
+                options.silent = true;
+                args.remove(1);
+            },
+            "--no-notify" => {
+                options.no_notify = true;
+                args.remove(1);
+            },
+            "--no-pager" => {
+                options.no_pager = true;
+                args.remove(1);
+            },
+            "--absolute" => {
+                options.absolute = true;
+                args.remove(1);
+            },
+            "--volume" => {
+                options.volume = match args.get(2).map(|arg| arg.parse::<u32>()) {
+                    Some(Ok(volume)) => Some(volume),
+                    _ => return Err("Invalid volume. Specify the volume in percent, e.g. --volume 50.".to_string()),
+                };
+                args.drain(1..3);
+            },
+            "--read-only" => {
+                options.read_only = true;
+                args.remove(1);
+            },
+            "--quiet" | "-q" => {
+                options.verbosity = output::Verbosity::Quiet;
+                args.remove(1);
+            },
+            "--verbose" | "-v" => {
+                options.verbosity = output::Verbosity::Verbose;
+                args.remove(1);
+            },
+            "--json" => {
+                options.json = true;
+                args.remove(1);
+            },
+            "--dry-run" => {
+                options.dry_run = true;
+                options.read_only = true;
+                args.remove(1);
+            },
+            _ => break,
+        }
+    }
+    Ok(options)
+}
+
+fn main() {
+    report_panics();
+    if panic::catch_unwind(run).is_err() {
+        std::process::exit(EXIT_ERROR);
+    }
+    // Commands report failures through output, so scripts can tell them apart by the exit code
+    if output::exit_code() != 0 {
+        std::process::exit(output::exit_code());
+    }
+}
+
+// Bugs end pt with a one line message instead of a backtrace, unless RUST_BACKTRACE is set. A closed
+// pipe, e.g. pt --list | head, ends it quietly.
+fn report_panics() {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let payload = info.payload();
+        let message = payload.downcast_ref::<&str>().copied()
+            .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("unknown error");
+        if message.contains("Broken pipe") {
+            return;
+        }
+        match env::var_os("RUST_BACKTRACE") {
+            Some(_) => default_hook(info),
+            None => eprintln!("Unexpected error: {}", message),
+        }
+    }));
 }
 
-impl Task {
-    fn new(id: u32, description: String) -> Task {
-        Task {
-            id,
-            description,
-            done: false,
-            archived: false,
-            pomodoros: Vec::new(),
+fn run() {
+    let mut args: Vec<String> = env::args().collect();
+    let options = match parse_global_options(&mut args) {
+        Ok(options) => options,
+        Err(e) => {
+            output::fail(EXIT_USAGE, e);
+            return;
+        }
+    };
+    output::init(options.json, options.verbosity);
+    let _json = output::JsonGuard;
+    let mut args = match cli::translate(args) {
+        Ok(args) => args,
+        Err(e) if options.json => {
+            let message = match e.use_stderr() {
+                true => e.render().to_string().lines().next().unwrap_or_default().trim_start_matches("error: ").to_string(),
+                false => "The help has no JSON output.".to_string(),
+            };
+            output::fail(EXIT_USAGE, message);
+            return;
+        },
+        Err(e) => {
+            let _ = e.print();
+            match e.use_stderr() {
+                true => std::process::exit(EXIT_USAGE),
+                false => return,
+            }
+        }
+    };
+    // pt <name> runs the plugin pt-<name> if there is one, otherwise the words are a new task
+    if let (Some(name), Some(data_dir)) = (args.get(1).filter(|arg| !arg.starts_with('-')), pt_core::data_dir()) {
+        if let Some(path) = plugins::find(name) {
+            if options.json {
+                output::fail(EXIT_USAGE, format!("The plugin {} has no JSON output.", name));
+                return;
+            }
+            let Some(exe) = current_exe() else { return };
+            match plugins::run(&path, &args[2..], &exe, &data_dir, &options.list_name) {
+                Ok(0) => (),
+                Ok(code) => std::process::exit(code),
+                Err(e) => output::error(format!("Failed to run {}: {}", path.display(), e)),
+            }
+            return;
+        }
+    }
+    if pt_core::data_dir().is_none() {
+        output::error("Failed to find the home directory. Set HOME to the directory pt should keep its data in.");
+        return;
+    }
+    if options.json && args.len() > 1 && args[1].starts_with('-') && !JSON_COMMANDS.contains(&args[1].as_str()) {
+        output::fail(EXIT_USAGE, format!("{} has no JSON output.", args[1]));
+        return;
+    }
+    if options.dry_run && args.len() > 1 && args[1].starts_with('-') && !DRY_RUN_COMMANDS.contains(&args[1].as_str()) {
+        output::fail(EXIT_USAGE, format!("{} doesn't support --dry-run.", args[1]));
+        return;
+    }
+    // Archived tasks are only loaded for the commands that need them
+    let include_archive = args.len() > 1 && matches!(args[1].as_str(), "--list-archived" | "--unarchive" | "--backup" | "--restore" | "--sync" | "--compact" | "--taskwarrior-hook"
+        | "--report" | "--email");
+
+    if args.len() > 1 && args[1] == "--lists" {
+        list_task_lists(&options.list_name);
+        return;
+    }
+
+    if args.len() > 1 && args[1] == "--plugins" {
+        let plugins = plugins::list();
+        if plugins.is_empty() {
+            println!("No plugins found. Plugins are executables named pt-<name> on the PATH.");
         }
+        for (name, path) in plugins {
+            println!("{} ({})", name, path.display());
+        }
+        return;
     }
 
-    fn time_spent(&self) -> Duration {
-        let mut time = Duration::zero();
-        for pomodoro in &self.pomodoros {
-            match pomodoro.end_time {
-                Some(end_time) => time = time + (end_time - pomodoro.start_time),
-                None => time = time + (Utc::now() - pomodoro.start_time),
+    // Runs before the config is loaded, as a broken config file is one of the problems it reports
+    if args.len() > 1 && args[1] == "--doctor" {
+        let project_file = match options.global {
+            true => None,
+            false => env::current_dir().ok().and_then(|dir| storage::find_project_file(&dir)),
+        };
+        if !doctor::run(&data_dir(), &options.list_name, project_file) {
+            std::process::exit(EXIT_ERROR);
+        }
+        return;
+    }
+
+    if args.len() > 1 && args[1] == "--merge-files" {
+        if args.len() < 4 {
+            output::fail(EXIT_USAGE, "Specify the two task files to merge.");
+            return;
+        }
+        let config = match config::load_config(&data_dir()) {
+            Ok(config) => config,
+            Err(e) => {
+                output::error(e.to_string());
+                return;
             }
+        };
+        crypto::init(&config.encryption);
+        match merge::merge_files(Path::new(&args[2]), Path::new(&args[3])) {
+            Ok(report) => {
+                println!("Merged {} into {}: {} tasks added, {} tasks merged, {} conflicts resolved.",
+                    args[3], args[2], report.added, report.merged, report.conflicts);
+                println!("{} can be deleted now.", args[3]);
+            },
+            Err(e) => output::error(e),
         }
-        time
+        return;
     }
 
-    fn pomodoro_time_remaining(&self) -> Option<Duration> {
-        match self.pomodoros.last() {
-            Some(pomodoro) => {
-                match pomodoro.end_time {
-                    Some(_end_time) => None,
-                    None => Some(Duration::minutes(POMODORO_DURATION) - (Utc::now() - pomodoro.start_time)),
+    if args.len() > 1 && args[1] == "--init" {
+        init_project_file();
+        return;
+    }
+
+    let mut config = match config::load_config(&data_dir()) {
+        Ok(config) => config,
+        Err(e) => {
+            output::error(e.to_string());
+            return;
+        }
+    };
+    crypto::init(&config.encryption);
+    i18n::init(config.language.as_deref(), &data_dir());
+    color::init(&config.theme);
+    relative::init(options.absolute || config.list.absolute_times);
+    table::init(config.list.format.clone());
+    config.sound.silent |= options.silent;
+    config.notification.desktop &= !options.no_notify;
+    if let Some(volume) = options.volume {
+        config.sound.volume = volume;
+    }
+    if args.len() > 1 && args[1] == sound::PLAY_COMMAND {
+        sound::play_from_args(&config.sound, &args[2..]);
+        return;
+    }
+    let project_file = match options.global {
+        true => None,
+        false => env::current_dir().ok().and_then(|dir| storage::find_project_file(&dir)),
+    };
+    if project_file.is_some() && args.len() > 1 && matches!(args[1].as_str(), "--backup" | "--restore" | "--sync") {
+        output::fail(EXIT_USAGE, format!("{} is not available for project task files. Use --global to use the global task list.", args[1]));
+        return;
+    }
+    let task_file = match &project_file {
+        Some(path) => path.clone(),
+        None => storage::task_path(&storage::list_dir(&data_dir(), &options.list_name), config.storage),
+    };
+    // Commands that are run very often or from git hooks read the tasks without waiting for the lock
+    let peek = || {
+        let mode = if project_file.is_some() { config::StorageMode::Json } else { config.storage };
+        storage::peek(&task_file, mode)
+    };
+    if args.len() > 1 && args[1] == "--prompt" {
+        if let Some(segment) = peek().and_then(|tasks| status::prompt(&tasks)) {
+            println!("{}", segment);
+        }
+        return;
+    }
+    if args.len() > 1 && args[1] == "--commit-trailer" {
+        let trailers = peek().map(|tasks| git::task_trailers(&tasks)).unwrap_or_default();
+        match args.get(2) {
+            None => trailers.iter().for_each(|trailer| println!("{}", trailer)),
+            Some(file) if !trailers.is_empty() => {
+                // A failing hook would stop the commit, so errors are only printed
+                if let Err(e) = git::add_trailers(Path::new(file), &trailers) {
+                    println!("Failed to add the task to the commit message: {}", e);
                 }
             },
-            None => None,
+            Some(_) => (),
         }
+        return;
     }
-
-    fn pomodoro_active(&self) -> bool {
-        match self.pomodoros.last() {
-            Some(pomodoro) => {
-                match pomodoro.end_time {
-                    Some(_end_time) => false,
-                    None => true,
+    if args.len() > 1 && args[1] == "--validate" {
+        let files = match &project_file {
+            Some(path) => vec![path.clone(), path.with_file_name(storage::PROJECT_ARCHIVE_FILE)],
+            None => {
+                let list_dir = storage::list_dir(&data_dir(), &options.list_name);
+                vec![storage::task_path(&list_dir, config.storage), list_dir.join(storage::ARCHIVE_FILE)]
+            },
+        };
+        validate_files(&files);
+        return;
+    }
+    if args.len() > 1 && args[1] == "--google-auth" {
+        match google_calendar::authorize(&config.google_calendar) {
+            Ok(refresh_token) => println!("Set google_calendar.refresh_token in the config file to {}", refresh_token),
+            Err(e) => output::error(format!("Failed to authorize pt: {}", e)),
+        }
+        return;
+    }
+    if args.len() > 1 && args[1] == "--install-service" {
+        let kind = match args.get(2).map(String::as_str) {
+            None | Some("daemon") => service::ServiceKind::Daemon,
+            Some("timer") => service::ServiceKind::Timer,
+            Some(other) => {
+                output::fail(EXIT_USAGE, format!("Invalid service type {}. Use daemon or timer.", other));
+                return;
+            }
+        };
+        let list_name = Some(options.list_name.as_str()).filter(|_| options.global && options.list_name != storage::DEFAULT_LIST);
+        match service::install(kind, &options.list_args(), list_name) {
+            Ok(paths) => {
+                for path in paths {
+                    println!("Wrote {}.", path.display());
                 }
+                println!("Service enabled.");
             },
-            None => false,
+            Err(e) => output::error(format!("Failed to install the service: {}", e)),
         }
+        return;
+    }
+    if args.len() > 1 && args[1] == "--daemon" {
+        if let Err(e) = daemon::run(&options, &project_file, &config) {
+            output::error(e);
+        }
+        return;
+    }
+    if args.len() > 1 && args[1] == "--status" {
+        let format = match (args.get(2).map(String::as_str), args.get(3).map(String::as_str)) {
+            (None, _) => Some(status::Format::Plain),
+            (Some("--format"), Some(name)) => status::Format::parse(name),
+            _ => None,
+        };
+        let format = match format {
+            Some(format) => format,
+            None => {
+                output::fail(EXIT_USAGE, "Invalid status format. Use plain, json, waybar, tmux or i3blocks.");
+                return;
+            }
+        };
+        // Status bars poll this often, so the task list is only read
+        let tasks = match control::loader(&options, &project_file, &config)() {
+            Ok(tasks) => tasks,
+            Err(e) => {
+                output::fail(exit_code(&e), e.to_string());
+                return;
+            }
+        };
+        let state = state::load_state(&data_dir());
+        match options.json {
+            true => output::set("status", status::json(&tasks, &state, pomodoros_today(&tasks))),
+            false => println!("{}", status::render(&tasks, &state, pomodoros_today(&tasks), format)),
+        }
+        return;
+    }
+    if args.len() > 1 && args[1] == "--agenda" {
+        let tasks = match control::loader(&options, &project_file, &config)() {
+            Ok(tasks) => tasks,
+            Err(e) => {
+                output::fail(exit_code(&e), e.to_string());
+                return;
+            }
+        };
+        println!("{}", agenda::render(&tasks, &state::load_state(&data_dir())));
+        return;
+    }
+    if args.len() > 1 && args[1] == "--watch" {
+        let load = control::loader(&options, &project_file, &config);
+        watch::run(&load, &task_file);
+        return;
+    }
+    if args.len() > 1 && args[1] == "--i3blocks" {
+        let load = control::loader(&options, &project_file, &config);
+        let Some(exe) = current_exe() else { return };
+        i3blocks::run(&load, &exe, &options.list_args(), config.notification.desktop);
+        return;
+    }
+    if args.len() > 1 && args[1] == "--serve" {
+        let port = match (args.get(2).map(String::as_str), args.get(3).map(|arg| arg.parse::<u16>())) {
+            (None, _) => config.server.port,
+            (Some("--port"), Some(Ok(port))) => port,
+            _ => {
+                output::fail(EXIT_USAGE, "Invalid port. Use pt --serve --port 7878.");
+                return;
+            }
+        };
+        let load = control::loader(&options, &project_file, &config);
+        // Asks for the passphrase of an encrypted task list once, it is handed on to the pt runs
+        if let Err(e) = load() {
+            output::fail(exit_code(&e), e.to_string());
+            return;
+        }
+        let Some(exe) = current_exe() else { return };
+        server::serve(&config.server, port, &load, &exe, &options.list_args());
+        return;
     }
-}
 
-fn main() {
-    let mut file = open_file();
-    let mut tasks = read_tasks(&mut file);
+    if args.len() > 1 && args[1] == "--telegram" {
+        let load = control::loader(&options, &project_file, &config);
+        // Asks for the passphrase of an encrypted task list once, it is handed on to the pt runs
+        if let Err(e) = load() {
+            output::fail(exit_code(&e), e.to_string());
+            return;
+        }
+        let Some(exe) = current_exe() else { return };
+        telegram::run(&config.telegram, &load, &exe, &options.list_args());
+        return;
+    }
+
+    let markdown_path = match project_file {
+        Some(_) => None,
+        None => markdown::markdown_path(&config.markdown, &options.list_name),
+    };
+    // Listing the tasks also applies the changes made in the Markdown file, which needs the lock.
+    // Without a command the tasks are listed.
+    let read_only = options.read_only
+        || (markdown_path.is_none() && args.get(1).is_none_or(|command| READ_ONLY_COMMANDS.contains(&command.as_str())));
+    let list_dir = match &project_file {
+        Some(path) => path.parent().unwrap_or(Path::new(".")).to_path_buf(),
+        None => storage::list_dir(&data_dir(), &options.list_name),
+    };
+    let open_storage = |read_only: bool| match &project_file {
+        Some(path) => storage::Storage::open_project(path, read_only, options.lock_timeout),
+        None => {
+            if !list_dir.exists() && !read_only {
+                output::message(format!("Task list {} created.", options.list_name));
+            }
+            storage::Storage::open(&list_dir, &config, read_only, options.lock_timeout)
+        },
+    };
+    let mut storage = match open_storage(read_only) {
+        Ok(storage) => storage,
+        Err(e) => {
+            output::fail(exit_code(&e), e.to_string());
+            return;
+        }
+    };
+    if storage.read_only() && args.len() > 1 && matches!(args[1].as_str(), "--restore" | "--sync") {
+        output::fail(EXIT_USAGE, format!("The task list is read-only, {} is not available.", args[1]));
+        return;
+    }
+    let mut loaded = storage.load(include_archive);
+    // Listing a broken task list recovers it like the commands that change it, which needs the
+    // lock. With --read-only it stays broken.
+    if matches!(loaded, Err(Error::Corrupt { .. })) && read_only && !options.read_only {
+        drop(storage);
+        storage = match open_storage(false) {
+            Ok(storage) => storage,
+            Err(e) => {
+                output::fail(exit_code(&e), e.to_string());
+                return;
+            }
+        };
+        loaded = storage.load(include_archive);
+    }
+    // A task or archive file that can't be parsed is recovered the way the user chooses
+    while let Err(Error::Recoverable(damage)) = &loaded {
+        match ask_recovery(damage) {
+            Ok(recovery) => {
+                storage.recover_with(recovery);
+                loaded = storage.load(include_archive);
+            },
+            Err(message) => {
+                output::error(message);
+                return;
+            }
+        }
+    }
+    let mut tasks = match loaded {
+        Ok(loaded) => {
+            for notice in loaded.notices {
+                output::message(notice);
+            }
+            loaded.tasks
+        },
+        Err(e) => {
+            output::fail(exit_code(&e), e.to_string());
+            return;
+        }
+    };
+    // The time of the commands, like the clock of a TaskStore
+    let clock = SystemClock;
     let mut notifications: Vec<NotificationContent> = Vec::new();
+    let loaded_tasks = tasks.clone();
+
+    if let (Some(path), false) = (&markdown_path, storage.read_only()) {
+        markdown::read_back(path, &mut tasks, storage.last_id());
+    }
 
-    let args: Vec<String> = env::args().collect();
+    // Without a command the tasks are listed, changes read from the Markdown file are still saved
     if args.len() == 1 {
-        list_tasks(&tasks, false);
-        return;
+        args.push("--list".to_string());
     }
 
     let command = &args[1];
+    let mut quiet_notify = false;
     match command.as_str() {
         "--pomodoro" | "-p" => {
+            let ids = match task_ids(&args, &tasks, "Start a pomodoro for", |task| !task.archived && !task.done && !task.pomodoro_active()) {
+                Some(ids) => ids,
+                None => return,
+            };
+            for id in ids {
+                start_pomodoro(id, &mut tasks, &clock);
+            }
+            relist(&tasks);
+        },
+        "--finish-pomodoro" | "-f" => {
+            let ids = match task_ids(&args, &tasks, "Finish the pomodoro of", |task| !task.archived && task.pomodoro_active()) {
+                Some(ids) => ids,
+                None => return,
+            };
+            for id in ids {
+                finish_pomodoro(id, &mut tasks, &clock);
+            }
+            relist(&tasks);
+        },
+        "--track" | "-t" => {
+            if args.len() < 3 {
+                output::fail(EXIT_USAGE, "No task ID specified.");
+                return;
+            }
+            if args.len() < 4 {
+                output::fail(EXIT_USAGE, "No time specified.");
+                return;
+            }
+            let id = match args[2].parse::<u32>() {
+                Ok(id) => id,
+                Err(_) => {
+                    output::fail(EXIT_USAGE, format!("Invalid task ID {}.", args[2]));
+                    return;
+                }
+            };
+            let (start_time, end_time) = match tracked_range(&args[3..].join(" ")) {
+                Ok(range) => range,
+                Err(e) => {
+                    output::fail(EXIT_USAGE, e);
+                    return;
+                }
+            };
+            track_time(id, start_time, end_time, &mut tasks);
+            relist(&tasks);
+        },
+        "--edit-times" => {
+            let id = match args.get(2).map(|arg| arg.parse::<u32>()) {
+                Some(Ok(id)) => id,
+                Some(Err(_)) => {
+                    output::fail(EXIT_USAGE, format!("Invalid task ID {}.", args[2]));
+                    return;
+                }
+                None => {
+                    output::fail(EXIT_USAGE, "No task ID specified.");
+                    return;
+                }
+            };
+            let Some(task) = tasks.iter_mut().find(|task| task.id == id) else {
+                output::fail(EXIT_NOT_FOUND, i18n::text("task-not-found", &[("id", id.into())]));
+                return;
+            };
+            match edit::edit_times(task) {
+                Ok(Some(pomodoros)) => {
+                    task.pomodoros = pomodoros;
+                    output::message(format!("Pomodoros of task {} updated.", id));
+                },
+                Ok(None) => {
+                    output::message("No changes.");
+                    return;
+                },
+                Err(e) => {
+                    output::fail(EXIT_ERROR, e);
+                    return;
+                }
+            }
+            relist(&tasks);
+        },
+        "--here" => {
+            let branch = env::current_dir().map_err(|e| e.to_string()).and_then(|dir| git::current_branch(&dir));
+            match branch {
+                Ok((repository, branch)) => {
+                    let id = branch_task(&repository, &branch, storage.last_id(), &mut tasks);
+                    start_pomodoro(id, &mut tasks, &clock);
+                },
+                Err(e) => {
+                    output::error(format!("Failed to find the git branch: {}", e));
+                    return;
+                }
+            }
+            relist(&tasks);
+        },
+        "--add" => {
             if args.len() < 3 {
-                println!("No task ID specified.");
+                output::fail(EXIT_USAGE, "No description specified.");
                 return;
             }
-            for arg in args.iter().skip(2) {
-                match arg.parse::<u32>() {
-                    Ok(id) => start_pomodoro(id, &mut tasks),
-                    Err(_) => {
-                        println!("Invalid task ID {}.", arg);
+            add_task(args[2..].join(" "), storage.last_id(), &mut tasks);
+            relist(&tasks);
+        },
+        "--add-interactive" => {
+            match wizard::new_task(next_task_id(&tasks, storage.last_id()), &args[2..].join(" ")) {
+                Ok(task) => {
+                    output::message(i18n::text("task-added", &[("id", task.id.into())]));
+                    tasks.push(task);
+                },
+                Err(e) => {
+                    output::fail(EXIT_USAGE, e);
+                    return;
+                }
+            }
+            relist(&tasks);
+        },
+        "--list" | "-l" | "--list-archived" => {
+            let format = match (args.get(2).map(String::as_str), args.get(3)) {
+                (None, _) => table::format(),
+                (Some("--format"), Some(format)) => Some(format.as_str()),
+                _ => {
+                    output::fail(EXIT_USAGE, "Invalid option. Use e.g. pt list --format \"{id} {status} {description} {time_today}\".");
+                    return;
+                }
+            };
+            list_tasks(&tasks, args[1] == "--list-archived", !options.no_pager, format);
+        },
+        "--check" | "-c" => {
+            let ids = match task_ids(&args, &tasks, "Check", |task| !task.archived && !task.done) {
+                Some(ids) => ids,
+                None => return,
+            };
+            for id in ids {
+                check_task(id, &mut tasks);
+            }
+            relist(&tasks);
+        }
+        "--uncheck" | "-u" => {
+            let ids = match task_ids(&args, &tasks, "Uncheck", |task| !task.archived && task.done) {
+                Some(ids) => ids,
+                None => return,
+            };
+            for id in ids {
+                uncheck_task(id, &mut tasks);
+            }
+            relist(&tasks);
+        }
+        "--archive" | "-a" => {
+            let ids = match task_ids(&args, &tasks, "Archive", |task| !task.archived) {
+                Some(ids) => ids,
+                None => return,
+            };
+            for id in ids {
+                archive_task(id, &mut tasks);
+            }
+            relist(&tasks);
+        }
+        "--unarchive" => {
+            let ids = match task_ids(&args, &tasks, "Unarchive", |task| task.archived) {
+                Some(ids) => ids,
+                None => return,
+            };
+            for id in ids {
+                unarchive_task(id, &mut tasks);
+            }
+            relist(&tasks);
+        }
+        "--archive-checked" => {
+            archive_all_checked(&mut tasks);
+            relist(&tasks);
+        }
+        "--backup" => {
+            if args.len() < 3 {
+                output::fail(EXIT_USAGE, "No backup path specified.");
+                return;
+            }
+            match backup::create_backup(&list_dir, &tasks)
+                .and_then(|b| backup::write_backup(&b, Path::new(&args[2]))) {
+                Ok(_) => println!("Backup written to {}.", args[2]),
+                Err(e) => output::error(format!("Failed to write backup to {}: {}", args[2], e)),
+            }
+        }
+        "--restore" => {
+            if args.len() < 3 {
+                output::fail(EXIT_USAGE, "No backup path specified.");
+                return;
+            }
+            match backup::read_backup(Path::new(&args[2])) {
+                Ok(b) => {
+                    if let Err(e) = backup::restore_files(&b, &list_dir) {
+                        output::error(format!("Failed to restore {}: {}", list_dir.display(), e));
                         return;
                     }
+                    tasks = match b.tasks() {
+                        Ok(backup_tasks) => backup_tasks,
+                        Err(e) => {
+                            output::fail(EXIT_USAGE, format!("Invalid backup file {}: {}", args[2], e));
+                            return;
+                        }
+                    };
+                    println!("Backup from {} restored.", b.created.format("%Y-%m-%d %H:%M"));
+                    relist(&tasks);
+                },
+                Err(e) => {
+                    output::fail(EXIT_USAGE, format!("Invalid backup file {}: {}", args[2], e));
+                    return;
+                }
+            }
+        }
+        "--import" => {
+            if args.len() < 3 {
+                output::fail(EXIT_USAGE, "No import format specified.");
+                return;
+            }
+            if !["todotxt", "todoist", "org", "github", "gitlab"].contains(&args[2].as_str()) {
+                output::fail(EXIT_USAGE, format!("Unknown import format {}. Supported formats: todotxt, todoist, org, github, gitlab", args[2]));
+                return;
+            }
+            if args.len() < 4 {
+                match args[2].as_str() {
+                    "github" | "gitlab" => output::fail(EXIT_USAGE, "No repository specified."),
+                    _ => output::fail(EXIT_USAGE, "No file specified."),
                 }
+                return;
+            }
+            if args[2] == "github" || args[2] == "gitlab" {
+                let result = match args[2].as_str() {
+                    "github" => github::import(&config.github, &args[3], storage.last_id(), &mut tasks),
+                    _ => gitlab::import(&config.gitlab, &args[3], storage.last_id(), &mut tasks),
+                };
+                match result {
+                    Ok(report) => println!("Imported {} issues from {}, updated {} tasks.", report.added, args[3], report.updated),
+                    Err(e) => {
+                        output::error(format!("Failed to import issues from {}: {}", args[3], e));
+                        return;
+                    }
+                }
+            } else {
+                let content = match fs::read_to_string(&args[3]) {
+                    Ok(content) => content,
+                    Err(e) => {
+                        output::error(format!("Failed to read {}: {}", args[3], e));
+                        return;
+                    }
+                };
+                let result = match args[2].as_str() {
+                    "todotxt" => Ok(todotxt::import(&content, storage.last_id(), &mut tasks)),
+                    "todoist" => todoist::import(Path::new(&args[3]), &content, storage.last_id(), &mut tasks),
+                    _ => Ok(org::import(&content, storage.last_id(), &mut tasks)),
+                };
+                match result {
+                    Ok(count) => println!("Imported {} tasks from {}.", count, args[3]),
+                    Err(e) => {
+                        output::error(format!("Failed to import {}: {}", args[3], e));
+                        return;
+                    }
+                }
+            }
+            relist(&tasks);
+        }
+        "--open" => {
+            let id = match args.get(2).map(|arg| arg.parse::<u32>()) {
+                Some(Ok(id)) => Some(id),
+                None => select::task(&tasks, "Open", |task| !task.archived && task.url.is_some()),
+                Some(Err(_)) => None,
             };
-            list_tasks(&tasks, false);
-        },
-        "--finish-pomodoro" | "-f" => {
+            let id = match id {
+                Some(id) => id,
+                None => {
+                    output::fail(EXIT_USAGE, "No valid task ID specified.");
+                    return;
+                }
+            };
+            match tasks.iter().find(|task| task.id == id).map(|task| task.url.as_deref()) {
+                Some(Some(url)) => {
+                    if let Err(e) = open_url(url) {
+                        output::error(format!("Failed to open {}: {}", url, e));
+                    }
+                },
+                Some(None) => output::fail(EXIT_CONFLICT, format!("Task {} has no link.", id)),
+                None => output::fail(EXIT_NOT_FOUND, i18n::text("task-not-found", &[("id", id.into())])),
+            }
+        }
+        "--taskwarrior-hook" => {
+            // Taskwarrior passes the added task, or the original and the modified task, one per
+            // line. It expects the task back first, further lines are shown as messages.
+            let input = std::io::read_to_string(std::io::stdin()).unwrap_or_default();
+            let line = match input.lines().rev().find(|line| !line.trim().is_empty()) {
+                Some(line) => line,
+                None => return,
+            };
+            println!("{}", line);
+            if let Err(e) = taskwarrior::apply(line, storage.last_id(), &mut tasks, &clock) {
+                println!("pt could not mirror the task: {}", e);
+            }
+        }
+        "--export" => {
             if args.len() < 3 {
-                println!("No task ID specified.");
+                output::fail(EXIT_USAGE, "No export format specified.");
+                return;
+            }
+            if args.len() < 4 {
+                output::fail(EXIT_USAGE, "No file specified.");
+                return;
+            }
+            let content = match args[2].as_str() {
+                "todotxt" => todotxt::export(&tasks),
+                "org" => org::export(&tasks),
+                _ => {
+                    output::fail(EXIT_USAGE, format!("Unknown export format {}. Supported formats: todotxt, org", args[2]));
+                    return;
+                }
+            };
+            match fs::write(&args[3], content) {
+                Ok(_) => println!("Exported tasks to {}.", args[3]),
+                Err(e) => output::error(format!("Failed to write {}: {}", args[3], e)),
+            }
+        }
+        "--sync" => {
+            if args.get(2).map(String::as_str) == Some("jira") {
+                match jira::push(&config.jira, &list_dir, &tasks) {
+                    Ok(0) => println!("No new pomodoros to log in Jira."),
+                    Ok(logged) => println!("Logged {} pomodoros in Jira.", logged),
+                    Err(e) => output::error(format!("Jira sync failed: {}", e)),
+                }
+                return;
+            }
+            if args.get(2).map(String::as_str) == Some("clockify") {
+                match clockify::push(&config.clockify, &list_dir, &tasks) {
+                    Ok(0) => println!("No new pomodoros to add to Clockify."),
+                    Ok(count) => println!("Added {} pomodoros to Clockify.", count),
+                    Err(e) => output::error(format!("Clockify sync failed: {}", e)),
+                }
                 return;
             }
-            for arg in args.iter().skip(2) {
-                match arg.parse::<u32>() {
-                    Ok(id) => finish_pomodoro(id, &mut tasks),
-                    Err(_) => {
-                        println!("Invalid task ID {}.", arg);
-                        return;
-                    }
+            if args.get(2).map(String::as_str) == Some("activitywatch") {
+                match activitywatch::push(&config.activitywatch, &list_dir, &options.list_name, &tasks) {
+                    Ok(0) => println!("No new pomodoros to add to ActivityWatch."),
+                    Ok(count) => println!("Added {} pomodoros to ActivityWatch.", count),
+                    Err(e) => output::error(format!("ActivityWatch sync failed: {}", e)),
                 }
-            };
-            list_tasks(&tasks, false);
-        },
-        "--track" | "-t" => {
-            if args.len() < 3 {
-                println!("No task ID specified.");
                 return;
             }
-            if args.len() < 4 {
-                println!("No time specified.");
+            if args.get(2).map(String::as_str) == Some("gitlab") {
+                match gitlab::push_spent_time(&config.gitlab, &list_dir, &tasks) {
+                    Ok(0) => println!("No new pomodoros to add to GitLab."),
+                    Ok(spent) => println!("Added the time of {} pomodoros to GitLab issues.", spent),
+                    Err(e) => output::error(format!("GitLab sync failed: {}", e)),
+                }
                 return;
             }
-            match args[2].parse::<u32>() {
-                Ok(id) => {
-                    match args[3].parse::<i64>() {
-                        Ok(time) => track_time(id, time, &mut tasks),
-                        Err(_) => {
-                            println!("Invalid time {}.", args[3]);
-                            return;
-                        }
+            if args.get(2).map(String::as_str) == Some("caldav") {
+                // Tasks are saved even if the sync fails, as the sync state already includes the changes
+                match caldav::sync(&config.caldav, &options.list_name, &list_dir, &mut tasks, storage.last_id()) {
+                    Ok(report) => {
+                        println!("{} tasks added and {} changed in pt, {} added and {} changed on the CalDAV server.",
+                            report.added_local, report.changed_local, report.added_remote, report.changed_remote);
+                        relist(&tasks);
+                    },
+                    Err(e) => output::error(format!("CalDAV sync failed: {}", e)),
+                }
+            } else {
+                let mode = match args.get(2).map(String::as_str) {
+                    None => sync::SyncMode::Auto,
+                    Some("push") => sync::SyncMode::Push,
+                    Some("pull") => sync::SyncMode::Pull,
+                    Some(other) => {
+                        output::fail(EXIT_USAGE, format!("Invalid sync mode {}. Use push, pull, jira, gitlab, clockify, caldav or activitywatch.", other));
+                        return;
                     }
-                },
-                Err(_) => {
-                    println!("Invalid task ID {}.", args[2]);
+                };
+                if let Err(e) = sync::sync(&config.sync, &options.list_name, &list_dir, &mut tasks, mode) {
+                    output::error(format!("Sync failed: {}", e));
                     return;
                 }
-            };
-            list_tasks(&tasks, false);
-        },
-        "--list" | "-l" => list_tasks(&tasks, false),
-        "--list-archived" => list_tasks(&tasks, true),
-        "--check" | "-c" => {
-            if args.len() < 3 {
-                println!("No task ID specified.");
-                return;
+                relist(&tasks);
             }
-            for arg in args.iter().skip(2) {
-                match arg.parse::<u32>() {
-                    Ok(id) => check_task(id, &mut tasks),
-                    Err(_) => {
-                        println!("Invalid task ID {}.", arg);
+        }
+        "--due" => {
+            let id = match args.get(2).map(|arg| arg.parse::<u32>()) {
+                Some(Ok(id)) => id,
+                _ => {
+                    output::fail(EXIT_USAGE, "No valid task ID specified.");
+                    return;
+                }
+            };
+            let due = match args.len() {
+                3 => None,
+                _ => match when::parse_date(&args[3..].join(" "), chrono::Local::now().date_naive()) {
+                    Ok(date) => Some(date),
+                    Err(e) => {
+                        output::fail(EXIT_USAGE, e);
                         return;
                     }
+                },
+            };
+            set_due_date(id, due, &mut tasks);
+            relist(&tasks);
+        }
+        "--jira" => {
+            let id = match args.get(2).map(|arg| arg.parse::<u32>()) {
+                Some(Ok(id)) => id,
+                _ => {
+                    output::fail(EXIT_USAGE, "No valid task ID specified.");
+                    return;
                 }
             };
-            list_tasks(&tasks, false);
+            link_jira_issue(id, args.get(3).cloned(), &mut tasks);
         }
-        "--uncheck" | "-u" => {
-            if args.len() < 3 {
-                println!("No task ID specified.");
-                return;
-            }
-            for arg in args.iter().skip(2) {
-                match arg.parse::<u32>() {
-                    Ok(id) => uncheck_task(id, &mut tasks),
-                    Err(_) => {
-                        println!("Invalid task ID {}.", arg);
-                        return;
+        "--email" => {
+            match args.get(2).map(String::as_str) {
+                Some("summary") => {
+                    let today = chrono::Local::now().date_naive();
+                    match email::send(&config.email, &format!("Pomodoro summary for {}", today), report::summary(&tasks, today)) {
+                        Ok(_) => println!("Summary sent."),
+                        Err(e) => output::error(format!("Failed to send the summary: {}", e)),
                     }
                 }
+                Some("check") => check_unfinished_pomodoros(&tasks, &config.email),
+                _ => output::fail(EXIT_USAGE, "Specify what to send: summary or check."),
+            }
+        }
+        "--report" => {
+            let today = chrono::Local::now().date_naive();
+            let date = match args.len() {
+                2 => Ok(today),
+                _ => when::parse_date(&args[2..].join(" "), today),
+            };
+            let date = match date {
+                Ok(date) => date,
+                Err(e) => {
+                    output::fail(EXIT_USAGE, e);
+                    return;
+                }
             };
-            list_tasks(&tasks, false);
+            match output::json() {
+                true => output::set("report", report::summary_json(&tasks, date)),
+                false => print!("{}", report::summary(&tasks, date)),
+            }
         }
-        "--archive" | "-a" => {
-            if args.len() < 3 {
-                println!("No task ID specified.");
-                return;
+        "--compact" => {
+            let report = compact::compact(&mut tasks);
+            if report.total() == 0 {
+                println!("Nothing to clean up.");
+            } else {
+                println!("Removed {} zero-length pomodoros.", report.zero_length);
+                println!("Merged {} tiny pomodoros into adjacent ones.", report.merged);
+                println!("Removed {} duplicate pomodoros.", report.duplicate_pomodoros);
+                println!("Removed {} duplicate tasks.", report.duplicate_tasks);
             }
-            for arg in args.iter().skip(2) {
-                match arg.parse::<u32>() {
-                    Ok(id) => archive_task(id, &mut tasks),
-                    Err(_) => {
-                        println!("Invalid task ID {}.", arg);
-                        return;
-                    }
+        }
+        "--git" => {
+            // The task file stays locked while git runs, but is not written afterwards,
+            // so that changes pulled by git are not overwritten.
+            match git::passthrough(&data_dir(), &args[2..]) {
+                Ok(status) if !status.success() => output::error(format!("git failed with {}.", status)),
+                Ok(_) => (),
+                Err(e) => output::error(e),
+            }
+            return;
+        }
+        "--break" | "-b" => {
+            let duration = match args.len() {
+                2 => Ok(Duration::minutes(BREAK_DURATION)),
+                _ => when::parse_duration(&args[2..].join(" ")),
+            };
+            let duration = match duration {
+                Ok(duration) if duration > Duration::zero() => duration,
+                Ok(_) => {
+                    output::fail(EXIT_USAGE, "The break must be longer than 0 minutes.");
+                    return;
+                }
+                Err(e) => {
+                    output::fail(EXIT_USAGE, e);
+                    return;
                 }
             };
-            list_tasks(&tasks, false);
-        }
-        "--unarchive" => {
-            if args.len() < 3 {
-                println!("No task ID specified.");
+            let Some(break_end) = Utc::now().checked_add_signed(duration) else {
+                output::fail(EXIT_USAGE, format!("A break of {} is too long.", args[2..].join(" ")));
                 return;
-            }
-            for arg in args.iter().skip(2) {
-                match arg.parse::<u32>() {
-                    Ok(id) => unarchive_task(id, &mut tasks),
-                    Err(_) => {
-                        println!("Invalid task ID {}.", arg);
-                        return;
-                    }
+            };
+            let _lock = state::lock(&data_dir());
+            let mut state = state::load_state(&data_dir());
+            state.break_end = Some(break_end);
+            state.nag = None;
+            state::save_state(&data_dir(), &state);
+            println!("{}", i18n::text("break-started", &[("minutes", duration.num_minutes().max(1).into())]));
+        }
+        "--snooze" => {
+            // A duration like 10m, or the time to deliver the alert again like tomorrow 9am
+            let text = args[2..].join(" ");
+            let duration = match args.len() {
+                2 => Ok(Duration::minutes(SNOOZE_DURATION)),
+                _ => when::parse_duration(&text).or_else(|_| {
+                    when::parse_time(&text, chrono::Local::now()).map(|time| time.with_timezone(&Utc) - Utc::now())
+                }),
+            };
+            let duration = match duration {
+                Ok(duration) if duration > Duration::zero() => duration,
+                Ok(_) => {
+                    output::fail(EXIT_USAGE, format!("{} is not in the future.", text));
+                    return;
                 }
+                Err(_) => {
+                    output::fail(EXIT_USAGE, format!("Invalid snooze duration {}. Use e.g. 30s, 5m, 1h, 14:30 or tomorrow 9am.", text));
+                    return;
+                }
+            };
+            let Some(until) = Utc::now().checked_add_signed(duration) else {
+                output::fail(EXIT_USAGE, format!("Invalid snooze duration {}. Use e.g. 30s, 5m, 1h, 14:30 or tomorrow 9am.", text));
+                return;
             };
-            list_tasks(&tasks, false);
+            let _lock = state::lock(&data_dir());
+            let mut state = state::load_state(&data_dir());
+            match state.last_alert.take() {
+                Some(alert) => {
+                    let time = until.with_timezone(&chrono::Local).format("%H:%M:%S").to_string();
+                    println!("{}", i18n::text("alert-snoozed", &[("id", alert.task_id.into()), ("time", time.into())]));
+                    state.snoozed.push(state::Snooze { alert, until });
+                    state.nag = None;
+                    state::save_state(&data_dir(), &state);
+                },
+                None => println!("{}", i18n::text("alert-nothing-to-snooze", &[])),
+            }
         }
-        "--archive-checked" => {
-            archive_all_checked(&mut tasks);
-            list_tasks(&tasks, false);
+        "--ack" => {
+            match acknowledge_alert() {
+                Some(alert) => println!("{}", i18n::text("alert-acknowledged", &[("id", alert.task_id.into())])),
+                None => println!("{}", i18n::text("alert-nothing-to-acknowledge", &[])),
+            }
         }
         "--notify" => {
-            compute_notifications(&mut tasks, &mut notifications);
+            quiet_notify = match args.get(2).map(String::as_str) {
+                None => false,
+                Some("--quiet") => true,
+                Some(other) => {
+                    output::fail(EXIT_USAGE, format!("Invalid option {}. Use pt --notify --quiet.", other));
+                    return;
+                }
+            };
+            compute_notifications(&mut tasks, &mut notifications, &config.notification, &options.list_name, &clock);
+            drop_repeated_notifications(&mut notifications, config.notification.repeat_seconds);
+            if config.notification.progress && config.notification.desktop {
+                progress::update(&tasks);
+            }
         }
         "--test-notification" => {
             notifications.push(NotificationContent {
-                title: "This is a test notification".to_string(),
-                body: "Here is some information about this test notification".to_string(),
+                title: i18n::text("notification-test-title", &[]),
+                body: i18n::text("notification-test-body", &[]),
+                kind: NotificationKind::PomodoroFinished,
+                task_id: None,
             });
         }
         "--help" | "-h" => {
-            println!("Usage: task [command] [arguments]");
+            println!("Usage: task [options] [command] [arguments]");
+            println!("Options:");
+            println!("  -n, --list-name [name]      Use the task list with the specified name instead of the default list");
+            println!("  -g, --global                Use the global task list even inside a directory with a project task file");
+            println!("  --wait [seconds]            Wait this long for a task list locked by another process (default: 10)");
+            println!("  --silent, --no-sound        Don't play any sounds");
+            println!("  --no-notify                 Print notifications instead of showing them on the desktop, e.g. over SSH");
+            println!("  --no-pager                  Print long task lists directly instead of through $PAGER or less");
+            println!("  --absolute                  Show dates and times in the task list instead of e.g. 12m ago or in 3d");
+            println!("  --volume [percent]          Play sounds at the specified volume, e.g. 50");
+            println!("  --read-only                 Don't lock or write the task list, e.g. for listing tasks from scripts");
+            println!("  --dry-run                   Print what a command would change in the task list without saving it");
+            println!("  -q, --quiet                 Don't show the task list again after changing it");
+            println!("  -v, --verbose               Show details like the times of started and finished pomodoros and the saved changes");
+            println!("  --json                      Print the result as JSON, for listing and changing tasks, report and --status");
+            println!("Subcommands (pt help [subcommand] for their options):");
+            println!("  add [-i] [description]      Add a new task. -i asks for its projects, tags, estimate, due date and priority");
+            println!("  start [task ID]             Start a pomodoro for the specified task");
+            println!("  finish [task ID]            Finish the pomodoro for the specified task");
+            println!("  done [task ID]              Check the specified task");
+            println!("  due [task ID] [date]        Set the due date of the task, e.g. tomorrow or friday");
+            println!("  list [--archived]           List all tasks, or all archived tasks");
+            println!("  list --format [template]    List the tasks in a format like \"{{id}} {{status}} {{description}} {{time_today}}\"");
+            println!("  report [--date date]        Print the pomodoros of today or the specified day per task, e.g. yesterday");
+            println!("  doctor                      Check the task files, config, notifications and sound and suggest fixes");
             println!("Commands:");
+            println!("  Without a task ID, the commands that take one let you pick the task by typing part of its description");
             println!("  [no command]                List all tasks");
             println!("  [no command] [description]  Add a new task with the specified description");
+            println!("  --add [description]         Add a new task, also if the description starts with a subcommand or a plugin name");
+            println!("  --report [date]             Print the pomodoros of today or the specified day per task");
             println!("  -p, --pomodoro [task ID]    Start a pomodoro for the specified task");
             println!("  -f, --finish-pomodoro [task ID] Finish the pomodoro for the specified task");
-            println!("  -t, --track [task ID] [time] Track the specified time for the specified task");
+            println!("  --here                      Start a pomodoro for the task of the current git branch, adding it if needed");
+            println!("  -t, --track [task ID] [time] Track time for the task: minutes, a duration like 1h30m, a start like 9am or a range like 9am to 10:30");
+            println!("  --edit-times [task ID]      Edit the start and end times of the pomodoros of the task in $EDITOR");
+            println!("  -b, --break [duration]      Start a break, 5 minutes by default. --notify announces its end");
+            println!("  --snooze [duration|time]    Deliver the last finished pomodoro alert again later, e.g. 30s, 5m, 1h or tomorrow 9am (default: 5m)");
+            println!("  --ack                       Stop repeating the alert of a finished pomodoro (notification.nag_minutes)");
             println!("  -l, --list                  List all tasks");
             println!("  --list-archived             List all archived tasks");
+            println!("  --lists                     List all task lists");
+            println!("  --plugins                   List the plugins, executables named pt-<name> that run as pt <name>");
+            println!("  --init                      Create a project task file .pt.json in the current directory");
             println!("  -c, --check [task ID]       Check the specified task");
             println!("  -u, --uncheck [task ID]     Uncheck the specified task");
             println!("  -a, --archive [task ID]     Archive the specified task");
             println!("  --unarchive [task ID]       Unarchive the specified task");
             println!("  --archive-checked           Archive all checked tasks");
-            println!("  --notify                    Display notifications for tasks that are due");
+            println!("  --backup [path]             Write a backup of all data to the specified file");
+            println!("  --restore [path]            Restore all data from the specified backup file");
+            println!("  --import [format] [path]    Import tasks from the specified file. Formats: todotxt, todoist (CSV), org");
+            println!("  --import github [owner/repo] Import the issues assigned to you, updating tasks imported before");
+            println!("  --import gitlab [group/project] Import the GitLab issues assigned to you, updating tasks imported before");
+            println!("  --open [task ID]            Open the web page of the task, e.g. its GitHub or GitLab issue");
+            println!("  --export [format] [path]    Export active tasks to the specified file. Formats: todotxt, org");
+            println!("  --taskwarrior-hook          Mirror a task added or modified in Taskwarrior, run from its on-add and on-modify hooks");
+            println!("  --merge-files [a] [b]       Merge the tasks of task file b, e.g. a sync conflict copy, into task file a");
+            println!("  --validate                  Check the task files for errors without changing them");
+            println!("  --email [summary|check]     Email today's summary, or an alert about pomodoros left running (for cron)");
+            println!("  --compact                   Remove empty, tiny and duplicate pomodoros and duplicate tasks");
+            println!("  --git [arguments]           Run git in the data directory, e.g. pt --git pull");
+            println!("  --sync [push|pull]          Synchronize the tasks with the configured server");
+            println!("  --sync jira                 Log the finished pomodoros of linked tasks as Jira worklogs");
+            println!("  --sync gitlab               Add the time of finished pomodoros to their GitLab issues with /spend");
+            println!("  --sync clockify             Add each finished pomodoro as a Clockify time entry");
+            println!("  --sync caldav               Synchronize the tasks with a CalDAV task list in both directions");
+            println!("  --sync activitywatch        Add the finished pomodoros as events to ActivityWatch");
+            println!("  --due [task ID] [date]      Set the due date of the task, e.g. tomorrow, friday or 2024-03-01. Removes it without a date");
+            println!("  --jira [task ID] [issue]    Link the task to a Jira issue, e.g. PROJ-123. Unlinks it without an issue");
+            println!("  --notify [--quiet]          Display notifications for tasks that are due. --quiet prints nothing and exits with 2 if nothing was due");
+            println!("  --daemon                    Keep running and display notifications exactly when they are due");
+            println!("  --agenda                    Show the pomodoros, breaks and due tasks of today and tomorrow in order");
+            println!("  --watch                     Keep showing the task list with live countdowns, e.g. in a side pane");
+            println!("  --status [--format format]  Print the running pomodoro for status bars. Formats: plain, json, waybar, tmux, i3blocks");
+            println!("  --prompt                    Print a short countdown like 🍅12m for shell prompts while a pomodoro runs");
+            println!("  --commit-trailer [file]     Add the task of the running pomodoro to a commit message, for prepare-commit-msg hooks");
+            println!("  --i3blocks                  Run as a persistent i3blocks block, click to start or finish a pomodoro");
+            println!("  --serve [--port port]       Serve the tasks over an HTTP JSON API");
+            println!("  --telegram                  Answer commands sent to your Telegram bot, e.g. /list or /pomodoro 3");
+            println!("  --google-auth               Allow pt to add focus blocks to your Google Calendar");
+            println!("  --install-service [daemon|timer] Install and enable a systemd user service for --daemon, or a timer for --notify");
             println!("  --test-notification         Display a test notification");
             println!("  -h, --help                  Display this help message");
             
@@ -259,257 +1278,736 @@ fn main() {
         _ => {
             // Assume the user is adding a new task
             let description = args[1..].join(" ");
-            add_task(description, &mut tasks);
-            list_tasks(&tasks, false);
+            add_task(description, storage.last_id(), &mut tasks);
+            relist(&tasks);
         }
     }
 
-    write_tasks(&tasks, &mut file);
-    drop(file);
-
-    display_notifications(notifications);
-}
-
-fn start_pomodoro(task_id: u32, tasks: &mut Vec<Task>) {
-    // Update task time spent
-    match tasks.iter_mut().find(|task| task.id == task_id) {
-        Some(t) => {
-            if t.pomodoro_active() {
-                println!("Pomodoro already active for task {}.", task_id);
-                return;
+    if options.dry_run {
+        let changes = storage.preview(&tasks);
+        match changes.is_empty() {
+            true => output::message("Dry run, nothing would change."),
+            false => output::message("Dry run, these changes would be saved:"),
+        }
+        for change in changes {
+            output::message(format!("  {}", change));
+        }
+        return;
+    }
+    let details = if output::verbose() { storage.preview(&tasks) } else { Vec::new() };
+    let changes = match storage.save(&mut tasks) {
+        Ok(saved) => {
+            for notice in saved.notices {
+                output::message(notice);
             }
-
-            t.pomodoros.push(Pomodoro {
-                start_time: Utc::now(),
-                end_time: None,
-            });
-            println!("Pomodoro started for task {}.", task_id);
+            saved.changes
         },
-        None => {
-            println!("Task {} not found.", task_id);
+        Err(e) => {
+            output::fail(exit_code(&e), e.to_string());
             return;
         }
     };
-}
+    if !changes.is_empty() {
+        for detail in details {
+            output::detail(format!("Saved at {}: {}", output::timestamp(Utc::now()), detail));
+        }
+    }
+    // Project task files are usually committed along with the project
+    if config.git.enabled && project_file.is_none() && !changes.is_empty() {
+        if let Err(e) = git::commit(&data_dir(), &changes, config.git.push) {
+            println!("Failed to commit changes to git: {}", e);
+        }
+    }
+    let read_only = storage.read_only();
+    drop(storage);
 
-fn finish_pomodoro(task_id: u32, tasks: &mut Vec<Task>) {
-    // Update task time spent
-    match tasks.iter_mut().find(|task| task.id == task_id) {
-        Some(t) => {
-            match t.pomodoros.last_mut() {
-                Some(p) => {
-                    match p.end_time {
-                        Some(_) => {
-                            println!("No pomodoro active for task {}.", task_id);
-                        },
-                        None => {
-                            p.end_time = Some(Utc::now());
-                            println!("Pomodoro finished for task {}.", task_id);
-                        },
-                    }
-                },
-                None => {
-                    println!("No pomodoros found for task {}.", task_id);
+    if !read_only {
+        let events = event::detect(&loaded_tasks, &tasks);
+        webhook::send(&config.webhooks, &events, &options.list_name);
+        hooks::run(&data_dir(), &events, &options.list_name);
+        if let Some(exe) = current_exe() {
+            plugins::notify(&config.plugins, &events, &exe, &data_dir(), &options.list_name);
+        }
+        google_calendar::update(&config.google_calendar, &data_dir(), &events, &options.list_name);
+        if project_file.is_none() {
+            activitywatch::update(&config.activitywatch, &list_dir, &options.list_name, &events, &tasks);
+        }
+        dnd::update(&config.dnd, &events, &tasks);
+        let break_started = command == "--break" || command == "-b";
+        media::update(&config.media, &data_dir(), &events, &tasks, break_started);
+        blocklist::update(&config.blocklist, &events, &tasks, break_started);
+        mqtt::update(&config.mqtt, &events, &options.list_name, &tasks, break_started);
+        // Starting the next pomodoro shows that the alert was noticed
+        if events.iter().any(|event| matches!(event, event::Event::PomodoroStarted { .. })) {
+            acknowledge_alert();
+        }
+        if config.notification.on_start {
+            for event in &events {
+                if let event::Event::PomodoroStarted { task } = event {
+                    notifications.push(start_notification(task, &config.notification));
                 }
             }
-        },
-        None => {
-            println!("Task {} not found.", task_id);
         }
+    }
+
+    if !read_only && project_file.is_none() {
+        if let Err(e) = backup::auto_backup(&list_dir, &tasks) {
+            println!("Failed to write automatic backup: {}", e);
+        }
+    }
+    if let (Some(path), false) = (&markdown_path, read_only) {
+        if let Err(e) = markdown::write(path, &tasks) {
+            println!("Failed to write {}: {}", path.display(), e);
+        }
+    }
+
+    let notified = !notifications.is_empty();
+    notification::display_notifications(notifications, &config, &options.list_args(), quiet_notify || options.json);
+    if quiet_notify && !notified {
+        std::process::exit(EXIT_NOTHING_DUE);
+    }
+}
+
+// Changes the tasks through a TaskStore like other frontends of pt_core, with the time of the
+// command as its clock
+fn in_store<T>(tasks: &mut Vec<Task>, now: DateTime<Utc>, change: impl FnOnce(&mut TaskStore) -> Result<T, Error>) -> Result<T, Error> {
+    let mut store = TaskStore::in_memory(std::mem::take(tasks)).with_clock(now);
+    let result = change(&mut store);
+    *tasks = store.into_tasks();
+    result
+}
+
+fn start_pomodoro(task_id: u32, tasks: &mut Vec<Task>, clock: &dyn Clock) {
+    let now = clock.now();
+    match in_store(tasks, now, |store| store.start_pomodoro(task_id)) {
+        Ok(()) => {
+            output::message(i18n::text("pomodoro-started", &[("id", task_id.into())]));
+            output::detail(format!("Started at {}, ends at {}.", output::timestamp(now), output::timestamp(now + Duration::minutes(POMODORO_DURATION))));
+        },
+        Err(Error::NotFound(_)) => output::fail(EXIT_NOT_FOUND, i18n::text("task-not-found", &[("id", task_id.into())])),
+        Err(_) => output::fail(EXIT_CONFLICT, i18n::text("pomodoro-already-active", &[("id", task_id.into())])),
+    }
+}
+
+fn finish_pomodoro(task_id: u32, tasks: &mut Vec<Task>, clock: &dyn Clock) {
+    let now = clock.now();
+    let result = in_store(tasks, now, |store| store.finish_pomodoro(task_id));
+    let task = tasks.iter().find(|task| task.id == task_id);
+    match (result, task.and_then(|task| task.pomodoros.last())) {
+        (Ok(()), Some(pomodoro)) => {
+            let end_time = pomodoro.end_time.unwrap_or(now);
+            // The store ends the pomodoro at its start if the system clock went back
+            if pomodoro.clock_went_back {
+                output::message(i18n::text("pomodoro-clock-went-back", &[("id", task_id.into())]));
+            }
+            output::message(i18n::text("pomodoro-finished", &[("id", task_id.into())]));
+            output::detail(format!("Ran from {} to {}, {} min.", output::timestamp(pomodoro.start_time), output::timestamp(end_time), (end_time - pomodoro.start_time).num_minutes()));
+        },
+        (Err(Error::NotFound(_)), _) => output::fail(EXIT_NOT_FOUND, i18n::text("task-not-found", &[("id", task_id.into())])),
+        (_, None) => output::fail(EXIT_CONFLICT, i18n::text("pomodoro-none", &[("id", task_id.into())])),
+        (Err(_), Some(_)) => output::fail(EXIT_CONFLICT, i18n::text("pomodoro-not-active", &[("id", task_id.into())])),
+    }
+}
+
+// Time for --track: minutes or a duration up to now, a start time up to now, or a range like
+// 9am to 10:30, where the end is on the day of the start
+fn tracked_range(text: &str) -> Result<(DateTime<Utc>, DateTime<Utc>), String> {
+    let now = chrono::Local::now();
+    let (start_time, end_time) = match (when::parse_duration(text), text.split_once(" to ")) {
+        (Ok(duration), _) => match now.checked_sub_signed(duration) {
+            Some(start_time) => (start_time, now),
+            None => return Err(format!("{} is too long to track.", text)),
+        },
+        (Err(_), Some((start, end))) => {
+            let start_time = when::parse_time(start, now)?;
+            (start_time, when::parse_time(end, start_time)?)
+        },
+        (Err(_), None) => match when::parse_time(text, now) {
+            Ok(start_time) => (start_time, now),
+            Err(_) => return Err(format!("Invalid time {}. Use minutes like 25 or 1h30m, a start time like 9am or a range like 9am to 10:30.", text)),
+        },
     };
+    if start_time >= end_time {
+        return Err(format!("The tracked time must end after it starts, {} is not after {}.", end_time.format("%Y-%m-%d %H:%M"), start_time.format("%Y-%m-%d %H:%M")));
+    }
+    Ok((start_time.with_timezone(&Utc), end_time.with_timezone(&Utc)))
 }
 
-fn track_time(task_id: u32, time: i64, tasks: &mut Vec<Task>) {
+fn track_time(task_id: u32, start_time: DateTime<Utc>, end_time: DateTime<Utc>, tasks: &mut [Task]) {
     // Update task time spent
     match tasks.iter_mut().find(|task| task.id == task_id) {
         Some(t) => {
-            if t.pomodoro_active() {
-                t.pomodoros.insert(t.pomodoros.len() - 1, Pomodoro {
-                    start_time: Utc::now() - chrono::Duration::minutes(time),
-                    end_time: Some(Utc::now()),
-                });
-            } else {
-                t.pomodoros.push(Pomodoro {
-                    start_time: Utc::now() - chrono::Duration::minutes(time),
-                    end_time: Some(Utc::now()),
-                });
-            }
-            println!("Tracked {} minutes for task {}.", time, task_id);
+            let pomodoro = Pomodoro::new(start_time, Some(end_time));
+            // Pomodoros stay in the order they started in, a running one stays last
+            let position = t.pomodoros.iter()
+                .position(|pomodoro| pomodoro.start_time > start_time || pomodoro.end_time.is_none())
+                .unwrap_or(t.pomodoros.len());
+            t.pomodoros.insert(position, pomodoro);
+            output::message(i18n::text("pomodoro-tracked", &[("minutes", (end_time - start_time).num_minutes().into()), ("id", task_id.into())]));
+            output::detail(format!("Tracked from {} to {}.", output::timestamp(start_time), output::timestamp(end_time)));
         },
         None => {
-            println!("Task {} not found.", task_id);
-            return;
+            output::fail(EXIT_NOT_FOUND, i18n::text("task-not-found", &[("id", task_id.into())]));
         }
     };
 }
 
-fn list_tasks(tasks: &[Task], list_archived: bool) {
+// Shows the task list after it was changed, unless -q was given
+fn relist(tasks: &[Task]) {
+    if !output::quiet() || output::json() {
+        list_tasks(tasks, false, false, table::format());
+    }
+}
+
+// Explicit listings are paged, the list shown again after a change is not. A format template
+// replaces the columns.
+fn list_tasks(tasks: &[Task], list_archived: bool, paged: bool, format: Option<&str>) {
+    if output::json() {
+        let listed: Vec<&Task> = tasks.iter().filter(|task| task.archived == list_archived).collect();
+        output::set("tasks", serde_json::json!(listed));
+        return;
+    }
     if tasks.is_empty() {
-        println!("No tasks found.");
+        println!("{}", i18n::text("no-tasks", &[]));
         return;
     }
 
-    for task in tasks {
-        if task.archived != list_archived {
-            continue;
-        }
-        let status = if task.done { "x" } else { " " };
-        let time = match task.pomodoro_time_remaining() {
-            None => format!("Σ{} min", task.time_spent().num_minutes()),
-            Some(t) => format!("{}m {:0>2}s", t.num_minutes(), t.num_seconds() % 60),
-        };
-        let task_str = format!("{:0>3} [{}]: {} ({})", task.id, status, task.description, time);
-        println!("{}", task_str);
+    let listed: Vec<&Task> = tasks.iter().filter(|task| task.archived == list_archived).collect();
+    let lines = match format {
+        Some(format) => listed.iter().map(|task| notification::render(format, &task_values(task))).collect(),
+        None => table::lines(&listed.iter().map(|task| task_columns(task)).collect::<Vec<_>>(), table::terminal_width()),
+    };
+    let mut text = String::new();
+    for (task, line) in listed.iter().zip(lines) {
+        text.push_str(&color::task(task, line));
+        text.push('\n');
+    }
+    match paged {
+        true => pager::show(&text),
+        false => print!("{}", text),
+    }
+}
+
+// Line of the task in the task list, with the remaining time of a running pomodoro or when the
+// task was last worked on
+fn task_line(task: &Task) -> String {
+    let (prefix, description, time) = task_columns(task);
+    format!("{} {} {}", prefix, description, time)
+}
+
+// ID and status, description and time of the task, which the task list aligns as columns
+fn task_columns(task: &Task) -> (String, String, String) {
+    let status = if task.done { "x" } else { " " };
+    let mut time = match task.pomodoro_time_remaining() {
+        None => format!("Σ{} min", task.time_spent().num_minutes()),
+        Some(t) => format!("{}m {:0>2}s", t.num_minutes(), t.num_seconds() % 60),
+    };
+    if let Some(estimate) = task.estimate {
+        time.push_str(&format!(", {} of {} pomodoros", task.pomodoros.len(), estimate));
+    }
+    // The absolute due date is part of the description already
+    if let Some(due) = task.due.filter(|_| !relative::absolute()) {
+        time.push_str(&format!(", {}", i18n::text("list-due", &[("when", relative::date(due).into())])));
+    }
+    match task.pomodoros.last().map(|pomodoro| (pomodoro.start_time, pomodoro.end_time)) {
+        Some((start_time, None)) => {
+            time.push_str(&format!(", {}", i18n::text("list-started", &[("when", relative::time(start_time).into())])));
+            if task.clock_went_back() {
+                time.push_str(&format!(", {}", i18n::text("list-clock-went-back", &[])));
+            }
+        },
+        Some((_, Some(end_time))) => {
+            time.push_str(&format!(", {}", i18n::text("list-last-worked", &[("when", relative::time(end_time).into())])));
+            if task.clock_went_back() {
+                time.push_str(&format!(", {}", i18n::text("list-clock-went-back", &[])));
+            }
+        },
+        None => (),
+    }
+    (format!("{:0>3} [{}]:", task.id, status), task.display_description(), format!("({})", time))
+}
+
+// Values of the placeholders of list.format and pt list --format
+fn task_values(task: &Task) -> Vec<(&'static str, String)> {
+    let today = chrono::Local::now().date_naive();
+    let time_today = task.pomodoros.iter()
+        .filter(|pomodoro| pomodoro.start_time.with_timezone(&chrono::Local).date_naive() == today)
+        .map(|pomodoro| pomodoro.duration_at(Utc::now()))
+        .fold(Duration::zero(), |total, time| total + time);
+    let remaining = task.pomodoro_time_remaining()
+        .map(|t| format!("{}m {:0>2}s", t.num_minutes(), t.num_seconds() % 60))
+        .unwrap_or_default();
+    vec![
+        ("id", task.id.to_string()),
+        ("status", if task.done { "x" } else { " " }.to_string()),
+        ("description", task.display_description()),
+        ("title", task.description.clone()),
+        ("priority", task.priority.map(String::from).unwrap_or_default()),
+        ("projects", task.projects.join(" ")),
+        ("tags", task.tags.join(" ")),
+        ("due", task.due.map(|due| due.to_string()).unwrap_or_default()),
+        ("estimate", task.estimate.map(|estimate| estimate.to_string()).unwrap_or_default()),
+        ("pomodoros", task.pomodoros.len().to_string()),
+        ("time_spent", format!("{} min", task.time_spent().num_minutes())),
+        ("time_today", format!("{} min", time_today.num_minutes())),
+        ("remaining", remaining),
+    ]
+}
+
+fn list_task_lists(current: &str) {
+    for name in storage::list_names(&data_dir()) {
+        let marker = if name == current { "*" } else { " " };
+        println!("{} {}", marker, name);
     }
 }
 
-fn check_task(task_id: u32, tasks: &mut Vec<Task>) {
+fn check_task(task_id: u32, tasks: &mut [Task]) {
     let task = tasks.iter_mut().find(|task| task.id == task_id);
     match task {
         Some(t) => {
             t.done = true;
-            println!("Task {} checked.", t.id);
+            output::message(i18n::text("task-checked", &[("id", t.id.into())]));
         }
         None => {
-            println!("Task {} not found.", task_id);
+            output::fail(EXIT_NOT_FOUND, i18n::text("task-not-found", &[("id", task_id.into())]));
         }
     }
 }
 
-fn uncheck_task(task_id: u32, tasks: &mut Vec<Task>) {
+fn uncheck_task(task_id: u32, tasks: &mut [Task]) {
     let task = tasks.iter_mut().find(|task| task.id == task_id);
     match task {
         Some(t) => {
             t.done = false;
-            println!("Task {} unchecked.", t.id);
+            output::message(i18n::text("task-unchecked", &[("id", t.id.into())]));
         }
         None => {
-            println!("Task {} not found.", task_id);
+            output::fail(EXIT_NOT_FOUND, i18n::text("task-not-found", &[("id", task_id.into())]));
         }
     }
 }
 
-fn archive_task(task_id: u32, tasks: &mut Vec<Task>) {
+fn archive_task(task_id: u32, tasks: &mut [Task]) {
     let task = tasks.iter_mut().find(|task| task.id == task_id);
     match task {
         Some(t) => {
             t.archived = true;
-            println!("Task {} moved to archive.", t.id);
+            output::message(i18n::text("task-archived", &[("id", t.id.into())]));
         }
         None => {
-            println!("Task {} not found.", task_id);
+            output::fail(EXIT_NOT_FOUND, i18n::text("task-not-found", &[("id", task_id.into())]));
         }
     }
 }
 
-fn unarchive_task(task_id: u32, tasks: &mut Vec<Task>) {
+fn unarchive_task(task_id: u32, tasks: &mut [Task]) {
     let task = tasks.iter_mut().find(|task| task.id == task_id);
     match task {
         Some(t) => {
             t.archived = false;
-            println!("Task {} moved out of archive.", t.id);
+            output::message(i18n::text("task-unarchived", &[("id", t.id.into())]));
         }
         None => {
-            println!("Task {} not found.", task_id);
+            output::fail(EXIT_NOT_FOUND, i18n::text("task-not-found", &[("id", task_id.into())]));
         }
     }
 }
 
-fn archive_all_checked(tasks: &mut Vec<Task>) {
+fn archive_all_checked(tasks: &mut [Task]) {
     for task in tasks.iter_mut() {
         if task.done {
             task.archived = true;
-            println!("Task {} moved to archive.", task.id);
+            output::message(i18n::text("task-archived", &[("id", task.id.into())]));
+        }
+    }
+}
+
+fn set_due_date(task_id: u32, due: Option<NaiveDate>, tasks: &mut [Task]) {
+    let task = match tasks.iter_mut().find(|task| task.id == task_id) {
+        Some(task) => task,
+        None => {
+            output::fail(EXIT_NOT_FOUND, i18n::text("task-not-found", &[("id", task_id.into())]));
+            return;
+        }
+    };
+    match due {
+        Some(due) => output::message(i18n::text("task-due", &[("id", task.id.into()), ("date", due.to_string().into())])),
+        None => output::message(i18n::text("task-not-due", &[("id", task.id.into())])),
+    }
+    task.due = due;
+}
+
+fn link_jira_issue(task_id: u32, issue: Option<String>, tasks: &mut [Task]) {
+    let task = match tasks.iter_mut().find(|task| task.id == task_id) {
+        Some(task) => task,
+        None => {
+            output::fail(EXIT_NOT_FOUND, i18n::text("task-not-found", &[("id", task_id.into())]));
+            return;
+        }
+    };
+    match &issue {
+        Some(issue) => output::message(format!("Task {} linked to {}.", task.id, issue)),
+        None => output::message(format!("Task {} unlinked from Jira.", task.id)),
+    }
+    task.jira = issue;
+}
+
+// Finds the task linked to the branch of the repository, or adds one. The repository is kept as
+// project, so branches like main of different repositories get separate tasks.
+fn branch_task(repository: &str, branch: &str, last_id: u32, tasks: &mut Vec<Task>) -> u32 {
+    let task = tasks.iter().find(|task| {
+        !task.archived && task.branch.as_deref() == Some(branch) && task.projects.iter().any(|project| project == repository)
+    });
+    if let Some(task) = task {
+        return task.id;
+    }
+    let mut task = Task::new(next_task_id(tasks, last_id), branch.to_string());
+    task.projects.push(repository.to_string());
+    task.branch = Some(branch.to_string());
+    output::message(format!("Task {} added for branch {}.", task.id, branch));
+    let id = task.id;
+    tasks.push(task);
+    id
+}
+
+// Opens the URL in the default browser
+fn open_url(url: &str) -> std::io::Result<()> {
+    let mut command = if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else {
+        Command::new("xdg-open")
+    };
+    command.arg(url).spawn().map(|_| ())
+}
+
+// Parses the task IDs after the command. Without any, the task is picked in the fuzzy finder from
+// the candidates. Returns None after printing what went wrong.
+fn task_ids(args: &[String], tasks: &[Task], prompt: &str, candidate: impl Fn(&Task) -> bool) -> Option<Vec<u32>> {
+    if args.len() < 3 {
+        let id = select::task(tasks, prompt, candidate);
+        if id.is_none() {
+            output::fail(EXIT_USAGE, "No task ID specified.");
+        }
+        return id.map(|id| vec![id]);
+    }
+    let mut ids = Vec::new();
+    for arg in &args[2..] {
+        match arg.parse::<u32>() {
+            Ok(id) => ids.push(id),
+            Err(_) => {
+                output::fail(EXIT_USAGE, format!("Invalid task ID {}.", arg));
+                return None;
+            }
         }
     }
+    Some(ids)
 }
 
-fn add_task(description: String, tasks: &mut Vec<Task>) {
-    let next_id = tasks.iter().map(|task| task.id).max().unwrap_or(0) + 1;
+fn add_task(description: String, last_id: u32, tasks: &mut Vec<Task>) {
+    let next_id = next_task_id(tasks, last_id);
     let task = Task::new(next_id, description);
     tasks.push(task);
-    println!("Task {} added.", next_id);
-}
-
-fn open_file() -> File {
-    let filename = dirs::home_dir().unwrap().join(TASK_FILE);
-    let path = Path::new(&filename);
-    let file = OpenOptions::new()
-        .read(true)
-        .write(true)
-        .create(true)
-        .open(path)
-        .expect(format!("Failed to open task file {}.", path.display()).as_str());
-    
-    file.lock_exclusive()
-        .expect("Failed to lock task file.");
-
-    file
-}
-
-fn read_tasks(file: &mut File) -> Vec<Task> {
-    if file.metadata().unwrap().len() == 0 {
-        return Vec::new();
-    }
-    let reader = io::BufReader::new(file);
-    let tasks: Vec<Task> = serde_json::from_reader(reader).expect("Failed to parse task file.");
-    tasks
-}
-
-fn write_tasks(tasks: &[Task], file: &mut File) {
-    file.set_len(0).expect("Failed to truncate task file.");
-    file.seek(SeekFrom::Start(0)).expect("Failed to seek to start of task file.");
-    let serialized_tasks = serde_json::to_string_pretty(tasks).expect("Failed to serialize tasks.");
-    let mut writer = io::BufWriter::new(file);
-    writer
-        .write_all(serialized_tasks.as_bytes())
-        .expect("Failed to write tasks.");
-}
-
-fn compute_notifications(tasks: &mut Vec<Task>, notifications: &mut Vec<NotificationContent>) {
-    for task in tasks {
-        match task.pomodoro_time_remaining() {
-            Some(t) => {
-                if t.num_milliseconds() <= 0 {
-                    task.pomodoros.last_mut().unwrap().end_time = Some(task.pomodoros.last().unwrap().start_time + Duration::minutes(POMODORO_DURATION));
-                    notifications.push(NotificationContent {
-                        title: format!("Pomodoro finished for task {}.", task.id),
-                        body: task.description.clone(),
-                    });
+    output::message(i18n::text("task-added", &[("id", next_id.into())]));
+}
+
+// Checks the task files without locking or modifying them, exits with an error if any is invalid
+fn validate_files(files: &[PathBuf]) {
+    for path in files.iter().filter(|path| path.exists()) {
+        match storage::validate_file(path) {
+            Ok((count, problems)) if problems.is_empty() => println!("{}: {} tasks, no problems found.", path.display(), count),
+            Ok((count, problems)) => {
+                output::error(format!("{}: {} tasks, {} problems found:", path.display(), count, problems.len()));
+                for problem in problems {
+                    println!("  {}", problem);
                 }
             },
-            None => {},
+            Err(e) => output::error(format!("{}: {}", path.display(), e)),
         }
     }
 }
 
-fn display_notifications(notifications: Vec<NotificationContent>) {
-    for notification in &notifications {
-        println!("{}: {}", notification.title, notification.body);
-        match Notification::new()
-            .summary(&notification.title)
-            .body(&notification.body)
-            .appname("pt")
-            .timeout(Timeout::Never)
-            .show() {
-                Ok(_) => {},
-                Err(e) => println!("Failed to display notification: {}", e),
+// Creates an empty project task file in the current directory
+fn init_project_file() {
+    let path = Path::new(storage::PROJECT_FILE);
+    if path.exists() {
+        println!("Project task file {} already exists.", path.display());
+        return;
+    }
+    match fs::write(path, schema::serialize_task_file(&[], 0)) {
+        Ok(_) => println!("Created project task file {}.", path.display()),
+        Err(e) => output::error(format!("Failed to create {}: {}", path.display(), e)),
+    }
+}
+
+// Asks how to recover a task or archive file that can't be parsed. Fails with the message to show
+// without a terminal or if the user leaves the file as it is.
+fn ask_recovery(damage: &recovery::Damage) -> Result<recovery::Recovery, String> {
+    println!("The task file {} is corrupt: {}", damage.path.display(), damage.message);
+    match damage.copy_saved {
+        true => println!("A copy of the broken file was saved to {}.", damage.copy.display()),
+        false => println!("A copy of the broken file is in {}.", damage.copy.display()),
+    }
+    if !io::stdin().is_terminal() {
+        return Err("Run pt in an interactive terminal to recover the task file.".to_string());
+    }
+
+    println!("How do you want to recover?");
+    if let Some(created) = damage.backup {
+        println!("  [r] Restore the backup from {}", created.format("%Y-%m-%d %H:%M"));
+    }
+    println!("  [s] Salvage {} readable tasks ({} entries lost)", damage.salvaged, damage.lost);
+    println!("  [q] Quit without changing anything");
+    loop {
+        match prompt("Choice:").as_str() {
+            "r" if damage.backup.is_some() => return Ok(recovery::Recovery::RestoreBackup),
+            "s" => return Ok(recovery::Recovery::Salvage),
+            "q" | "" => return Err(format!("The task file {} was left unchanged.", damage.path.display())),
+            _ => println!("Invalid choice."),
+        }
+    }
+}
+
+// Reads the answer to a question from the terminal, trimmed and in lower case
+fn prompt(question: &str) -> String {
+    print!("{} ", question);
+    let _ = io::stdout().flush();
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer).unwrap_or(0);
+    answer.trim().to_lowercase()
+}
+
+fn exit_code(error: &Error) -> i32 {
+    match error {
+        Error::Locked(_) => EXIT_LOCKED,
+        Error::NotFound(_) => EXIT_NOT_FOUND,
+        Error::Conflict(_) => EXIT_CONFLICT,
+        _ => EXIT_ERROR,
+    }
+}
+
+// For the commands that run pt again for changes
+fn current_exe() -> Option<PathBuf> {
+    match env::current_exe() {
+        Ok(exe) => Some(exe),
+        Err(e) => {
+            output::error(format!("Failed to find the pt executable: {}", e));
+            None
+        }
+    }
+}
+
+// The home directory is checked when pt starts
+fn data_dir() -> PathBuf {
+    pt_core::data_dir().expect("Failed to find the home directory.")
+}
+
+fn pomodoro_notification(task: &Task, pomodoros_today: usize, config: &config::NotificationConfig) -> NotificationContent {
+    let values = [
+        ("id", task.id.to_string()),
+        ("description", task.description.clone()),
+        ("time_spent", format!("{} min", task.time_spent().num_minutes())),
+        ("pomodoros_today", pomodoros_today.to_string()),
+    ];
+    NotificationContent {
+        title: notification::text(config.pomodoro.title.as_deref(), "notification-pomodoro-title", &values),
+        body: notification::text(config.pomodoro.body.as_deref(), "notification-pomodoro-body", &values),
+        kind: NotificationKind::PomodoroFinished,
+        task_id: Some(task.id),
+    }
+}
+
+fn start_notification(task: &Task, config: &config::NotificationConfig) -> NotificationContent {
+    let end = Utc::now() + task.pomodoro_time_remaining().unwrap_or_else(Duration::zero);
+    let values = [
+        ("id", task.id.to_string()),
+        ("description", task.description.clone()),
+        ("end", end.with_timezone(&chrono::Local).format("%H:%M").to_string()),
+    ];
+    NotificationContent {
+        title: notification::text(config.start.title.as_deref(), "notification-start-title", &values),
+        body: notification::text(config.start.body.as_deref(), "notification-start-body", &values),
+        kind: NotificationKind::PomodoroStarted,
+        task_id: None,
+    }
+}
+
+fn due_notification(task: &Task, today: NaiveDate, config: &config::NotificationConfig) -> NotificationContent {
+    let due = task.due.unwrap_or(today);
+    let due = match (due - today).num_days() {
+        0 => i18n::text("due-today", &[]),
+        1 => i18n::text("due-tomorrow", &[]),
+        -1 => i18n::text("due-yesterday", &[]),
+        _ => i18n::text("due-on", &[("date", due.to_string().into())]),
+    };
+    let values = [
+        ("id", task.id.to_string()),
+        ("description", task.description.clone()),
+        ("due", due),
+    ];
+    NotificationContent {
+        title: notification::text(config.due.title.as_deref(), "notification-due-title", &values),
+        body: notification::text(config.due.body.as_deref(), "notification-due-body", &values),
+        kind: NotificationKind::DueReminder,
+        task_id: None,
+    }
+}
+
+// Emails an alert about pomodoros that run for longer than configured, e.g. because --notify
+// doesn't run. Each pomodoro is only reported once.
+fn check_unfinished_pomodoros(tasks: &[Task], config: &config::EmailConfig) {
+    let _lock = state::lock(&data_dir());
+    let mut state = state::load_state(&data_dir());
+    let running: Vec<(&Task, DateTime<Utc>)> = tasks.iter()
+        .filter(|task| task.pomodoro_active())
+        .map(|task| (task, task.pomodoros.last().unwrap().start_time))
+        .collect();
+    let limit = Utc::now() - Duration::minutes(config.unfinished_minutes);
+    let unfinished: Vec<&(&Task, DateTime<Utc>)> = running.iter()
+        .filter(|(_, start_time)| *start_time <= limit && !state.emailed_pomodoros.contains(start_time))
+        .collect();
+    // Reported pomodoros that were finished since are forgotten
+    state.emailed_pomodoros.retain(|start_time| running.iter().any(|(_, running)| running == start_time));
+    if unfinished.is_empty() {
+        println!("No unfinished pomodoros.");
+        state::save_state(&data_dir(), &state);
+        return;
+    }
+    let mut body = String::new();
+    for (task, start_time) in &unfinished {
+        body.push_str(&format!("Task {}: {} (started {})\n", task.id, task.description, start_time.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M")));
+    }
+    match email::send(config, &format!("{} unfinished pomodoros", unfinished.len()), body) {
+        Ok(_) => {
+            println!("Alert about {} unfinished pomodoros sent.", unfinished.len());
+            state.emailed_pomodoros.extend(unfinished.iter().map(|(_, start_time)| *start_time));
+        },
+        Err(e) => output::error(format!("Failed to send the alert: {}", e)),
+    }
+    state::save_state(&data_dir(), &state);
+}
+
+// Stops repeating the alert of a finished pomodoro
+fn acknowledge_alert() -> Option<state::Alert> {
+    let _lock = state::lock(&data_dir());
+    let mut state = state::load_state(&data_dir());
+    let nag = state.nag.take()?;
+    state::save_state(&data_dir(), &state);
+    Some(nag.alert)
+}
+
+// The alert may come from another task list, where the ID belongs to a different task
+fn alert_task(tasks: &[Task], alert: &state::Alert) -> Task {
+    tasks.iter()
+        .find(|task| task.id == alert.task_id && task.description == alert.description)
+        .cloned()
+        .unwrap_or_else(|| Task::new(alert.task_id, alert.description.clone()))
+}
+
+// Drops notifications that are identical to one shown within the last seconds, e.g. when several
+// --notify from cron run at once after the machine woke up, or to another one of the same run
+fn drop_repeated_notifications(notifications: &mut Vec<NotificationContent>, seconds: u32) {
+    let _lock = state::lock(&data_dir());
+    let mut state = state::load_state(&data_dir());
+    let now = Utc::now();
+    let before = state.shown.len();
+    state.shown.retain(|shown| now - shown.time < Duration::seconds(seconds as i64));
+    let mut changed = state.shown.len() != before;
+    notifications.retain(|notification| {
+        if state.shown.iter().any(|shown| shown.title == notification.title && shown.body == notification.body) {
+            return false;
+        }
+        state.shown.push(state::ShownNotification { title: notification.title.clone(), body: notification.body.clone(), time: now });
+        changed = true;
+        true
+    });
+    if changed {
+        state::save_state(&data_dir(), &state);
+    }
+}
+
+fn pomodoros_today(tasks: &[Task]) -> usize {
+    let today = chrono::Local::now().date_naive();
+    tasks.iter()
+        .flat_map(|task| &task.pomodoros)
+        .filter(|pomodoro| pomodoro.start_time.with_timezone(&chrono::Local).date_naive() == today)
+        .count()
+}
+
+fn compute_notifications(tasks: &mut [Task], notifications: &mut Vec<NotificationContent>, config: &config::NotificationConfig, list_name: &str, clock: &dyn Clock) {
+    let _lock = state::lock(&data_dir());
+    let mut state = state::load_state(&data_dir());
+    let mut state_changed = false;
+    let now = clock.now();
+    let today = now.with_timezone(&chrono::Local).date_naive();
+    let pomodoros_today = pomodoros_today(tasks);
+    let nag_interval = Duration::minutes(config.nag_minutes as i64);
+    for task in tasks.iter_mut() {
+        if let Some(t) = task.pomodoro_time_remaining_at(now) {
+            if t.num_milliseconds() <= 0 {
+                task.pomodoros.last_mut().unwrap().end_time = Some(task.pomodoros.last().unwrap().start_time + Duration::minutes(POMODORO_DURATION));
+                notifications.push(pomodoro_notification(task, pomodoros_today, config));
+                let alert = state::Alert { task_id: task.id, description: task.description.clone() };
+                if config.nag_minutes > 0 {
+                    state.nag = Some(state::Snooze { alert: alert.clone(), until: now + nag_interval });
+                }
+                state.last_alert = Some(alert);
+                state_changed = true;
             }
+        }
+    }
+
+    let (due, snoozed) = state.snoozed.into_iter().partition(|snooze| snooze.until <= now);
+    state.snoozed = snoozed;
+    for snooze in due {
+        notifications.push(pomodoro_notification(&alert_task(tasks, &snooze.alert), pomodoros_today, config));
+        if config.nag_minutes > 0 {
+            state.nag = Some(state::Snooze { alert: snooze.alert.clone(), until: now + nag_interval });
+        }
+        state.last_alert = Some(snooze.alert);
+        state_changed = true;
+    }
+
+    if config.nag_minutes == 0 && state.nag.is_some() {
+        state.nag = None;
+        state_changed = true;
+    }
+    if let Some(nag) = state.nag.as_mut().filter(|nag| nag.until <= now) {
+        notifications.push(pomodoro_notification(&alert_task(tasks, &nag.alert), pomodoros_today, config));
+        nag.until = now + nag_interval;
+        state.last_alert = Some(nag.alert.clone());
+        state_changed = true;
     }
-    if !notifications.is_empty() {
-        // Get a output stream handle to the default physical sound device
-        let (_stream, stream_handle) = OutputStream::try_default().unwrap();
-        let sink = Sink::try_new(&stream_handle).unwrap();
-        // Load a sound from a file, using a path relative to Cargo.toml
-        let file = io::BufReader::new(File::open(dirs::home_dir().unwrap().join(ALARM_FILE)).unwrap());
-        // Decode that sound file into a source
-        let source = Decoder::new(file).unwrap();
-        // Play the sound directly on the device
-        sink.append(source);
 
-        // The sound plays in a separate thread. This call will block the current thread until the sink
-        // has finished playing all its queued sounds.
-        sink.sleep_until_end();
+    if let Some(break_end) = state.break_end.filter(|break_end| *break_end <= now) {
+        let values = [("end", break_end.with_timezone(&chrono::Local).format("%H:%M").to_string())];
+        notifications.push(NotificationContent {
+            title: notification::text(config.break_finished.title.as_deref(), "notification-break-title", &values),
+            body: notification::text(config.break_finished.body.as_deref(), "notification-break-body", &values),
+            kind: NotificationKind::BreakFinished,
+            task_id: None,
+        });
+        state.break_end = None;
+        state_changed = true;
+    }
+    if config.due_reminders {
+        let before = state.due_reminders.len();
+        state.due_reminders.retain(|reminder| reminder.date == today);
+        state_changed |= state.due_reminders.len() != before;
+        let remind_until = today + Duration::days(config.due_lead_days);
+        for task in tasks.iter().filter(|task| !task.done && !task.archived && task.due.is_some_and(|due| due <= remind_until)) {
+            if state.due_reminders.iter().any(|reminder| reminder.list == list_name && reminder.task_id == task.id) {
+                continue;
+            }
+            notifications.push(due_notification(task, today, config));
+            state.due_reminders.push(state::DueReminder { list: list_name.to_string(), task_id: task.id, date: today });
+            state_changed = true;
+        }
+    }
+    if state_changed {
+        state::save_state(&data_dir(), &state);
     }
-    
 }
@@ -0,0 +1,98 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::{next_task_id, Task};
+use crate::config::{self, MarkdownConfig};
+use crate::storage;
+use crate::todotxt;
+
+// Active tasks are mirrored into a Markdown checklist, e.g. in an Obsidian vault. Each line ends
+// with a block ID like ^pt-12 that links it to its task.
+const HEADER: &str = "<!-- Written by pt. Check boxes or add lines like \"- [ ] New task\", everything else is overwritten. -->";
+const ID_PREFIX: &str = "^pt-";
+
+// Path of the Markdown file of a task list, None if the list is not mirrored
+pub fn markdown_path(config: &MarkdownConfig, list_name: &str) -> Option<PathBuf> {
+    let path = config.path.as_ref()?;
+    let path = if path.contains("{list}") {
+        path.replace("{list}", list_name)
+    } else if list_name == storage::DEFAULT_LIST {
+        path.clone()
+    } else {
+        return None;
+    };
+    Some(config::expand_home(&path))
+}
+
+// Splits a checklist line into its checkbox state, text and task ID
+fn parse_line(line: &str) -> Option<(bool, &str, Option<u32>)> {
+    let rest = line.trim_start().strip_prefix("- [")?;
+    let done = match rest.chars().next()? {
+        'x' | 'X' => true,
+        ' ' => false,
+        _ => return None,
+    };
+    let text = rest[1..].strip_prefix(']')?.trim();
+    match text.rsplit_once(ID_PREFIX) {
+        Some((text, id)) => Some((done, text.trim(), id.trim().parse().ok())),
+        None => Some((done, text, None)),
+    }
+}
+
+// Applies checkbox changes and new lines of the Markdown file to the tasks.
+// Returns the number of changed or added tasks.
+pub fn read_back(path: &Path, tasks: &mut Vec<Task>, last_id: u32) -> usize {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(_) => return 0,
+    };
+    let mut count = 0;
+    for (done, text, id) in content.lines().filter_map(parse_line) {
+        match id {
+            Some(id) => {
+                if let Some(task) = tasks.iter_mut().find(|task| task.id == id && task.done != done) {
+                    task.done = done;
+                    let state = if done { "checked" } else { "unchecked" };
+                    println!("Task {} {} in {}.", id, state, path.display());
+                    count += 1;
+                }
+            },
+            None => {
+                // New lines can use todo.txt notation for priority, projects, tags and due date
+                let parsed = match todotxt::parse_line(text) {
+                    Some(parsed) => parsed,
+                    None => continue,
+                };
+                let mut task = Task::new(next_task_id(tasks, last_id), parsed.description);
+                task.done = done;
+                task.priority = parsed.priority;
+                task.projects = parsed.projects;
+                task.tags = parsed.contexts;
+                task.due = parsed.due;
+                println!("Task {} added from {}.", task.id, path.display());
+                tasks.push(task);
+                count += 1;
+            },
+        }
+    }
+    count
+}
+
+// Formats a task as a checklist line with the time spent on it
+pub fn format_task(task: &Task) -> String {
+    let status = if task.done { "x" } else { " " };
+    format!("- [{}] {} (Σ{} min) {}{}", status, task.display_description(), task.time_spent().num_minutes(), ID_PREFIX, task.id)
+}
+
+pub fn write(path: &Path, tasks: &[Task]) -> io::Result<()> {
+    let mut content = format!("{}\n\n", HEADER);
+    for task in tasks.iter().filter(|task| !task.archived) {
+        content.push_str(&format_task(task));
+        content.push('\n');
+    }
+    if fs::read_to_string(path).is_ok_and(|existing| existing == content) {
+        return Ok(());
+    }
+    fs::write(path, content)
+}
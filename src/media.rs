@@ -0,0 +1,66 @@
+use std::path::Path;
+use std::process::Command;
+
+use crate::config::MediaConfig;
+use crate::event::Event;
+use crate::state;
+use crate::Task;
+
+// Media players are controlled over MPRIS with playerctl
+fn playerctl(args: &[&str]) -> Result<String, String> {
+    let output = Command::new("playerctl").args(args).output().map_err(|e| format!("failed to run playerctl: {}", e))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn playing_players() -> Result<Vec<String>, String> {
+    // playerctl fails if no player is running, so only a missing playerctl is an error
+    let output = Command::new("playerctl").arg("--list-all").output().map_err(|e| format!("failed to run playerctl: {}", e))?;
+    Ok(String::from_utf8_lossy(&output.stdout).lines()
+        .filter(|player| playerctl(&[&format!("--player={}", player), "status"]).is_ok_and(|status| status == "Playing"))
+        .map(str::to_string)
+        .collect())
+}
+
+// Pauses the players that are playing when a pomodoro starts, and resumes them when the last
+// running pomodoro is finished or a break is started. Players that were paused before are left alone.
+pub fn update(config: &MediaConfig, data_dir: &Path, events: &[Event], tasks: &[Task], break_started: bool) {
+    if !config.enabled {
+        return;
+    }
+    let started = events.iter().any(|event| matches!(event, Event::PomodoroStarted { .. }));
+    let finished = events.iter().any(|event| matches!(event, Event::PomodoroFinished { .. }));
+    let resume = (finished || break_started) && !tasks.iter().any(|task| task.pomodoro_active());
+    if !started && !resume {
+        return;
+    }
+    let _lock = state::lock(data_dir);
+    let mut state = state::load_state(data_dir);
+    if !started && state.paused_players.is_empty() {
+        return;
+    }
+    if started {
+        let players = match playing_players() {
+            Ok(players) => players,
+            Err(e) => {
+                println!("Failed to pause media players: {}", e);
+                return;
+            }
+        };
+        for player in players {
+            match playerctl(&[&format!("--player={}", player), "pause"]) {
+                Ok(_) if !state.paused_players.contains(&player) => state.paused_players.push(player),
+                Ok(_) => (),
+                Err(e) => println!("Failed to pause {}: {}", player, e),
+            }
+        }
+    } else {
+        // Players that were closed in the meantime are forgotten as well
+        for player in state.paused_players.drain(..) {
+            let _ = playerctl(&[&format!("--player={}", player), "play"]);
+        }
+    }
+    state::save_state(data_dir, &state);
+}
@@ -0,0 +1,156 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, IsTerminal, Seek, SeekFrom, Write};
+use std::path::Path;
+use fs2::FileExt;
+
+use crate::{Pomodoro, Task};
+use crate::crypto;
+use crate::schema::{self, TaskFile};
+
+#[derive(Default)]
+pub struct MergeReport {
+    // Tasks that only existed in the second file
+    pub added: usize,
+    // Tasks that existed in both files and differed
+    pub merged: usize,
+    pub conflicts: usize,
+}
+
+fn read_task_file(path: &Path) -> Result<TaskFile, String> {
+    let content = fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let content = crypto::decode(content).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    schema::parse_task_file(&content).map_err(|e| format!("{} is not a valid task file: {}", path.display(), e))
+}
+
+// Everything but the pomodoros
+fn same_fields(a: &Task, b: &Task) -> bool {
+    a.description == b.description
+        && a.done == b.done
+        && a.archived == b.archived
+        && a.priority == b.priority
+        && a.projects == b.projects
+        && a.tags == b.tags
+        && a.due == b.due
+}
+
+// Pomodoros are identified by their start time. If both files finished the same pomodoro at
+// different times, prefer_b decides which end time is kept and the conflict is reported.
+fn merge_pomodoros(a: &[Pomodoro], b: &[Pomodoro], prefer_b: bool) -> (Vec<Pomodoro>, bool) {
+    let mut merged = a.to_vec();
+    let mut conflict = false;
+    for pomodoro in b {
+        match merged.iter_mut().find(|p| p.start_time == pomodoro.start_time) {
+            Some(existing) => match (existing.end_time, pomodoro.end_time) {
+                (None, Some(_)) => existing.end_time = pomodoro.end_time,
+                (Some(x), Some(y)) if x != y => {
+                    conflict = true;
+                    if prefer_b {
+                        existing.end_time = pomodoro.end_time;
+                    }
+                },
+                _ => {},
+            },
+            None => merged.push(pomodoro.clone()),
+        }
+    }
+    merged.sort_by_key(|p| p.start_time);
+    (merged, conflict)
+}
+
+enum Resolution {
+    KeepA,
+    KeepB,
+    KeepBoth,
+}
+
+fn ask(a: &Task, b: &Task, a_path: &Path, b_path: &Path) -> Resolution {
+    if !io::stdin().is_terminal() {
+        println!("Run pt in an interactive terminal to resolve conflicts.");
+        std::process::exit(1);
+    }
+    println!("Task {} differs:", a.id);
+    println!("  [a] {} ({} pomodoros) from {}", a.display_description(), a.pomodoros.len(), a_path.display());
+    println!("  [b] {} ({} pomodoros) from {}", b.display_description(), b.pomodoros.len(), b_path.display());
+    println!("  [k] Keep both as separate tasks");
+    loop {
+        match crate::prompt("Choice:").as_str() {
+            "a" => return Resolution::KeepA,
+            "b" => return Resolution::KeepB,
+            "k" => return Resolution::KeepBoth,
+            _ => println!("Invalid choice."),
+        }
+    }
+}
+
+// Merges the tasks of two copies of a task file, e.g. conflict copies created by file sync.
+// Tasks are matched by ID, the user is only asked if both copies changed a task differently.
+pub fn merge(a: TaskFile, b: TaskFile, a_path: &Path, b_path: &Path) -> (TaskFile, MergeReport) {
+    let mut report = MergeReport::default();
+    let mut tasks = a.tasks;
+    let mut last_id = a.last_id.max(b.last_id);
+    last_id = tasks.iter().chain(b.tasks.iter()).map(|task| task.id).fold(last_id, u32::max);
+    let mut separate = Vec::new();
+
+    for other in b.tasks {
+        let task = match tasks.iter_mut().find(|task| task.id == other.id) {
+            Some(task) => task,
+            None => {
+                tasks.push(other);
+                report.added += 1;
+                continue;
+            }
+        };
+        if *task == other {
+            continue;
+        }
+        report.merged += 1;
+        // Differences in the pomodoros alone are merged without asking
+        if same_fields(task, &other) {
+            let (pomodoros, conflict) = merge_pomodoros(&task.pomodoros, &other.pomodoros, false);
+            if !conflict {
+                task.pomodoros = pomodoros;
+                continue;
+            }
+        }
+        report.conflicts += 1;
+        match ask(task, &other, a_path, b_path) {
+            Resolution::KeepA => task.pomodoros = merge_pomodoros(&task.pomodoros, &other.pomodoros, false).0,
+            Resolution::KeepB => {
+                let (pomodoros, _) = merge_pomodoros(&task.pomodoros, &other.pomodoros, true);
+                *task = other;
+                task.pomodoros = pomodoros;
+            },
+            Resolution::KeepBoth => separate.push(other),
+        }
+    }
+    for mut task in separate {
+        last_id += 1;
+        println!("Task {} from {} was added as task {}.", task.id, b_path.display(), last_id);
+        task.id = last_id;
+        tasks.push(task);
+    }
+    tasks.sort_by_key(|task| task.id);
+    (TaskFile { last_id, tasks }, report)
+}
+
+// Merges the second file into the first one. The first file is locked like a task file while merging.
+pub fn merge_files(a_path: &Path, b_path: &Path) -> Result<MergeReport, String> {
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(a_path)
+        .map_err(|e| format!("Failed to open {}: {}", a_path.display(), e))?;
+    file.lock_exclusive().map_err(|e| format!("Failed to lock {}: {}", a_path.display(), e))?;
+
+    let a = read_task_file(a_path)?;
+    let b = read_task_file(b_path)?;
+    let (merged, report) = merge(a, b, a_path, b_path);
+
+    let content = crypto::encode(schema::serialize_task_file(&merged.tasks, merged.last_id))?;
+    let mut file = &file;
+    file.set_len(0)
+        .and_then(|_| file.seek(SeekFrom::Start(0)))
+        .and_then(|_| file.write_all(content.as_bytes()))
+        .map_err(|e| format!("Failed to write {}: {}", a_path.display(), e))?;
+    Ok(report)
+}
@@ -0,0 +1,168 @@
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::process;
+use std::time::Duration;
+
+use crate::config::MqttConfig;
+use crate::event::Event;
+use crate::state::{self, State};
+use crate::status::{self, Format};
+use crate::{data_dir, pomodoros_today, Task};
+
+// MQTT 3.1.1 packet types, in the upper four bits of the first byte
+const CONNECT: u8 = 0x10;
+const CONNACK: u8 = 0x20;
+const PUBLISH: u8 = 0x30;
+const DISCONNECT: u8 = 0xe0;
+const PROTOCOL_LEVEL: u8 = 4;
+const KEEP_ALIVE: u16 = 60;
+// A broker that doesn't answer must not hold up the command
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+struct Message {
+    topic: String,
+    payload: String,
+    // The broker keeps the last retained message of a topic for new subscribers
+    retain: bool,
+}
+
+// Strings are prefixed with their length as a big endian u16
+fn push_string(packet: &mut Vec<u8>, value: &str) -> Result<(), String> {
+    let length = u16::try_from(value.len()).map_err(|_| "strings in MQTT packets can't be longer than 65535 bytes")?;
+    packet.extend_from_slice(&length.to_be_bytes());
+    packet.extend_from_slice(value.as_bytes());
+    Ok(())
+}
+
+// The fixed header holds the type and the remaining length, 7 bits per byte
+fn packet(kind: u8, body: &[u8]) -> Vec<u8> {
+    let mut packet = vec![kind];
+    let mut length = body.len();
+    loop {
+        let byte = (length % 128) as u8;
+        length /= 128;
+        if length == 0 {
+            packet.push(byte);
+            break;
+        }
+        packet.push(byte | 0x80);
+    }
+    packet.extend_from_slice(body);
+    packet
+}
+
+fn connect(config: &MqttConfig) -> Result<TcpStream, String> {
+    let host = config.host.as_deref().ok_or("no host configured")?;
+    let mut error = format!("{} has no address", host);
+    let mut stream = None;
+    for address in (host, config.port).to_socket_addrs().map_err(|e| e.to_string())? {
+        match TcpStream::connect_timeout(&address, TIMEOUT) {
+            Ok(connected) => {
+                stream = Some(connected);
+                break;
+            },
+            Err(e) => error = e.to_string(),
+        }
+    }
+    let mut stream = stream.ok_or(error)?;
+    stream.set_read_timeout(Some(TIMEOUT)).map_err(|e| e.to_string())?;
+    stream.set_write_timeout(Some(TIMEOUT)).map_err(|e| e.to_string())?;
+
+    let mut body = Vec::new();
+    push_string(&mut body, "MQTT")?;
+    body.push(PROTOCOL_LEVEL);
+    // Clean session, with the flags for username and password if they are set
+    let mut flags = 0x02;
+    if config.username.is_some() {
+        flags |= 0x80;
+    }
+    if config.password.is_some() {
+        flags |= 0x40;
+    }
+    body.push(flags);
+    body.extend_from_slice(&KEEP_ALIVE.to_be_bytes());
+    push_string(&mut body, &format!("pt-{}", process::id()))?;
+    if let Some(username) = &config.username {
+        push_string(&mut body, username)?;
+    }
+    if let Some(password) = &config.password {
+        push_string(&mut body, password)?;
+    }
+    stream.write_all(&packet(CONNECT, &body)).map_err(|e| e.to_string())?;
+
+    let mut connack = [0; 4];
+    stream.read_exact(&mut connack).map_err(|e| e.to_string())?;
+    match connack {
+        [CONNACK, 2, _, 0] => Ok(stream),
+        [CONNACK, 2, _, 4] | [CONNACK, 2, _, 5] => Err("the broker refused the username or password".to_string()),
+        [CONNACK, 2, _, code] => Err(format!("the broker refused the connection with code {}", code)),
+        _ => Err("unexpected answer from the broker".to_string()),
+    }
+}
+
+// Publishes the messages with QoS 0, which needs no acknowledgement
+fn publish(config: &MqttConfig, messages: &[Message]) -> Result<(), String> {
+    let mut stream = connect(config)?;
+    for message in messages {
+        let mut body = Vec::new();
+        push_string(&mut body, &message.topic)?;
+        body.extend_from_slice(message.payload.as_bytes());
+        let kind = if message.retain { PUBLISH | 0x01 } else { PUBLISH };
+        stream.write_all(&packet(kind, &body)).map_err(|e| e.to_string())?;
+    }
+    stream.write_all(&packet(DISCONNECT, &[])).map_err(|e| e.to_string())
+}
+
+fn topic(config: &MqttConfig, list_name: &str, name: &str) -> String {
+    format!("{}/{}", config.topic.replace("{list}", list_name).trim_end_matches('/'), name)
+}
+
+// Retained status of the running pomodoro or break, in the JSON format of pt --status
+fn state_message(config: &MqttConfig, list_name: &str, tasks: &[Task], state: &State, pomodoros_today: usize) -> Message {
+    Message {
+        topic: topic(config, list_name, "state"),
+        payload: status::render(tasks, state, pomodoros_today, Format::Json),
+        retain: true,
+    }
+}
+
+// Publishes the events of a command and the new status, e.g. for Home Assistant automations
+pub fn update(config: &MqttConfig, events: &[Event], list_name: &str, tasks: &[Task], break_started: bool) {
+    if config.host.is_none() || (events.is_empty() && !break_started) {
+        return;
+    }
+    let mut messages: Vec<Message> = events.iter()
+        .map(|event| Message {
+            topic: topic(config, list_name, event.name()),
+            payload: serde_json::to_string(&event.payload(list_name)).unwrap(),
+            retain: false,
+        })
+        .collect();
+    messages.push(state_message(config, list_name, tasks, &state::load_state(&data_dir()), pomodoros_today(tasks)));
+    if let Err(e) = publish(config, &messages) {
+        println!("Failed to publish to MQTT: {}", e);
+    }
+}
+
+// Keeps the remaining time on the state topic current while the daemon runs
+#[derive(Default)]
+pub struct Publisher {
+    failing: bool,
+}
+
+impl Publisher {
+    pub fn update(&mut self, config: &MqttConfig, list_name: &str, tasks: &[Task], state: &State) {
+        if config.host.is_none() {
+            return;
+        }
+        match publish(config, &[state_message(config, list_name, tasks, state, pomodoros_today(tasks))]) {
+            Ok(_) => self.failing = false,
+            // The broker is tried again on the next update, the error is only printed once
+            Err(e) if !self.failing => {
+                println!("Failed to publish to MQTT: {}", e);
+                self.failing = true;
+            },
+            Err(_) => (),
+        }
+    }
+}
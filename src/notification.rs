@@ -0,0 +1,306 @@
+use std::env;
+use std::panic::{self, AssertUnwindSafe};
+use std::process::Command;
+
+use chrono::{Datelike, Duration, Local, NaiveDateTime};
+use fluent_bundle::FluentValue;
+use notify_rust::{Notification, Timeout};
+#[cfg(all(unix, not(target_os = "macos")))]
+use notify_rust::Hint;
+#[cfg(not(target_os = "macos"))]
+use notify_rust::Urgency;
+
+use crate::config::{Config, EventNotificationConfig, NotificationConfig, QuietHours};
+use crate::i18n;
+use crate::push;
+use crate::sound::{self, SoundEvent};
+
+#[derive(Clone, Copy)]
+pub enum NotificationKind {
+    PomodoroStarted,
+    PomodoroFinished,
+    BreakFinished,
+    DueReminder,
+}
+
+impl NotificationKind {
+    // Sound played with the notification, if any
+    fn sound(self) -> Option<SoundEvent> {
+        match self {
+            NotificationKind::PomodoroStarted => None,
+            NotificationKind::PomodoroFinished => Some(SoundEvent::PomodoroFinished),
+            NotificationKind::BreakFinished => Some(SoundEvent::BreakFinished),
+            NotificationKind::DueReminder => Some(SoundEvent::DueReminder),
+        }
+    }
+
+    // Name of the kind in the config file
+    pub fn name(self) -> &'static str {
+        match self {
+            NotificationKind::PomodoroStarted => "start",
+            NotificationKind::PomodoroFinished => "pomodoro",
+            NotificationKind::BreakFinished => "break",
+            NotificationKind::DueReminder => "due",
+        }
+    }
+}
+
+pub struct NotificationContent {
+    pub title: String,
+    pub body: String,
+    pub kind: NotificationKind,
+    // Task the notification is about, enables the actions for it
+    pub task_id: Option<u32>,
+}
+
+// Replaces the {name} placeholders of a notification template with their values
+pub fn render(template: &str, values: &[(&str, String)]) -> String {
+    let mut text = template.to_string();
+    for (name, value) in values {
+        text = text.replace(&format!("{{{}}}", name), value);
+    }
+    text
+}
+
+// The template of the config file if there is one, or else the message of the language
+pub fn text(template: Option<&str>, id: &str, values: &[(&str, String)]) -> String {
+    match template {
+        Some(template) => render(template, values),
+        None => {
+            let values: Vec<(&str, FluentValue)> = values.iter().map(|(name, value)| (*name, value.clone().into())).collect();
+            i18n::text(id, &values)
+        }
+    }
+}
+
+// Buttons of a finished pomodoro notification and the pt arguments they run
+fn actions(task_id: u32) -> Vec<(&'static str, String, Vec<String>)> {
+    vec![
+        ("break", i18n::text("action-break", &[]), vec!["--break".to_string()]),
+        ("snooze", i18n::text("action-snooze", &[]), vec!["--snooze".to_string()]),
+        ("ack", i18n::text("action-acknowledge", &[]), vec!["--ack".to_string()]),
+        ("pomodoro", i18n::text("action-pomodoro", &[]), vec!["--pomodoro".to_string(), task_id.to_string()]),
+        ("done", i18n::text("action-done", &[]), vec!["--check".to_string(), task_id.to_string()]),
+    ]
+}
+
+// Runs pt again for the chosen action, so the task list is locked and saved like for any other command.
+// list_args select the same task list as the current command.
+fn run_action(list_args: &[String], args: &[String]) {
+    let exe = match env::current_exe() {
+        Ok(exe) => exe,
+        Err(e) => {
+            println!("Failed to run the notification action: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = Command::new(exe).args(list_args).args(args).status() {
+        println!("Failed to run the notification action: {}", e);
+    }
+}
+
+// macOS only shows notifications on behalf of an application bundle. pt has none, so it borrows
+// the one of the Terminal.
+#[cfg(target_os = "macos")]
+fn set_application() {
+    // Fails if it was already set, which is fine
+    let _ = notify_rust::set_application("com.apple.Terminal");
+}
+
+#[cfg(not(target_os = "macos"))]
+fn set_application() {}
+
+// Shows the notification through AppleScript if the notification center refused it.
+// Returns whether that worked.
+#[cfg(target_os = "macos")]
+fn show_fallback(notification: &NotificationContent) -> bool {
+    let quote = |text: &str| format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""));
+    let script = format!("display notification {} with title {}", quote(&notification.body), quote(&notification.title));
+    Command::new("osascript").arg("-e").arg(script).status().is_ok_and(|status| status.success())
+}
+
+// Shows the notification as a balloon of a tray icon through PowerShell if the toast failed, e.g.
+// when the toast API is missing. The icon has to stay until the balloon is gone, so PowerShell is
+// left running for it. Returns whether PowerShell started.
+#[cfg(windows)]
+fn show_fallback(notification: &NotificationContent) -> bool {
+    let quote = |text: &str| format!("'{}'", text.replace('\'', "''"));
+    let script = format!(
+        "Add-Type -AssemblyName System.Windows.Forms; $icon = New-Object System.Windows.Forms.NotifyIcon; \
+         $icon.Icon = [System.Drawing.SystemIcons]::Information; $icon.Visible = $true; \
+         $icon.ShowBalloonTip(10000, {}, {}, 'None'); Start-Sleep -Seconds 10; $icon.Dispose()",
+        quote(&notification.title),
+        quote(&notification.body),
+    );
+    Command::new("powershell").args(["-NoProfile", "-NonInteractive", "-Command", &script]).spawn().is_ok()
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn show_fallback(_notification: &NotificationContent) -> bool {
+    false
+}
+
+fn event_settings(config: &NotificationConfig, kind: NotificationKind) -> &EventNotificationConfig {
+    match kind {
+        NotificationKind::PomodoroStarted => &config.start,
+        NotificationKind::PomodoroFinished => &config.pomodoro,
+        NotificationKind::BreakFinished => &config.break_finished,
+        NotificationKind::DueReminder => &config.due,
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+fn urgency(urgency: crate::config::Urgency) -> Urgency {
+    match urgency {
+        crate::config::Urgency::Low => Urgency::Low,
+        crate::config::Urgency::Normal => Urgency::Normal,
+        crate::config::Urgency::Critical => Urgency::Critical,
+    }
+}
+
+// The Linux notification servers support urgency and residency, Windows only urgency and macOS neither
+#[cfg(all(unix, not(target_os = "macos")))]
+fn apply_platform_settings(builder: &mut Notification, settings: &EventNotificationConfig, quiet: bool) {
+    let level = if quiet { crate::config::Urgency::Low } else { settings.urgency };
+    builder.urgency(urgency(level)).hint(Hint::Resident(settings.resident));
+}
+
+// Toasts are only shown on behalf of an installed app. pt has none, so it borrows the one of PowerShell.
+#[cfg(windows)]
+const WINDOWS_APP_ID: &str = "{1AC14E77-02E7-4E5D-B744-2EB1AE5198B7}\\WindowsPowerShell\\v1.0\\powershell.exe";
+
+#[cfg(windows)]
+fn apply_platform_settings(builder: &mut Notification, settings: &EventNotificationConfig, quiet: bool) {
+    let level = if quiet { crate::config::Urgency::Low } else { settings.urgency };
+    builder.urgency(urgency(level)).app_id(WINDOWS_APP_ID);
+}
+
+#[cfg(target_os = "macos")]
+fn apply_platform_settings(_builder: &mut Notification, _settings: &EventNotificationConfig, _quiet: bool) {}
+
+fn in_window(window: &QuietHours, time: NaiveDateTime) -> bool {
+    // A window that spans midnight started on the day before for times before its end
+    let start_day = if window.start > window.end && time.time() < window.end { time.date() - Duration::days(1) } else { time.date() };
+    if !window.days.is_empty() && !window.days.contains(&start_day.weekday()) {
+        return false;
+    }
+    match window.start.cmp(&window.end) {
+        std::cmp::Ordering::Equal => true,
+        std::cmp::Ordering::Less => window.start <= time.time() && time.time() < window.end,
+        std::cmp::Ordering::Greater => window.start <= time.time() || time.time() < window.end,
+    }
+}
+
+fn is_quiet(quiet_hours: &[QuietHours]) -> bool {
+    let now = Local::now().naive_local();
+    quiet_hours.iter().any(|window| in_window(window, now))
+}
+
+// Without a notification service, e.g. on a server over SSH, the notification is printed in a
+// frame and the terminal bell rings instead
+fn print_banner(notification: &NotificationContent) {
+    let width = notification.title.chars().count().max(notification.body.chars().count());
+    let border = "*".repeat(width + 4);
+    println!("\x07{}", border);
+    println!("* {:<width$} *", notification.title, width = width);
+    println!("* {:<width$} *", notification.body, width = width);
+    println!("{}", border);
+}
+
+// Name and version of the notification server, for pt --doctor
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn server() -> Result<String, String> {
+    match panic::catch_unwind(notify_rust::get_server_information) {
+        Ok(Ok(server)) => Ok(format!("{} {}", server.name, server.version)),
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(_) => Err("the notification server failed".to_string()),
+    }
+}
+
+// Other systems always have a notification center
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+pub fn server() -> Result<String, String> {
+    Ok("notification center of the system".to_string())
+}
+
+// Shows the notifications and plays their sounds. With notification actions enabled, waits until
+// the notifications with buttons are answered or closed. Quiet doesn't print the notifications,
+// e.g. for cron, which mails all output.
+pub fn display_notifications(notifications: Vec<NotificationContent>, config: &Config, list_args: &[String], quiet_output: bool) {
+    set_application();
+    let quiet = is_quiet(&config.quiet_hours);
+    let mut handles = Vec::new();
+    for notification in &notifications {
+        let settings = event_settings(&config.notification, notification.kind);
+        push::send(&config.push, &config.telegram, notification, if quiet { crate::config::Urgency::Low } else { settings.urgency });
+        if !config.notification.desktop {
+            if !quiet_output {
+                println!("{}: {}", notification.title, notification.body);
+            }
+            continue;
+        }
+        let timeout = match settings.timeout {
+            0 => Timeout::Never,
+            seconds => Timeout::Milliseconds(seconds * 1000),
+        };
+        let mut builder = Notification::new();
+        builder
+            .summary(&notification.title)
+            .body(&notification.body)
+            .appname("pt")
+            .timeout(timeout);
+        apply_platform_settings(&mut builder, settings, quiet);
+        let task_id = notification.task_id.filter(|_| config.notification.actions);
+        if let Some(task_id) = task_id {
+            for (identifier, label, _) in actions(task_id) {
+                builder.action(identifier, &label);
+            }
+        }
+        // A broken notification server must not crash pt, the notification is printed instead
+        match panic::catch_unwind(AssertUnwindSafe(|| builder.show())) {
+            Ok(Ok(handle)) => {
+                if !quiet_output {
+                    println!("{}: {}", notification.title, notification.body);
+                }
+                if let Some(task_id) = task_id {
+                    handles.push((handle, task_id));
+                }
+            },
+            _ => {
+                if show_fallback(notification) {
+                    if !quiet_output {
+                        println!("{}: {}", notification.title, notification.body);
+                    }
+                } else if !quiet_output {
+                    print_banner(notification);
+                }
+            },
+        }
+    }
+
+    // Each kind of event is only heard once
+    let mut events: Vec<SoundEvent> = Vec::new();
+    for event in notifications.iter().filter_map(|notification| notification.kind.sound()) {
+        if !events.contains(&event) {
+            events.push(event);
+        }
+    }
+    if !quiet {
+        let texts: Vec<String> = notifications.iter()
+            .filter(|notification| notification.kind.sound().is_some())
+            .map(|notification| format!("{} {}", notification.title, notification.body))
+            .collect();
+        sound::play_in_background(&config.sound, &events, &texts);
+    }
+
+    for (handle, task_id) in handles {
+        let mut chosen = None;
+        handle.wait_for_action(|identifier| chosen = Some(identifier.to_string()));
+        let args = actions(task_id).into_iter()
+            .find(|(identifier, _, _)| Some(*identifier) == chosen.as_deref())
+            .map(|(_, _, args)| args);
+        if let Some(args) = args {
+            run_action(list_args, &args);
+        }
+    }
+}
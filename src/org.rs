@@ -0,0 +1,173 @@
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+
+use crate::{next_task_id, Pomodoro, Task};
+
+// Emacs org-mode files, see https://orgmode.org/manual/
+// Headlines with a TODO keyword are tasks, headlines without one are the project of the tasks
+// below them. Clock entries are pomodoros, their times are in local time.
+
+const OPEN_KEYWORDS: [&str; 4] = ["TODO", "NEXT", "WAITING", "STARTED"];
+const DONE_KEYWORDS: [&str; 3] = ["DONE", "CANCELLED", "CANCELED"];
+
+// Parses a timestamp like [2024-03-01 Fri 10:00]
+fn parse_timestamp(timestamp: &str) -> Option<DateTime<Utc>> {
+    let inner = timestamp.trim().strip_prefix('[')?.strip_suffix(']')?;
+    let words: Vec<&str> = inner.split_whitespace().collect();
+    let (date, time) = (words.first()?, words.last()?);
+    let naive = NaiveDateTime::parse_from_str(&format!("{} {}", date, time), "%Y-%m-%d %H:%M").ok()?;
+    Local.from_local_datetime(&naive).earliest().map(|time| time.with_timezone(&Utc))
+}
+
+fn format_timestamp(time: DateTime<Utc>) -> String {
+    time.with_timezone(&Local).format("[%Y-%m-%d %a %H:%M]").to_string()
+}
+
+// Parses a CLOCK line, e.g. CLOCK: [2024-03-01 Fri 10:00]--[2024-03-01 Fri 10:25] =>  0:25
+fn parse_clock(line: &str) -> Option<Pomodoro> {
+    let clock = line.trim().strip_prefix("CLOCK:")?;
+    let clock = clock.split("=>").next()?.trim();
+    match clock.split_once("--") {
+        Some((start, end)) => Some(Pomodoro::new(parse_timestamp(start)?, Some(parse_timestamp(end)?))),
+        None => Some(Pomodoro::new(parse_timestamp(clock)?, None)),
+    }
+}
+
+// Parses DEADLINE: <2024-03-01 Fri> from a planning line
+fn parse_deadline(line: &str) -> Option<NaiveDate> {
+    let deadline = &line[line.find("DEADLINE:")? + "DEADLINE:".len()..];
+    let deadline = deadline.trim_start().strip_prefix('<')?;
+    NaiveDate::parse_from_str(deadline.get(..10)?, "%Y-%m-%d").ok()
+}
+
+// Splits a headline into its level and the text after the stars
+fn parse_headline(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|c| *c == '*').count();
+    if level == 0 || !line[level..].starts_with(' ') {
+        return None;
+    }
+    Some((level, line[level..].trim()))
+}
+
+// Reads keyword, priority, title and tags from the text of a headline
+fn parse_task_headline(text: &str) -> Option<(bool, Option<char>, String, Vec<String>)> {
+    let mut words: Vec<&str> = text.split_whitespace().collect();
+    let keyword = *words.first()?;
+    let done = if DONE_KEYWORDS.contains(&keyword) {
+        true
+    } else if OPEN_KEYWORDS.contains(&keyword) {
+        false
+    } else {
+        return None;
+    };
+    words.remove(0);
+
+    let mut priority = None;
+    if let Some(p) = words.first().and_then(|w| w.strip_prefix("[#")).and_then(|w| w.strip_suffix(']')) {
+        priority = p.chars().next().filter(|p| p.is_ascii_uppercase());
+        words.remove(0);
+    }
+    let mut tags = Vec::new();
+    if let Some(last) = words.last().filter(|w| w.len() > 1 && w.starts_with(':') && w.ends_with(':')) {
+        tags = last.split(':').filter(|tag| !tag.is_empty()).map(str::to_string).collect();
+        words.pop();
+    }
+    Some((done, priority, words.join(" "), tags))
+}
+
+// Adds all tasks of an org file, returns the number of imported tasks
+pub fn import(content: &str, last_id: u32, tasks: &mut Vec<Task>) -> usize {
+    let mut count = 0;
+    // Headlines without keyword above the current line, by level
+    let mut projects: Vec<(usize, String)> = Vec::new();
+    // Index of the task the following lines belong to
+    let mut current: Option<usize> = None;
+
+    for line in content.lines() {
+        if let Some((level, text)) = parse_headline(line) {
+            projects.retain(|(l, _)| *l < level);
+            current = None;
+            match parse_task_headline(text) {
+                Some((done, priority, description, tags)) => {
+                    let mut task = Task::new(next_task_id(tasks, last_id), description);
+                    task.done = done;
+                    task.priority = priority;
+                    task.tags = tags;
+                    task.projects = projects.iter().map(|(_, project)| project.clone()).collect();
+                    tasks.push(task);
+                    current = Some(tasks.len() - 1);
+                    count += 1;
+                },
+                None => {
+                    // Projects are single words, like in todo.txt
+                    let title = text.split_whitespace().filter(|w| !(w.starts_with(':') && w.ends_with(':'))).collect::<Vec<_>>();
+                    projects.push((level, title.join("_")));
+                },
+            }
+            continue;
+        }
+        let task = match current {
+            Some(index) => &mut tasks[index],
+            None => continue,
+        };
+        if let Some(pomodoro) = parse_clock(line) {
+            task.pomodoros.push(pomodoro);
+        } else if let Some(due) = parse_deadline(line) {
+            task.due = Some(due);
+        } else if let Some(project) = line.trim().strip_prefix(":PROJECT:") {
+            task.projects = project.split_whitespace().map(str::to_string).collect();
+        }
+    }
+    for task in tasks.iter_mut() {
+        task.pomodoros.sort_by_key(|p| p.start_time);
+    }
+    count
+}
+
+// Formats a task as an org headline with its deadline, project and clock entries
+pub fn format_task(task: &Task) -> String {
+    let mut headline = vec!["*".to_string(), if task.done { "DONE" } else { "TODO" }.to_string()];
+    if let Some(priority) = task.priority {
+        headline.push(format!("[#{}]", priority));
+    }
+    if !task.description.is_empty() {
+        headline.push(task.description.clone());
+    }
+    if !task.tags.is_empty() {
+        headline.push(format!(":{}:", task.tags.join(":")));
+    }
+    let mut lines = vec![headline.join(" ")];
+    if let Some(due) = task.due {
+        lines.push(format!("  DEADLINE: <{}>", due.format("%Y-%m-%d %a")));
+    }
+    if !task.projects.is_empty() {
+        lines.push("  :PROPERTIES:".to_string());
+        lines.push(format!("  :PROJECT: {}", task.projects.join(" ")));
+        lines.push("  :END:".to_string());
+    }
+    if !task.pomodoros.is_empty() {
+        lines.push("  :LOGBOOK:".to_string());
+        // Org lists the latest clock entry first
+        for pomodoro in task.pomodoros.iter().rev() {
+            let line = match pomodoro.end_time {
+                Some(end_time) => {
+                    let minutes = (end_time - pomodoro.start_time).num_minutes();
+                    format!("  CLOCK: {}--{} => {:2}:{:02}", format_timestamp(pomodoro.start_time), format_timestamp(end_time), minutes / 60, minutes % 60)
+                },
+                None => format!("  CLOCK: {}", format_timestamp(pomodoro.start_time)),
+            };
+            lines.push(line);
+        }
+        lines.push("  :END:".to_string());
+    }
+    lines.join("\n")
+}
+
+// Formats all active tasks as an org file
+pub fn export(tasks: &[Task]) -> String {
+    let mut content = String::new();
+    for task in tasks.iter().filter(|task| !task.archived) {
+        content.push_str(&format_task(task));
+        content.push('\n');
+    }
+    content
+}
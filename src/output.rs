@@ -0,0 +1,101 @@
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU8, Ordering};
+use std::sync::Mutex;
+
+use chrono::{DateTime, Local, Utc};
+use serde_json::{Map, Value};
+
+use crate::EXIT_ERROR;
+
+// With --json, the messages and results of a command are collected and printed as one JSON
+// object when it is done, e.g. {"ok": true, "messages": ["Task 3 checked."], "tasks": [...]}
+static JSON: AtomicBool = AtomicBool::new(false);
+static VERBOSITY: AtomicU8 = AtomicU8::new(Verbosity::Normal as u8);
+// Exit code of the first failure, 0 if nothing failed
+static EXIT_CODE: AtomicI32 = AtomicI32::new(0);
+static MESSAGES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+static VALUES: Mutex<Option<Map<String, Value>>> = Mutex::new(None);
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Verbosity {
+    // -q, the task list is not shown again after changing it
+    Quiet,
+    Normal,
+    // -v, details like the times of changed pomodoros are shown
+    Verbose,
+}
+
+pub fn init(json: bool, verbosity: Verbosity) {
+    JSON.store(json, Ordering::Relaxed);
+    VERBOSITY.store(verbosity as u8, Ordering::Relaxed);
+}
+
+pub fn json() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+pub fn quiet() -> bool {
+    VERBOSITY.load(Ordering::Relaxed) == Verbosity::Quiet as u8
+}
+
+pub fn verbose() -> bool {
+    VERBOSITY.load(Ordering::Relaxed) == Verbosity::Verbose as u8
+}
+
+// Local time of a record for the details, e.g. 2024-03-01 14:02:11
+pub fn timestamp(time: DateTime<Utc>) -> String {
+    time.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+// Prints a message about what the command did
+pub fn message(text: impl Into<String>) {
+    let text = text.into();
+    match json() {
+        true => MESSAGES.lock().unwrap().push(text),
+        // Ignores a closed stdout, e.g. pt --add ... | head
+        false => { let _ = writeln!(io::stdout(), "{}", text); },
+    }
+}
+
+// Prints a detail only shown with -v
+pub fn detail(text: impl Into<String>) {
+    if verbose() {
+        message(text);
+    }
+}
+
+// Prints a message about what went wrong, pt exits with the code and the JSON output has "ok": false
+pub fn fail(code: i32, text: impl Into<String>) {
+    let _ = EXIT_CODE.compare_exchange(0, code, Ordering::Relaxed, Ordering::Relaxed);
+    message(text);
+}
+
+pub fn error(text: impl Into<String>) {
+    fail(EXIT_ERROR, text);
+}
+
+pub fn exit_code() -> i32 {
+    EXIT_CODE.load(Ordering::Relaxed)
+}
+
+// Adds a result to the JSON output, e.g. the tasks of a listing
+pub fn set(key: &str, value: Value) {
+    VALUES.lock().unwrap().get_or_insert_with(Map::new).insert(key.to_string(), value);
+}
+
+// Prints the JSON output when the command returns, also after an error
+pub struct JsonGuard;
+
+impl Drop for JsonGuard {
+    fn drop(&mut self) {
+        if !json() {
+            return;
+        }
+        let mut output = Map::new();
+        output.insert("ok".to_string(), Value::Bool(exit_code() == 0));
+        output.insert("messages".to_string(), Value::from(MESSAGES.lock().unwrap().clone()));
+        output.extend(VALUES.lock().unwrap().take().unwrap_or_default());
+        // Also printed while unwinding a panic, so a closed stdout must not panic again
+        let _ = writeln!(io::stdout(), "{}", Value::Object(output));
+    }
+}
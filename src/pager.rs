@@ -0,0 +1,40 @@
+use std::env;
+use std::io::{self, IsTerminal, Write};
+use std::process::Stdio;
+
+use crate::shell_command;
+
+// Long listings go through a pager like in git: PT_PAGER, PAGER or less. It is only used when
+// stdout is a terminal, and PAGER=cat turns it off.
+fn pager() -> Option<String> {
+    if !io::stdout().is_terminal() {
+        return None;
+    }
+    let pager = env::var("PT_PAGER").or_else(|_| env::var("PAGER")).ok()
+        .or_else(|| (!cfg!(windows)).then(|| "less".to_string()))?;
+    match pager.trim() {
+        "" | "cat" => None,
+        pager => Some(pager.to_string()),
+    }
+}
+
+// Prints the text, through the pager if there is one
+pub fn show(text: &str) {
+    if let Some(pager) = pager() {
+        let mut command = shell_command(&pager);
+        // less quits right away if the text fits on the screen (F), keeps the colors (R) and
+        // leaves the text on the screen when it quits (X)
+        if env::var_os("LESS").is_none() {
+            command.env("LESS", "FRX");
+        }
+        if let Ok(mut child) = command.stdin(Stdio::piped()).spawn() {
+            if let Some(mut stdin) = child.stdin.take() {
+                // The pager closes stdin if it is quit before the end
+                let _ = stdin.write_all(text.as_bytes());
+            }
+            let _ = child.wait();
+            return;
+        }
+    }
+    let _ = io::stdout().write_all(text.as_bytes());
+}
@@ -0,0 +1,114 @@
+use std::env;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::config::PluginsConfig;
+use crate::event::Event;
+
+// Plugins are executables named pt-<name> on the PATH, like the external commands of git and
+// cargo. pt <name> [args] runs one as a subcommand, and the listeners of the config get the
+// events of every command.
+const PREFIX: &str = "pt-";
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata().is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+// File names a plugin may have, on Windows with one of the extensions of PATHEXT
+fn file_names(name: &str) -> Vec<String> {
+    let file_name = format!("{}{}", PREFIX, name);
+    match env::var("PATHEXT") {
+        Ok(extensions) if cfg!(windows) => extensions.split(';').map(|extension| format!("{}{}", file_name, extension.to_lowercase())).collect(),
+        _ => vec![file_name],
+    }
+}
+
+// The first executable pt-<name> on the PATH
+pub fn find(name: &str) -> Option<PathBuf> {
+    if name.is_empty() || name.contains(['/', '\\']) {
+        return None;
+    }
+    let names = file_names(name);
+    env::split_paths(&env::var_os("PATH")?)
+        .flat_map(|dir| names.iter().map(move |name| dir.join(name)))
+        .find(|path| is_executable(path))
+}
+
+// Names and paths of all plugins on the PATH, for pt --plugins
+pub fn list() -> Vec<(String, PathBuf)> {
+    let mut plugins: Vec<(String, PathBuf)> = Vec::new();
+    let Some(path) = env::var_os("PATH") else { return plugins };
+    for dir in env::split_paths(&path) {
+        let Ok(entries) = dir.read_dir() else { continue };
+        for path in entries.flatten().map(|entry| entry.path()) {
+            let file_name = if cfg!(windows) { path.file_stem() } else { path.file_name() };
+            let name = match file_name.and_then(|name| name.to_str()).and_then(|name| name.strip_prefix(PREFIX)) {
+                Some(name) => name.to_string(),
+                None => continue,
+            };
+            // Earlier directories of the PATH win, like for the commands themselves
+            if is_executable(&path) && !plugins.iter().any(|(known, _)| *known == name) {
+                plugins.push((name, path));
+            }
+        }
+    }
+    plugins.sort();
+    plugins
+}
+
+// The environment of a plugin tells it how to call pt back and which task list it works on
+fn command(path: &Path, exe: &Path, data_dir: &Path, list_name: &str) -> Command {
+    let mut command = Command::new(path);
+    command.env("PT_EXE", exe).env("PT_DATA_DIR", data_dir).env("PT_LIST", list_name);
+    command
+}
+
+// Runs a plugin as a subcommand with the terminal of pt and returns its exit code
+pub fn run(path: &Path, args: &[String], exe: &Path, data_dir: &Path, list_name: &str) -> Result<i32, String> {
+    let status = command(path, exe, data_dir, list_name)
+        .args(args)
+        .status()
+        .map_err(|e| e.to_string())?;
+    Ok(status.code().unwrap_or(1))
+}
+
+// Runs each listener with the argument events and the events as JSON lines on stdin, in the
+// format of webhooks
+pub fn notify(config: &PluginsConfig, events: &[Event], exe: &Path, data_dir: &Path, list_name: &str) {
+    if events.is_empty() {
+        return;
+    }
+    let lines: String = events.iter()
+        .map(|event| serde_json::to_string(&event.payload(list_name)).expect("Failed to serialize event.") + "\n")
+        .collect();
+    for name in &config.listeners {
+        let Some(path) = find(name) else {
+            println!("Plugin pt-{} not found on the PATH.", name);
+            continue;
+        };
+        let result = command(&path, exe, data_dir, list_name)
+            .arg("events")
+            .stdin(Stdio::piped())
+            .spawn()
+            .and_then(|mut child| {
+                if let Some(mut stdin) = child.stdin.take() {
+                    // Plugins that don't read the events close stdin early, which is fine
+                    let _ = stdin.write_all(lines.as_bytes());
+                }
+                child.wait()
+            });
+        match result {
+            Ok(status) if status.success() => (),
+            Ok(status) => println!("Plugin pt-{} failed: {}", name, status),
+            Err(e) => println!("Plugin pt-{} failed: {}", name, e),
+        }
+    }
+}
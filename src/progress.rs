@@ -0,0 +1,53 @@
+#[cfg(all(unix, not(target_os = "macos")))]
+use notify_rust::{Hint, Notification, Timeout, Urgency};
+
+#[cfg(all(unix, not(target_os = "macos")))]
+use crate::{data_dir, state, POMODORO_DURATION};
+use crate::Task;
+
+// Keeps a single notification with the running pomodoro and its remaining minutes up to date.
+// Every pt --notify replaces it instead of showing a new one, and it is closed once no pomodoro
+// runs anymore. Only Linux notification servers can replace notifications.
+#[cfg(all(unix, not(target_os = "macos")))]
+pub fn update(tasks: &[Task]) {
+    let _lock = state::lock(&data_dir());
+    let mut state = state::load_state(&data_dir());
+    let active = tasks.iter().find_map(|task| task.pomodoro_time_remaining().map(|remaining| (task, remaining)));
+    let mut notification = Notification::new();
+    notification.appname("pt").urgency(Urgency::Low).timeout(Timeout::Never);
+    if let Some(id) = state.progress_notification {
+        notification.id(id);
+    }
+    match active {
+        Some((task, remaining)) => {
+            let minutes = remaining.num_minutes() + 1;
+            let percent = 100 - 100 * remaining.num_seconds() / (POMODORO_DURATION * 60);
+            notification
+                .summary(&format!("Task {}: {}", task.id, task.description))
+                .body(&format!("{} min remaining", minutes))
+                .hint(Hint::CustomInt("value".to_string(), percent.clamp(0, 100) as i32));
+            match notification.show() {
+                Ok(handle) if state.progress_notification != Some(handle.id()) => {
+                    state.progress_notification = Some(handle.id());
+                    state::save_state(&data_dir(), &state);
+                },
+                Ok(_) => (),
+                Err(e) => println!("Failed to display the progress notification: {}", e),
+            }
+        },
+        None => {
+            if state.progress_notification.is_none() {
+                return;
+            }
+            // Closing needs a handle, which only showing the notification again returns
+            if let Ok(handle) = notification.summary("No pomodoro running").show() {
+                handle.close();
+            }
+            state.progress_notification = None;
+            state::save_state(&data_dir(), &state);
+        },
+    }
+}
+
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+pub fn update(_tasks: &[Task]) {}
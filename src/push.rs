@@ -0,0 +1,68 @@
+use serde_json::json;
+
+use crate::config::{PushConfig, PushService, TelegramConfig, Urgency};
+use crate::{http, telegram};
+use crate::notification::NotificationContent;
+
+const PUSHBULLET_URL: &str = "https://api.pushbullet.com/v2/pushes";
+
+// ntfy priorities go from 1 (min) to 5 (max), 3 is the default
+fn ntfy_priority(urgency: Urgency) -> u8 {
+    match urgency {
+        Urgency::Low => 2,
+        Urgency::Normal => 3,
+        Urgency::Critical => 5,
+    }
+}
+
+fn send_ntfy(config: &PushConfig, notification: &NotificationContent, urgency: Urgency) -> Result<(), String> {
+    let topic = config.topic.as_deref().ok_or("no ntfy topic configured")?;
+    // Published as JSON to the root of the server, headers can't hold titles with non-ASCII characters
+    let mut request = http::agent().post(config.url.trim_end_matches('/'));
+    if let Some(token) = &config.token {
+        request = request.set("Authorization", &format!("Bearer {}", token));
+    }
+    request
+        .send_json(json!({
+            "topic": topic,
+            "title": notification.title,
+            "message": notification.body,
+            "priority": ntfy_priority(urgency),
+            "tags": ["tomato"],
+        }))
+        .map(|_| ())
+        .map_err(http::error_message)
+}
+
+fn send_pushbullet(config: &PushConfig, notification: &NotificationContent) -> Result<(), String> {
+    let token = config.token.as_deref().ok_or("no Pushbullet access token configured")?;
+    http::agent().post(PUSHBULLET_URL)
+        .set("Access-Token", token)
+        .send_json(json!({ "type": "note", "title": notification.title, "body": notification.body }))
+        .map(|_| ())
+        .map_err(http::error_message)
+}
+
+fn send_telegram(config: &TelegramConfig, notification: &NotificationContent) -> Result<(), String> {
+    let (token, chat_id) = config.token.as_deref().zip(config.chat_id).ok_or("set telegram.token and telegram.chat_id")?;
+    telegram::send_message(token, chat_id, &format!("{}\n{}", notification.title, notification.body))
+}
+
+// Sends the notification to the phone if push notifications are enabled for its kind
+pub fn send(config: &PushConfig, telegram: &TelegramConfig, notification: &NotificationContent, urgency: Urgency) {
+    let service = match config.service {
+        Some(service) => service,
+        None => return,
+    };
+    if !config.events.iter().any(|name| name == notification.kind.name()) {
+        return;
+    }
+    let result = match service {
+        PushService::Ntfy => send_ntfy(config, notification, urgency),
+        PushService::Pushbullet => send_pushbullet(config, notification),
+        PushService::Telegram => send_telegram(telegram, notification),
+    };
+    if let Err(e) = result {
+        println!("Failed to send the push notification: {}", e);
+    }
+}
@@ -0,0 +1,136 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, Utc};
+
+use serde_json::Value;
+
+use crate::Task;
+use crate::backup;
+use crate::crypto;
+use crate::error::Error;
+
+/// How to recover a task file that can not be parsed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Recovery {
+    /// Replace the tasks with the latest automatic backup
+    RestoreBackup,
+    /// Keep the tasks that can still be parsed, see salvage_tasks
+    Salvage,
+}
+
+/// A task or archive file that can not be parsed, with what each Recovery would bring back
+#[derive(Debug)]
+pub struct Damage {
+    pub path: PathBuf,
+    /// Why parsing failed
+    pub message: String,
+    /// The copy of the broken file, e.g. tasks.json.corrupt-20240501-090000
+    pub copy: PathBuf,
+    /// Whether the copy was saved now or by an earlier run
+    pub copy_saved: bool,
+    /// Creation time of the backup Recovery::RestoreBackup restores, None if there is none
+    pub backup: Option<DateTime<Utc>>,
+    /// Number of tasks Recovery::Salvage keeps and of the entries it loses
+    pub salvaged: usize,
+    pub lost: usize,
+}
+
+/// Extracts all tasks that can still be parsed from a broken task file.
+/// Returns the salvaged tasks and the number of entries that were lost.
+pub fn salvage_tasks(content: &str) -> (Vec<Task>, usize) {
+    let mut tasks = Vec::new();
+    let mut lost = 0;
+
+    // Old task files are a bare array, newer ones keep the array in the "tasks" field
+    let start = if content.trim_start().starts_with('[') {
+        content.find('[')
+    } else {
+        content.find("\"tasks\"").and_then(|pos| content[pos..].find('[').map(|i| pos + i))
+    };
+    let mut rest = match start {
+        Some(start) => &content[start + 1..],
+        None => return (tasks, lost),
+    };
+
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+        if rest.is_empty() || rest.starts_with(']') {
+            break;
+        }
+        let mut stream = serde_json::Deserializer::from_str(rest).into_iter::<Value>();
+        match stream.next() {
+            Some(Ok(value)) => {
+                match serde_json::from_value::<Task>(value) {
+                    Ok(task) => tasks.push(task),
+                    Err(_) => lost += 1,
+                }
+                rest = &rest[stream.byte_offset()..];
+            },
+            _ => {
+                // Everything after the first unparseable entry is lost
+                lost += 1;
+                break;
+            }
+        }
+    }
+    (tasks, lost)
+}
+
+// A copy of the broken file that was saved before, e.g. tasks.json.corrupt-20240501-090000
+fn existing_copy(task_path: &Path, content: &str) -> Option<PathBuf> {
+    let prefix = format!("{}.corrupt-", task_path.file_name()?.to_string_lossy());
+    let dir = task_path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    fs::read_dir(dir).ok()?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with(&prefix))
+        .map(|entry| entry.path())
+        .find(|path| fs::read(path).ok().and_then(|copy| crypto::decode(copy).ok()).is_some_and(|copy| copy == content))
+}
+
+// Keeps a copy of the broken file, unless it was saved before. Returns its path and whether it was saved now.
+fn save_copy(task_path: &Path, content: &str) -> Result<(PathBuf, bool), Error> {
+    // Runs from cron or the daemon would otherwise save another copy every time
+    if let Some(corrupt_path) = existing_copy(task_path, content) {
+        return Ok((corrupt_path, false));
+    }
+    let corrupt_path = task_path.with_extension(format!("json.corrupt-{}", Utc::now().format("%Y%m%d-%H%M%S")));
+    let copy = crypto::encode(content.to_string())
+        .map_err(|e| Error::Other(format!("Failed to encrypt {}: {}", corrupt_path.display(), e)))?;
+    fs::write(&corrupt_path, copy).map_err(Error::io("save a copy of the broken file to", &corrupt_path))?;
+    Ok((corrupt_path, true))
+}
+
+/// Called when the task file can not be parsed. Keeps a copy of the broken file and describes
+/// what each way of recovering it would bring back, for the user to choose.
+pub fn inspect(task_path: &Path, content: &str, error: &str, data_dir: &Path) -> Result<Damage, Error> {
+    let (copy, copy_saved) = save_copy(task_path, content)?;
+    let (salvaged, lost) = salvage_tasks(content);
+    Ok(Damage {
+        path: task_path.to_path_buf(),
+        message: error.to_string(),
+        copy,
+        copy_saved,
+        backup: backup::latest_auto_backup(data_dir).map(|backup| backup.created),
+        salvaged: salvaged.len(),
+        lost,
+    })
+}
+
+/// Recovers the tasks of a broken task file in the chosen way. Returns them with a message about
+/// what was restored. Fails if there is no backup to restore.
+pub fn recover_tasks(task_path: &Path, content: &str, data_dir: &Path, recovery: Recovery) -> Result<(Vec<Task>, String), Error> {
+    save_copy(task_path, content)?;
+    match recovery {
+        Recovery::RestoreBackup => {
+            let backup = backup::latest_auto_backup(data_dir)
+                .ok_or_else(|| Error::Other(format!("There is no backup to restore {} from.", task_path.display())))?;
+            let tasks = backup.tasks().map_err(|e| Error::Other(format!("Failed to restore the backup: {}", e)))?;
+            Ok((tasks, format!("Backup from {} restored.", backup.created.format("%Y-%m-%d %H:%M"))))
+        },
+        Recovery::Salvage => {
+            let (tasks, _) = salvage_tasks(content);
+            let message = format!("Salvaged {} tasks.", tasks.len());
+            Ok((tasks, message))
+        },
+    }
+}
@@ -0,0 +1,46 @@
+use std::sync::OnceLock;
+
+use chrono::{DateTime, Local, NaiveDate, Utc};
+
+use crate::i18n;
+
+// The task list shows times relative to now, like "12m ago" or "in 3d", unless --absolute or
+// list.absolute_times asks for dates and times
+static ABSOLUTE: OnceLock<bool> = OnceLock::new();
+
+pub fn init(absolute: bool) {
+    ABSOLUTE.get_or_init(|| absolute);
+}
+
+pub fn absolute() -> bool {
+    ABSOLUTE.get().copied().unwrap_or(false)
+}
+
+// A past time like "just now", "12m ago", "3h ago" or "yesterday", or the date and time if absolute
+pub fn time(time: DateTime<Utc>) -> String {
+    let local = time.with_timezone(&Local);
+    if absolute() {
+        return local.format("%Y-%m-%d %H:%M").to_string();
+    }
+    let elapsed = Utc::now() - time;
+    if elapsed.num_minutes() < 1 {
+        i18n::text("time-now", &[])
+    } else if elapsed.num_hours() < 1 {
+        i18n::text("time-minutes-ago", &[("count", elapsed.num_minutes().into())])
+    } else if local.date_naive() == Local::now().date_naive() {
+        i18n::text("time-hours-ago", &[("count", elapsed.num_hours().into())])
+    } else {
+        date(local.date_naive())
+    }
+}
+
+// A day like "today", "tomorrow", "in 3d" or "2d ago"
+pub fn date(date: NaiveDate) -> String {
+    match (date - Local::now().date_naive()).num_days() {
+        0 => i18n::text("due-today", &[]),
+        1 => i18n::text("due-tomorrow", &[]),
+        -1 => i18n::text("due-yesterday", &[]),
+        days if days > 0 => i18n::text("time-in-days", &[("count", days.into())]),
+        days => i18n::text("time-days-ago", &[("count", (-days).into())]),
+    }
+}
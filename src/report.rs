@@ -0,0 +1,68 @@
+use chrono::{Duration, Local, NaiveDate, Utc};
+use serde_json::{json, Value};
+
+use crate::{Pomodoro, Task};
+
+fn on_date(pomodoro: &Pomodoro, date: NaiveDate) -> bool {
+    pomodoro.start_time.with_timezone(&Local).date_naive() == date
+}
+
+/// Tasks with pomodoros on the day, with their number and the time spent in them
+pub fn day_totals(tasks: &[Task], date: NaiveDate) -> Vec<(&Task, usize, Duration)> {
+    let mut totals = Vec::new();
+    for task in tasks {
+        let pomodoros: Vec<&Pomodoro> = task.pomodoros.iter().filter(|pomodoro| on_date(pomodoro, date)).collect();
+        if pomodoros.is_empty() {
+            continue;
+        }
+        let time = pomodoros.iter()
+            .map(|pomodoro| pomodoro.duration_at(Utc::now()))
+            .fold(Duration::zero(), |sum, time| sum + time);
+        totals.push((task, pomodoros.len(), time));
+    }
+    totals
+}
+
+pub fn open_tasks(tasks: &[Task]) -> usize {
+    tasks.iter().filter(|task| !task.done && !task.archived).count()
+}
+
+/// Plain text report of the pomodoros of a day, per task
+pub fn summary(tasks: &[Task], date: NaiveDate) -> String {
+    let mut text = format!("Pomodoros on {}\n\n", date);
+    let mut total_count = 0;
+    let mut total_time = Duration::zero();
+    for (task, count, time) in day_totals(tasks, date) {
+        let status = if task.done { "x" } else { " " };
+        text.push_str(&format!("[{}] {}: {} pomodoros, {} min\n", status, task.display_description(), count, time.num_minutes()));
+        total_count += count;
+        total_time += time;
+    }
+    if total_count == 0 {
+        text.push_str("No pomodoros.\n");
+    }
+    text.push_str(&format!("\nTotal: {} pomodoros, {} min\nOpen tasks: {}\n", total_count, total_time.num_minutes(), open_tasks(tasks)));
+    text
+}
+
+/// The same report as JSON, for pt --json report
+pub fn summary_json(tasks: &[Task], date: NaiveDate) -> Value {
+    let totals = day_totals(tasks, date);
+    let entries: Vec<Value> = totals.iter()
+        .map(|(task, count, time)| json!({
+            "id": task.id,
+            "description": task.display_description(),
+            "done": task.done,
+            "pomodoros": count,
+            "minutes": time.num_minutes(),
+        }))
+        .collect();
+    let total_time = totals.iter().fold(Duration::zero(), |sum, (_, _, time)| sum + *time);
+    json!({
+        "date": date,
+        "tasks": entries,
+        "pomodoros": totals.iter().map(|(_, count, _)| count).sum::<usize>(),
+        "minutes": total_time.num_minutes(),
+        "open_tasks": open_tasks(tasks),
+    })
+}
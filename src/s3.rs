@@ -0,0 +1,64 @@
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+use crate::config::S3Config;
+use crate::http;
+
+// Signs requests to S3 compatible object storage with AWS Signature Version 4, see
+// https://docs.aws.amazon.com/AmazonS3/latest/API/sig-v4-authenticating-requests.html
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn hmac(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length.");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+// Adds the x-amz-* and Authorization headers for a request with the given body
+pub fn sign(request: ureq::Request, config: &S3Config, payload: &[u8]) -> Result<ureq::Request, String> {
+    let access_key = config.access_key.as_deref().ok_or("sync.s3.access_key is not set")?;
+    let secret_key = config.secret_key.as_deref().ok_or("sync.s3.secret_key is not set")?;
+    let request_url = request.request_url().map_err(|e| e.to_string())?;
+    let url = request_url.as_url();
+    let host = match url.port() {
+        Some(port) => format!("{}:{}", url.host_str().unwrap_or(""), port),
+        None => url.host_str().unwrap_or("").to_string(),
+    };
+    let mut query: Vec<(String, String)> = url.query_pairs().map(|(k, v)| (http::percent_encode(&k), http::percent_encode(&v))).collect();
+    query.sort();
+    let query = query.iter().map(|(k, v)| format!("{}={}", k, v)).collect::<Vec<_>>().join("&");
+
+    let now = Utc::now();
+    let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let date = now.format("%Y%m%d").to_string();
+    let payload_hash = hex(&Sha256::digest(payload));
+    let scope = format!("{}/{}/s3/aws4_request", date, config.region);
+    let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+
+    let canonical_request = format!(
+        "{}\n{}\n{}\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+        request.method(), url.path(), query, host, payload_hash, amz_date, signed_headers, payload_hash,
+    );
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date, scope, hex(&Sha256::digest(canonical_request.as_bytes())),
+    );
+    let mut key = hmac(format!("AWS4{}", secret_key).as_bytes(), &date);
+    for part in [config.region.as_str(), "s3", "aws4_request"] {
+        key = hmac(&key, part);
+    }
+    let signature = hex(&hmac(&key, &string_to_sign));
+
+    let authorization = format!(
+        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+        access_key, scope, signed_headers, signature,
+    );
+    Ok(request
+        .set("x-amz-date", &amz_date)
+        .set("x-amz-content-sha256", &payload_hash)
+        .set("Authorization", &authorization))
+}
@@ -0,0 +1,259 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use serde::{Serialize, Deserialize};
+use serde_json::value::RawValue;
+use serde_json::{json, Value};
+
+use crate::Task;
+
+/// Version of the task file format written by this build.
+/// Version 0 is the original format, a bare array of tasks.
+pub const CURRENT_VERSION: u64 = 2;
+
+// A migration upgrades the raw JSON of a task file from version n (its index) to version n + 1.
+// When adding a field to Task, prefer #[serde(default)] and only add a migration if old data
+// needs to be converted, e.g. when a field is renamed or its meaning changes.
+const MIGRATIONS: [fn(Value) -> Value; CURRENT_VERSION as usize] = [
+    migrate_v0_to_v1,
+    migrate_v1_to_v2,
+];
+
+pub enum SchemaError {
+    // The file is not valid JSON or does not match the task schema
+    Invalid(String),
+    // The file was written by a newer version of pt
+    TooNew(u64),
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SchemaError::Invalid(e) => write!(f, "{}", e),
+            SchemaError::TooNew(version) => write!(f, "task file has schema version {}, but this version of pt only supports up to {}. Please update pt.", version, CURRENT_VERSION),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct TaskFile {
+    /// Highest task ID ever assigned, including tasks that were moved to the archive file
+    pub last_id: u32,
+    pub tasks: Vec<Task>,
+}
+
+// A task file of the current version, read in one pass
+#[derive(Deserialize)]
+struct CurrentTaskFile {
+    version: u64,
+    last_id: u32,
+    tasks: Vec<Task>,
+}
+
+#[derive(Serialize)]
+struct TaskFileRef<'a, T> {
+    version: u64,
+    last_id: u32,
+    tasks: &'a [T],
+}
+
+/// A task as it is in the file, with only its ID read. Tasks are moved to the archive file without
+/// reading the pomodoros of all tasks that are already there.
+pub struct RawTask {
+    pub id: u32,
+    json: Box<RawValue>,
+}
+
+#[derive(Deserialize)]
+struct RawTaskFile {
+    version: u64,
+    last_id: u32,
+    tasks: Vec<Box<RawValue>>,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum TaskEntry<'a> {
+    Raw(&'a RawValue),
+    Task(&'a Task),
+}
+
+// Wraps the bare task array in a versioned object
+fn migrate_v0_to_v1(value: Value) -> Value {
+    json!({
+        "version": 1,
+        "tasks": value,
+    })
+}
+
+// Adds the highest task ID, so IDs of archived tasks are not reused
+fn migrate_v1_to_v2(mut value: Value) -> Value {
+    let last_id = value["tasks"].as_array()
+        .map(|tasks| tasks.iter().filter_map(|task| task["id"].as_u64()).max().unwrap_or(0))
+        .unwrap_or(0);
+    value["version"] = json!(2);
+    value["last_id"] = json!(last_id);
+    value
+}
+
+// The version of a task file without building the JSON tree of all tasks, which takes a while
+// for years of pomodoros
+fn version_of_text(content: &str) -> Result<u64, SchemaError> {
+    #[derive(Deserialize)]
+    struct Header {
+        version: Option<u64>,
+    }
+    if content.trim_start().starts_with('[') {
+        return Ok(0);
+    }
+    let header: Header = serde_json::from_str(content).map_err(|e| SchemaError::Invalid(e.to_string()))?;
+    header.version.ok_or(SchemaError::Invalid("task file has no schema version".to_string()))
+}
+
+fn version_of(value: &Value) -> Result<u64, SchemaError> {
+    if value.is_array() {
+        return Ok(0);
+    }
+    value.get("version")
+        .and_then(Value::as_u64)
+        .ok_or(SchemaError::Invalid("task file has no schema version".to_string()))
+}
+
+/// Upgrades the raw JSON of a task file of any known version to the current version
+pub fn migrate(mut value: Value) -> Result<Value, SchemaError> {
+    let mut version = version_of(&value)?;
+    if version > CURRENT_VERSION {
+        return Err(SchemaError::TooNew(version));
+    }
+    while version < CURRENT_VERSION {
+        value = MIGRATIONS[version as usize](value);
+        version = version_of(&value)?;
+    }
+    Ok(value)
+}
+
+// What a field is expected to contain, to explain why it could not be read
+fn expectation(field: &str) -> Option<&'static str> {
+    match field {
+        "start_time" | "end_time" => Some("is not a valid RFC 3339 timestamp"),
+        "due" => Some("is not a valid date (YYYY-MM-DD)"),
+        "id" | "last_id" => Some("is not a valid task ID"),
+        "priority" => Some("is not a single letter"),
+        _ => None,
+    }
+}
+
+// Describes a deserialization error with the path of the broken field and its position in the file,
+// e.g. tasks[12].pomodoros[3].end_time is not a valid RFC 3339 timestamp (line 80, column 37)
+fn describe_error(error: serde_path_to_error::Error<serde_json::Error>) -> String {
+    let path = error.path().to_string();
+    let inner = error.into_inner();
+    let field = path.rsplit('.').next().unwrap_or("");
+    let location = match inner.line() {
+        0 => String::new(),
+        line => format!(" (line {}, column {})", line, inner.column()),
+    };
+    match expectation(field) {
+        Some(expectation) if inner.is_data() => format!("{} {}{}", path, expectation, location),
+        _ if path == "." => inner.to_string(),
+        _ => format!("{}: {}", path, inner),
+    }
+}
+
+pub fn parse_task_file(content: &str) -> Result<TaskFile, SchemaError> {
+    // Files in the current version are read straight from the text in one pass, so errors include
+    // the line. Only if that fails, the version is looked up to migrate older files.
+    let deserializer = &mut serde_json::Deserializer::from_str(content);
+    let error = match serde_path_to_error::deserialize::<_, CurrentTaskFile>(deserializer) {
+        Ok(task_file) if task_file.version == CURRENT_VERSION => return Ok(TaskFile { last_id: task_file.last_id, tasks: task_file.tasks }),
+        Ok(_) => None,
+        Err(e) => Some(e),
+    };
+    match (version_of_text(content)?, error) {
+        (version, _) if version > CURRENT_VERSION => return Err(SchemaError::TooNew(version)),
+        (CURRENT_VERSION, Some(error)) => return Err(SchemaError::Invalid(describe_error(error))),
+        _ => (),
+    }
+    let value: Value = serde_json::from_str(content).map_err(|e| SchemaError::Invalid(e.to_string()))?;
+    let value = migrate(value)?;
+    serde_path_to_error::deserialize(value).map_err(|e| SchemaError::Invalid(describe_error(e)))
+}
+
+/// Finds problems that are valid JSON but make no sense, like duplicate IDs
+pub fn check_tasks(tasks: &[Task], last_id: u32) -> Vec<String> {
+    let mut problems = Vec::new();
+    let mut ids = HashSet::new();
+    for task in tasks {
+        if !ids.insert(task.id) {
+            problems.push(format!("task ID {} is used more than once", task.id));
+        }
+        if task.id > last_id {
+            problems.push(format!("task {} has a higher ID than last_id {}", task.id, last_id));
+        }
+        for (j, pomodoro) in task.pomodoros.iter().enumerate() {
+            match pomodoro.end_time {
+                Some(end_time) if end_time < pomodoro.start_time => {
+                    problems.push(format!("pomodoro {} of task {} ends before it starts", j, task.id));
+                },
+                None if j + 1 < task.pomodoros.len() => {
+                    problems.push(format!("pomodoro {} of task {} is unfinished, but not the last one", j, task.id));
+                },
+                _ => {},
+            }
+        }
+    }
+    problems
+}
+
+/// The last ID and the tasks of a task file without parsing them, None if the file is not of the
+/// current version or not valid
+pub fn parse_raw_tasks(content: &str) -> Option<(u32, Vec<RawTask>)> {
+    #[derive(Deserialize)]
+    struct Id {
+        id: u32,
+    }
+    let task_file: RawTaskFile = serde_json::from_str(content).ok().filter(|task_file: &RawTaskFile| task_file.version == CURRENT_VERSION)?;
+    let tasks = task_file.tasks.into_iter()
+        .map(|json| serde_json::from_str::<Id>(json.get()).ok().map(|Id { id }| RawTask { id, json }))
+        .collect::<Option<Vec<RawTask>>>()?;
+    Some((task_file.last_id, tasks))
+}
+
+pub fn parse_tasks(content: &str) -> Result<Vec<Task>, SchemaError> {
+    Ok(parse_task_file(content)?.tasks)
+}
+
+pub fn serialize_task_file(tasks: &[Task], last_id: u32) -> String {
+    serialize_task_file_as(tasks, last_id, false)
+}
+
+/// Serializes the tasks without indentation if compact is set, which is smaller and faster for
+/// long histories but harder to read and diff
+pub fn serialize_task_file_as(tasks: &[Task], last_id: u32, compact: bool) -> String {
+    let task_file = TaskFileRef {
+        version: CURRENT_VERSION,
+        last_id: tasks.iter().map(|task| task.id).fold(last_id, u32::max),
+        tasks,
+    };
+    match compact {
+        true => serde_json::to_string(&task_file),
+        false => serde_json::to_string_pretty(&task_file),
+    }.expect("Failed to serialize tasks.")
+}
+
+/// Serializes the raw tasks as they were read, followed by the tasks
+pub fn serialize_task_file_with(raw: &[RawTask], tasks: &[Task], last_id: u32, compact: bool) -> String {
+    let last_id = raw.iter().map(|task| task.id).chain(tasks.iter().map(|task| task.id)).fold(last_id, u32::max);
+    let entries: Vec<TaskEntry> = raw.iter().map(|task| TaskEntry::Raw(&task.json))
+        .chain(tasks.iter().map(TaskEntry::Task))
+        .collect();
+    let task_file = TaskFileRef { version: CURRENT_VERSION, last_id, tasks: &entries };
+    match compact {
+        true => serde_json::to_string(&task_file),
+        false => serde_json::to_string_pretty(&task_file),
+    }.expect("Failed to serialize tasks.")
+}
+
+pub fn serialize_tasks(tasks: &[Task]) -> String {
+    serialize_task_file(tasks, 0)
+}
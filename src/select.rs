@@ -0,0 +1,32 @@
+use std::io::{self, IsTerminal};
+
+use dialoguer::theme::ColorfulTheme;
+use dialoguer::FuzzySelect;
+
+use crate::{output, task_line, Task};
+
+// Lets the user pick one of the tasks by typing a few letters of its description, for commands
+// run without a task ID. Returns None if nothing was picked or there is no terminal to ask on.
+pub fn task(tasks: &[Task], prompt: &str, candidate: impl Fn(&Task) -> bool) -> Option<u32> {
+    // The finder is drawn on stderr, so it also works when the output is piped
+    if output::json() || !io::stdin().is_terminal() || !io::stderr().is_terminal() {
+        return None;
+    }
+    let candidates: Vec<&Task> = tasks.iter().filter(|task| candidate(task)).collect();
+    if candidates.is_empty() {
+        return None;
+    }
+    let lines: Vec<String> = candidates.iter().map(|task| task_line(task)).collect();
+    let chosen = FuzzySelect::with_theme(&ColorfulTheme::default())
+        .with_prompt(prompt)
+        .items(&lines)
+        .default(0)
+        .interact_opt();
+    match chosen {
+        Ok(chosen) => chosen.map(|index| candidates[index].id),
+        Err(e) => {
+            println!("Failed to show the task selection: {}", e);
+            None
+        },
+    }
+}
@@ -0,0 +1,132 @@
+use std::path::Path;
+
+use serde::Deserialize;
+use subtle::ConstantTimeEq;
+use serde_json::{json, Value};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::config::ServerConfig;
+use crate::control::{self, run_pt};
+use crate::{Error, Task, EXIT_CONFLICT, EXIT_LOCKED, EXIT_NOT_FOUND, EXIT_USAGE};
+
+#[derive(Deserialize)]
+struct NewTask {
+    description: String,
+}
+
+fn error(code: u16, message: &str) -> (u16, Value) {
+    (code, json!({ "ok": false, "error": message }))
+}
+
+// The task list couldn't be read, 423 Locked while another pt holds its lock
+fn load_error(e: &Error) -> (u16, Value) {
+    match e {
+        Error::Locked(_) => (423, control::error(e)),
+        _ => (500, control::error(e)),
+    }
+}
+
+fn authorized(request: &Request, token: &Option<String>) -> bool {
+    let token = match token {
+        Some(token) => token,
+        None => return true,
+    };
+    let expected = format!("Bearer {}", token);
+    // Compared in constant time, so the time of the answer doesn't tell how much of a guess was right
+    request.headers().iter()
+        .any(|header| header.field.equiv("Authorization") && bool::from(header.value.as_str().as_bytes().ct_eq(expected.as_bytes())))
+}
+
+// Runs pt for a change and answers with a status code that tells why it failed, e.g. 409 Conflict
+// for a pomodoro that is already running
+fn change(exe: &Path, list_args: &[String], args: &[String]) -> (u16, Value) {
+    let (code, body) = run_pt(exe, list_args, args);
+    let status = match code {
+        Some(0) => 200,
+        Some(EXIT_USAGE) => 400,
+        Some(EXIT_NOT_FOUND) => 404,
+        Some(EXIT_CONFLICT) => 409,
+        Some(EXIT_LOCKED) => 423,
+        _ => 500,
+    };
+    (status, body)
+}
+
+fn add_task(request: &mut Request, exe: &Path, list_args: &[String]) -> (u16, Value) {
+    let mut body = String::new();
+    if request.as_reader().read_to_string(&mut body).is_err() {
+        return error(400, "the request body is not valid UTF-8");
+    }
+    let task = match serde_json::from_str::<NewTask>(&body) {
+        Ok(task) => task,
+        Err(e) => return error(400, &format!("invalid task: {}", e)),
+    };
+    let description = task.description.trim();
+    // pt would take it for an option
+    if description.is_empty() || description.starts_with('-') {
+        return error(400, "the description must not be empty or start with -");
+    }
+    // With --add, descriptions like "report" are not taken for a command
+    change(exe, list_args, &["--add".to_string(), description.to_string()])
+}
+
+// Routes a request to the operation for its method and path
+fn handle(request: &mut Request, load: &dyn Fn() -> Result<Vec<Task>, Error>, exe: &Path, list_args: &[String]) -> (u16, Value) {
+    let path = request.url().split('?').next().unwrap_or("").trim_end_matches('/').to_string();
+    let segments: Vec<&str> = path.split('/').skip(1).collect();
+    match (request.method(), segments.as_slice()) {
+        (Method::Get, ["status"]) => load().map_or_else(|e| load_error(&e), |tasks| (200, control::status(&tasks))),
+        (Method::Get, ["tasks"]) => load().map_or_else(|e| load_error(&e), |tasks| (200, control::list(tasks))),
+        (Method::Post, ["tasks"]) => add_task(request, exe, list_args),
+        (Method::Post, ["tasks", id, action]) => {
+            let id = match id.parse::<u32>() {
+                Ok(id) => id,
+                Err(_) => return error(404, "not found"),
+            };
+            let command = match *action {
+                "start" => "--pomodoro",
+                "finish" => "--finish-pomodoro",
+                "check" => "--check",
+                _ => return error(404, "not found"),
+            };
+            let tasks = match load() {
+                Ok(tasks) => tasks,
+                Err(e) => return load_error(&e),
+            };
+            if !tasks.iter().any(|task| task.id == id) {
+                return error(404, &format!("task {} does not exist", id));
+            }
+            change(exe, list_args, &[command.to_string(), id.to_string()])
+        },
+        _ => error(404, "not found"),
+    }
+}
+
+// Answers requests one after another until the process ends
+pub fn serve(config: &ServerConfig, port: u16, load: &dyn Fn() -> Result<Vec<Task>, Error>, exe: &Path, list_args: &[String]) {
+    let address = format!("{}:{}", config.address, port);
+    let server = match Server::http(&address) {
+        Ok(server) => server,
+        Err(e) => {
+            println!("Failed to listen on {}: {}", address, e);
+            return;
+        }
+    };
+    if config.token.is_none() && config.address != "127.0.0.1" && config.address != "localhost" {
+        println!("Warning: server.token is not set, anyone who can reach {} can change your tasks.", address);
+    }
+    println!("Serving the task list on http://{}. Press Ctrl+C to stop.", address);
+    let content_type = Header::from_bytes("Content-Type", "application/json").expect("Invalid header.");
+    for mut request in server.incoming_requests() {
+        let (code, body) = match authorized(&request, &config.token) {
+            true => handle(&mut request, load, exe, list_args),
+            false => error(401, "missing or wrong token"),
+        };
+        let response = Response::from_string(body.to_string())
+            .with_status_code(code)
+            .with_header(content_type.clone());
+        if let Err(e) = request.respond(response) {
+            println!("Failed to answer a request: {}", e);
+        }
+    }
+}
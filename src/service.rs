@@ -0,0 +1,84 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+// Systemd user units that run pt in the background, either the daemon or --notify every minute
+pub enum ServiceKind {
+    Daemon,
+    Timer,
+}
+
+const TIMER: &str = "[Unit]\nDescription=Run pt --notify every minute\n\n\
+                     [Timer]\nOnCalendar=minutely\nAccuracySec=1s\n\n\
+                     [Install]\nWantedBy=timers.target\n";
+
+// Quotes an ExecStart argument, see systemd.syntax(7)
+fn quote(arg: &str) -> String {
+    format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn systemctl(args: &[&str]) -> Result<(), String> {
+    let status = Command::new("systemctl")
+        .arg("--user")
+        .args(args)
+        .status()
+        .map_err(|e| format!("failed to run systemctl: {}", e))?;
+    if !status.success() {
+        return Err(format!("systemctl --user {} failed with {}", args.join(" "), status));
+    }
+    Ok(())
+}
+
+// Writes the units to ~/.config/systemd/user and enables them. list_args select the task list,
+// which is also part of the unit name for other lists than the default one.
+pub fn install(kind: ServiceKind, list_args: &[String], list_name: Option<&str>) -> Result<Vec<PathBuf>, String> {
+    if !cfg!(target_os = "linux") {
+        return Err("systemd units can only be installed on Linux".to_string());
+    }
+    let unit_dir = dirs::config_dir().ok_or("failed to find the config directory")?.join("systemd").join("user");
+    fs::create_dir_all(&unit_dir).map_err(|e| e.to_string())?;
+    let exe = env::current_exe().map_err(|e| format!("failed to find the pt executable: {}", e))?;
+    let name = match list_name {
+        Some(list_name) => format!("pt-{}", list_name),
+        None => "pt".to_string(),
+    };
+    let mut exec = vec![quote(&exe.to_string_lossy())];
+    exec.extend(list_args.iter().map(|arg| quote(arg)));
+
+    let mut units = Vec::new();
+    match kind {
+        ServiceKind::Daemon => {
+            exec.push("--daemon".to_string());
+            let service = format!(
+                "[Unit]\nDescription=pt pomodoro notifications\nAfter=graphical-session.target\n\n\
+                 [Service]\nExecStart={}\nRestart=on-failure\n\n\
+                 [Install]\nWantedBy=default.target\n",
+                exec.join(" "),
+            );
+            units.push((format!("{}.service", name), service));
+        },
+        ServiceKind::Timer => {
+            exec.push("--notify".to_string());
+            let service = format!(
+                "[Unit]\nDescription=pt pomodoro notifications\n\n\
+                 [Service]\nType=oneshot\nExecStart={}\n",
+                exec.join(" "),
+            );
+            units.push((format!("{}-notify.service", name), service));
+            units.push((format!("{}-notify.timer", name), TIMER.to_string()));
+        },
+    }
+
+    let mut paths = Vec::new();
+    for (file_name, content) in &units {
+        let path = unit_dir.join(file_name);
+        fs::write(&path, content).map_err(|e| format!("failed to write {}: {}", path.display(), e))?;
+        paths.push(path);
+    }
+    systemctl(&["daemon-reload"])?;
+    // The timer starts the service, so only the last unit is enabled
+    let (unit, _) = units.last().unwrap();
+    systemctl(&["enable", "--now", unit])?;
+    Ok(paths)
+}
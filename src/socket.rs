@@ -0,0 +1,102 @@
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::fs;
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::control;
+use crate::i18n;
+use crate::{Error, Task, TaskStore};
+
+// File name of the control socket in the directory of the task list
+pub const SOCKET_FILE: &str = "pt.sock";
+
+// One JSON object per line, e.g. {"command": "start", "task_id": 3}
+#[derive(Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+enum Request {
+    Start { task_id: u32 },
+    Finish { task_id: u32 },
+    Status,
+    List,
+}
+
+// Starts or finishes a pomodoro in the daemon itself. The task list is only locked for the change,
+// and the daemon notices it with its next poll.
+fn change(list_name: &str, task_id: u32, change: fn(&mut TaskStore, u32) -> Result<(), Error>, message: &str) -> Value {
+    let result = TaskStore::open(list_name, false).and_then(|mut store| {
+        change(&mut store, task_id)?;
+        store.save()?;
+        // Shown in the output of the daemon, like the messages of the pt runs it starts
+        for notice in store.take_notices() {
+            println!("{}", notice);
+        }
+        Ok(store)
+    });
+    match result {
+        Ok(store) => json!({ "ok": true, "message": i18n::text(message, &[("id", task_id.into())]), "task": store.task(task_id) }),
+        Err(e) => control::error(&e),
+    }
+}
+
+fn read(list_name: &str, answer: fn(&[Task]) -> Value) -> Value {
+    match TaskStore::open(list_name, true) {
+        Ok(store) => answer(store.tasks()),
+        Err(e) => control::error(&e),
+    }
+}
+
+fn handle(line: &str, list_name: &str) -> Value {
+    let request = match serde_json::from_str::<Request>(line) {
+        Ok(request) => request,
+        Err(e) => return json!({ "ok": false, "error": format!("invalid request: {}", e) }),
+    };
+    match request {
+        Request::Start { task_id } => change(list_name, task_id, TaskStore::start_pomodoro, "pomodoro-started"),
+        Request::Finish { task_id } => change(list_name, task_id, TaskStore::finish_pomodoro, "pomodoro-finished"),
+        Request::Status => read(list_name, control::status),
+        Request::List => read(list_name, |tasks| control::list(tasks.to_vec())),
+    }
+}
+
+fn serve_client(stream: UnixStream, list_name: &str) {
+    let mut writer = match stream.try_clone() {
+        Ok(writer) => writer,
+        Err(_) => return,
+    };
+    for line in BufReader::new(stream).lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => return,
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = handle(&line, list_name);
+        if writeln!(writer, "{}", response).is_err() {
+            return;
+        }
+    }
+}
+
+// Accepts clients one after another until the process ends
+pub fn serve(path: PathBuf, list_name: &str) {
+    if UnixStream::connect(&path).is_ok() {
+        println!("Another pt daemon is listening on {}, not starting the control socket.", path.display());
+        return;
+    }
+    // Left over from a daemon that didn't exit cleanly
+    let _ = fs::remove_file(&path);
+    let listener = match UnixListener::bind(&path) {
+        Ok(listener) => listener,
+        Err(e) => {
+            println!("Failed to create the control socket {}: {}", path.display(), e);
+            return;
+        }
+    };
+    for stream in listener.incoming().flatten() {
+        serve_client(stream, list_name);
+    }
+}
@@ -0,0 +1,228 @@
+use std::env;
+use std::fs;
+#[cfg(feature = "audio")]
+use std::io::Cursor;
+#[cfg(feature = "audio")]
+use std::panic;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::thread;
+
+#[cfg(feature = "audio")]
+use rodio::{Decoder, OutputStream, Sink};
+
+use crate::config::{self, SoundConfig};
+
+// Played if no alarm is configured or the configured one can't be played
+#[cfg(feature = "audio")]
+const DEFAULT_ALARM: &[u8] = include_bytes!("../alarm.mp3");
+// Alarm used before it could be configured, still picked up if it exists
+const LEGACY_ALARM_FILE: &str = ".pt/alarm.mp3";
+
+// Whether pt was built with the audio feature, without it the alarm is never played and only the
+// notifications are shown
+pub const AVAILABLE: bool = cfg!(feature = "audio");
+
+const DEFAULT_SPEECH_COMMAND: &str = if cfg!(target_os = "macos") { "say" } else { "espeak" };
+
+// Hidden command of the process that plays the alarm in the background
+pub const PLAY_COMMAND: &str = "--play-alarm";
+
+// Events that play a sound, each can have its own sound file
+#[derive(Clone, Copy, PartialEq)]
+pub enum SoundEvent {
+    PomodoroFinished,
+    BreakFinished,
+    DueReminder,
+}
+
+impl SoundEvent {
+    fn name(self) -> &'static str {
+        match self {
+            SoundEvent::PomodoroFinished => "pomodoro",
+            SoundEvent::BreakFinished => "break",
+            SoundEvent::DueReminder => "due",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<SoundEvent> {
+        [SoundEvent::PomodoroFinished, SoundEvent::BreakFinished, SoundEvent::DueReminder].into_iter()
+            .find(|event| event.name() == name)
+    }
+}
+
+fn alarm_path(config: &SoundConfig, event: SoundEvent) -> Option<PathBuf> {
+    let configured = match event {
+        SoundEvent::PomodoroFinished => config.alarm.as_ref(),
+        SoundEvent::BreakFinished => config.break_alarm.as_ref().or(config.alarm.as_ref()),
+        SoundEvent::DueReminder => config.due_alarm.as_ref().or(config.alarm.as_ref()),
+    };
+    match configured {
+        Some(path) => Some(config::expand_home(path)),
+        None => dirs::home_dir().map(|home| home.join(LEGACY_ALARM_FILE)).filter(|path| path.exists()),
+    }
+}
+
+// Reads and decodes the configured alarm, falling back to the bundled one
+#[cfg(feature = "audio")]
+fn alarm_source(config: &SoundConfig, event: SoundEvent) -> Decoder<Cursor<Vec<u8>>> {
+    if let Some(path) = alarm_path(config, event) {
+        match fs::read(&path).map_err(|e| e.to_string()).and_then(|data| Decoder::new(Cursor::new(data)).map_err(|e| e.to_string())) {
+            Ok(source) => return source,
+            Err(e) => println!("Failed to play {}, using the default alarm: {}", path.display(), e),
+        }
+    }
+    Decoder::new(Cursor::new(DEFAULT_ALARM.to_vec())).expect("Failed to decode the default alarm.")
+}
+
+// Name of the default audio output device if a stream can be opened on it, for pt --doctor
+#[cfg(feature = "audio")]
+pub fn output_device() -> Result<String, String> {
+    use rodio::cpal::traits::HostTrait;
+    use rodio::DeviceTrait;
+    let result = panic::catch_unwind(|| {
+        OutputStream::try_default().map_err(|e| e.to_string())?;
+        let device = rodio::cpal::default_host().default_output_device().and_then(|device| device.name().ok());
+        Ok(device.unwrap_or_else(|| "default device".to_string()))
+    });
+    result.unwrap_or_else(|_| Err("the audio device failed".to_string()))
+}
+
+#[cfg(not(feature = "audio"))]
+pub fn output_device() -> Result<String, String> {
+    Err("pt was built without the audio feature".to_string())
+}
+
+// Configured alarm files and whether they can be played, for pt --doctor. Alarms that can't be
+// played fall back to the bundled one.
+pub fn check_alarms(config: &SoundConfig) -> Vec<(PathBuf, Result<(), String>)> {
+    let mut paths: Vec<PathBuf> = Vec::new();
+    for event in [SoundEvent::PomodoroFinished, SoundEvent::BreakFinished, SoundEvent::DueReminder] {
+        if let Some(path) = alarm_path(config, event).filter(|path| !paths.contains(path)) {
+            paths.push(path);
+        }
+    }
+    paths.into_iter()
+        .map(|path| {
+            let result = fs::read(&path).map_err(|e| e.to_string()).and_then(decode);
+            (path, result)
+        })
+        .collect()
+}
+
+#[cfg(feature = "audio")]
+fn decode(data: Vec<u8>) -> Result<(), String> {
+    Decoder::new(Cursor::new(data)).map(|_| ()).map_err(|e| e.to_string())
+}
+
+#[cfg(not(feature = "audio"))]
+fn decode(_data: Vec<u8>) -> Result<(), String> {
+    Ok(())
+}
+
+// Reads the text aloud with the text-to-speech program
+pub fn speak(config: &SoundConfig, text: &str) {
+    if config.silent || !config.speak {
+        return;
+    }
+    let command = config.speech_command.as_deref().unwrap_or(DEFAULT_SPEECH_COMMAND);
+    let mut parts = command.split_whitespace();
+    let program = match parts.next() {
+        Some(program) => program,
+        None => return,
+    };
+    match Command::new(program).args(parts).arg(text).status() {
+        Ok(status) if status.success() => (),
+        Ok(status) => println!("Failed to read the notification aloud: {} failed with {}.", program, status),
+        Err(e) => println!("Failed to read the notification aloud with {}: {}", program, e),
+    }
+}
+
+// Plays the sounds of the events and then reads the texts aloud in a separate pt process, so the
+// command returns right away. Falls back to playing them here if the process can't be started.
+pub fn play_in_background(config: &SoundConfig, events: &[SoundEvent], texts: &[String]) {
+    let play = AVAILABLE && !events.is_empty() && config.volume > 0;
+    let speak = !texts.is_empty() && config.speak;
+    if config.silent || !play && !speak {
+        return;
+    }
+    let mut command = match env::current_exe() {
+        Ok(exe) => Command::new(exe),
+        Err(_) => return play_and_speak(config, events, texts),
+    };
+    // Scripts that read the output of pt must not wait for the alarm either
+    command.arg("--volume").arg(config.volume.to_string())
+        .arg(PLAY_COMMAND)
+        .args(events.iter().map(|event| event.name()))
+        .arg("--")
+        .args(texts)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    match command.spawn() {
+        // Collects the exit status, so a long running daemon doesn't leave zombie processes behind
+        Ok(mut child) => {
+            thread::spawn(move || child.wait());
+        },
+        Err(_) => play_and_speak(config, events, texts),
+    }
+}
+
+// Runs the process started by play_in_background, the arguments are the events, -- and the texts
+pub fn play_from_args(config: &SoundConfig, args: &[String]) {
+    let separator = args.iter().position(|arg| arg == "--").unwrap_or(args.len());
+    let events: Vec<SoundEvent> = args[..separator].iter().filter_map(|name| SoundEvent::from_name(name)).collect();
+    let texts = args.get(separator + 1..).unwrap_or_default();
+    play_and_speak(config, &events, texts);
+}
+
+fn play_and_speak(config: &SoundConfig, events: &[SoundEvent], texts: &[String]) {
+    if !events.is_empty() {
+        play_alarm(config, events);
+    }
+    for text in texts {
+        speak(config, text);
+    }
+}
+
+// Plays the sounds of the events one after another and waits until they have finished
+#[cfg(feature = "audio")]
+pub fn play_alarm(config: &SoundConfig, events: &[SoundEvent]) {
+    if config.silent || config.volume == 0 {
+        return;
+    }
+    // Some audio backends panic instead of returning an error, e.g. when the device disappears
+    if panic::catch_unwind(|| play(config, events)).is_err() {
+        println!("Failed to play the alarm: the audio device failed.");
+    }
+}
+
+#[cfg(feature = "audio")]
+fn play(config: &SoundConfig, events: &[SoundEvent]) {
+    // Get a output stream handle to the default physical sound device
+    let (_stream, stream_handle) = match OutputStream::try_default() {
+        Ok(output) => output,
+        Err(e) => {
+            println!("Failed to play the alarm: {}", e);
+            return;
+        }
+    };
+    let sink = match Sink::try_new(&stream_handle) {
+        Ok(sink) => sink,
+        Err(e) => {
+            println!("Failed to play the alarm: {}", e);
+            return;
+        }
+    };
+    sink.set_volume(config.volume as f32 / 100.0);
+    for event in events {
+        sink.append(alarm_source(config, *event));
+    }
+
+    // The sound plays in a separate thread. This call will block the current thread until the sink
+    // has finished playing all its queued sounds.
+    sink.sleep_until_end();
+}
+
+#[cfg(not(feature = "audio"))]
+pub fn play_alarm(_config: &SoundConfig, _events: &[SoundEvent]) {}
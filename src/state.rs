@@ -0,0 +1,103 @@
+use std::fs::{self, File};
+use std::path::Path;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use fs2::FileExt;
+use serde::{Serialize, Deserialize};
+
+// Runtime state that is not part of any task list, like a running break
+const STATE_FILE: &str = "state.json";
+const LOCK_FILE: &str = "state.lock";
+
+// Alert of a finished pomodoro, kept so it can be snoozed
+#[derive(Clone)]
+#[derive(Serialize)]
+#[derive(Deserialize)]
+pub struct Alert {
+    pub task_id: u32,
+    pub description: String,
+}
+
+#[derive(Serialize)]
+#[derive(Deserialize)]
+pub struct Snooze {
+    pub alert: Alert,
+    pub until: DateTime<Utc>,
+}
+
+// A due date reminder that was shown, so it is only shown once a day
+#[derive(Serialize)]
+#[derive(Deserialize)]
+pub struct DueReminder {
+    pub list: String,
+    pub task_id: u32,
+    pub date: NaiveDate,
+}
+
+// A notification that was shown recently
+#[derive(Serialize)]
+#[derive(Deserialize)]
+pub struct ShownNotification {
+    pub title: String,
+    pub body: String,
+    pub time: DateTime<Utc>,
+}
+
+// Google Calendar event that blocks the time of a running pomodoro
+#[derive(Serialize)]
+#[derive(Deserialize)]
+pub struct CalendarEvent {
+    pub list: String,
+    pub task_id: u32,
+    pub start_time: DateTime<Utc>,
+    pub event_id: String,
+}
+
+#[derive(Default)]
+#[derive(Serialize)]
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct State {
+    pub break_end: Option<DateTime<Utc>>,
+    // Most recent finished pomodoro alert, the one --snooze delays
+    pub last_alert: Option<Alert>,
+    // Alerts that are delivered again by --notify once their time has come
+    pub snoozed: Vec<Snooze>,
+    // Alert that is repeated until it is acknowledged, with the time of the next repetition
+    pub nag: Option<Snooze>,
+    // Due date reminders shown today
+    pub due_reminders: Vec<DueReminder>,
+    // ID of the notification that shows the progress of the running pomodoro
+    pub progress_notification: Option<u32>,
+    // Notifications shown within notification.repeat_seconds, identical ones are not shown again
+    pub shown: Vec<ShownNotification>,
+    // Start times of the running pomodoros pt --email check already reported
+    pub emailed_pomodoros: Vec<DateTime<Utc>>,
+    // Focus blocks of running pomodoros, updated when they are finished early
+    pub calendar_events: Vec<CalendarEvent>,
+    // MPRIS players pt paused for the running pomodoros, resumed on the next break
+    pub paused_players: Vec<String>,
+}
+
+// Keeps other pt processes from changing the state until the returned file is dropped, e.g. when
+// several --notify run at once. None if the lock file can't be opened.
+pub fn lock(data_dir: &Path) -> Option<File> {
+    let file = File::create(data_dir.join(LOCK_FILE)).ok()?;
+    FileExt::lock_exclusive(&file).ok()?;
+    Some(file)
+}
+
+// A missing or unreadable state file is treated as empty, it only holds short-lived information
+pub fn load_state(data_dir: &Path) -> State {
+    fs::read_to_string(data_dir.join(STATE_FILE))
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+pub fn save_state(data_dir: &Path, state: &State) {
+    let content = serde_json::to_string_pretty(state).expect("Failed to serialize state.");
+    if let Err(e) = fs::write(data_dir.join(STATE_FILE), content) {
+        println!("Failed to write {}: {}", data_dir.join(STATE_FILE).display(), e);
+    }
+}
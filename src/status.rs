@@ -0,0 +1,127 @@
+use chrono::{Duration, Utc};
+use serde_json::{json, Value};
+
+use crate::state::State;
+use crate::{Task, POMODORO_DURATION};
+
+#[derive(Clone, Copy)]
+pub enum Format {
+    Plain,
+    Json,
+    // Custom module of waybar, also understood by polybar scripts
+    Waybar,
+    // Short and colored with tmux style codes, empty while nothing runs
+    Tmux,
+    // Block of i3blocks with format=json
+    I3blocks,
+}
+
+impl Format {
+    pub fn parse(name: &str) -> Option<Format> {
+        match name {
+            "plain" => Some(Format::Plain),
+            "json" => Some(Format::Json),
+            "waybar" => Some(Format::Waybar),
+            "tmux" => Some(Format::Tmux),
+            "i3blocks" => Some(Format::I3blocks),
+            _ => None,
+        }
+    }
+}
+
+// What the status line is about: the pomodoro that ends first, or else a running break
+pub enum Activity<'a> {
+    Pomodoro(&'a Task, Duration),
+    Break(Duration),
+    Idle,
+}
+
+pub fn activity<'a>(tasks: &'a [Task], state: &State) -> Activity<'a> {
+    let pomodoro = tasks.iter()
+        .filter_map(|task| task.pomodoro_time_remaining().map(|remaining| (task, remaining)))
+        .min_by_key(|(_, remaining)| *remaining);
+    if let Some((task, remaining)) = pomodoro {
+        return Activity::Pomodoro(task, remaining.max(Duration::zero()));
+    }
+    match state.break_end.map(|end| end - Utc::now()) {
+        Some(remaining) if remaining > Duration::zero() => Activity::Break(remaining),
+        _ => Activity::Idle,
+    }
+}
+
+fn clock(remaining: Duration) -> String {
+    format!("{:0>2}:{:0>2}", remaining.num_minutes(), remaining.num_seconds() % 60)
+}
+
+// Minutes left of the pomodoro that ends first, rounded up, e.g. 🍅12m
+pub fn prompt(tasks: &[Task]) -> Option<String> {
+    let remaining = tasks.iter().filter_map(|task| task.pomodoro_time_remaining()).min()?;
+    let minutes = (remaining.num_seconds().max(0) + 59) / 60;
+    Some(format!("🍅{}m", minutes))
+}
+
+fn json_status(activity: Activity, pomodoros_today: usize) -> Value {
+    let (state, task, remaining) = match activity {
+        Activity::Pomodoro(task, remaining) => ("pomodoro", Some(task), Some(remaining)),
+        Activity::Break(remaining) => ("break", None, Some(remaining)),
+        Activity::Idle => ("idle", None, None),
+    };
+    json!({
+        "state": state,
+        "task": task,
+        "remaining_seconds": remaining.map(|remaining| remaining.num_seconds()),
+        "pomodoros_today": pomodoros_today,
+    })
+}
+
+// The status in the JSON format, for pt --json --status
+pub fn json(tasks: &[Task], state: &State, pomodoros_today: usize) -> Value {
+    json_status(activity(tasks, state), pomodoros_today)
+}
+
+// Renders the status as a single line, polled by status bars
+pub fn render(tasks: &[Task], state: &State, pomodoros_today: usize, format: Format) -> String {
+    let activity = activity(tasks, state);
+    match format {
+        Format::Plain => match activity {
+            Activity::Pomodoro(task, remaining) => format!("{} {} ({} today)", clock(remaining), task.description, pomodoros_today),
+            Activity::Break(remaining) => format!("Break {} ({} today)", clock(remaining), pomodoros_today),
+            Activity::Idle => format!("No pomodoro ({} today)", pomodoros_today),
+        },
+        Format::Json => json_status(activity, pomodoros_today).to_string(),
+        Format::Waybar => {
+            let (text, tooltip, class, percentage) = match activity {
+                Activity::Pomodoro(task, remaining) => {
+                    let elapsed = Duration::minutes(POMODORO_DURATION) - remaining;
+                    let percentage = elapsed.num_seconds() * 100 / (POMODORO_DURATION * 60);
+                    (format!("{} {}", clock(remaining), task.description), format!("Pomodoro on task {}", task.id), "pomodoro", percentage)
+                },
+                Activity::Break(remaining) => (format!("Break {}", clock(remaining)), "Break".to_string(), "break", 0),
+                Activity::Idle => (String::new(), "No pomodoro running".to_string(), "idle", 0),
+            };
+            json!({
+                "text": text,
+                "tooltip": format!("{}, {} pomodoros today", tooltip, pomodoros_today),
+                "class": class,
+                "percentage": percentage,
+            }).to_string()
+        },
+        Format::Tmux => match activity {
+            Activity::Pomodoro(_, remaining) => format!("#[fg=red]🍅 {}#[default]", clock(remaining)),
+            Activity::Break(remaining) => format!("#[fg=green]☕ {}#[default]", clock(remaining)),
+            Activity::Idle => String::new(),
+        },
+        Format::I3blocks => {
+            let (full_text, short_text, color) = match activity {
+                Activity::Pomodoro(task, remaining) => (format!("🍅 {} {}", clock(remaining), task.description), format!("🍅 {}", clock(remaining)), Some("#ff5555")),
+                Activity::Break(remaining) => (format!("☕ {}", clock(remaining)), format!("☕ {}", clock(remaining)), Some("#50fa7b")),
+                Activity::Idle => (format!("🍅 {} today", pomodoros_today), "🍅".to_string(), None),
+            };
+            let mut block = json!({ "full_text": full_text, "short_text": short_text });
+            if let Some(color) = color {
+                block["color"] = json!(color);
+            }
+            block.to_string()
+        },
+    }
+}
@@ -0,0 +1,696 @@
+use std::fs::{self, OpenOptions, File};
+use std::io::{self, Read, Write, Seek, SeekFrom};
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use fs2::FileExt;
+use same_file::Handle;
+use serde::{Deserialize, Serialize};
+
+use crate::{next_task_id, Task};
+use crate::config::{Config, StorageMode};
+use crate::crypto;
+use crate::error::Error;
+use crate::journal::{self, JournalEvent};
+use crate::recovery::{self, Recovery};
+use crate::schema;
+
+pub const TASK_FILE: &str = "tasks.json";
+pub const ARCHIVE_FILE: &str = "archive.json";
+pub const JOURNAL_FILE: &str = "journal.jsonl";
+/// Write-ahead log of the JSON storage mode, only there while a save is in progress
+pub const WAL_FILE: &str = "tasks.wal";
+/// Remembers the state of the last sync of a task list, to tell local from remote changes
+pub const SYNC_STATE_FILE: &str = "sync.json";
+// Named task lists are kept in subdirectories of this directory, the default list in the data directory itself
+const LISTS_DIR: &str = "lists";
+pub const DEFAULT_LIST: &str = "default";
+/// Task file of a project, found in the current directory or one of its parents
+pub const PROJECT_FILE: &str = ".pt.json";
+pub const PROJECT_ARCHIVE_FILE: &str = ".pt.archive.json";
+
+/// Returns the directory holding the files of the given task list
+pub fn list_dir(data_dir: &Path, list_name: &str) -> PathBuf {
+    if list_name == DEFAULT_LIST {
+        data_dir.to_path_buf()
+    } else {
+        data_dir.join(LISTS_DIR).join(list_name)
+    }
+}
+
+pub fn valid_list_name(list_name: &str) -> bool {
+    !list_name.is_empty() && list_name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Checks a task file or journal without modifying it. Returns the number of tasks and the problems found.
+pub fn validate_file(path: &Path) -> Result<(usize, Vec<String>), String> {
+    let content = fs::read(path).map_err(|e| e.to_string())?;
+    let content = crypto::decode(content)?;
+    if content.trim().is_empty() {
+        return Ok((0, Vec::new()));
+    }
+    if path.extension().is_some_and(|extension| extension == "jsonl") {
+        let tasks = journal::replay(&content)?;
+        let last_id = tasks.iter().map(|task| task.id).max().unwrap_or(0);
+        return Ok((tasks.len(), schema::check_tasks(&tasks, last_id)));
+    }
+    let task_file = schema::parse_task_file(&content).map_err(|e| e.to_string())?;
+    Ok((task_file.tasks.len(), schema::check_tasks(&task_file.tasks, task_file.last_id)))
+}
+
+/// Searches the directory and its parents for a project task file, like git does for repositories
+pub fn find_project_file(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|dir| dir.join(PROJECT_FILE))
+        .find(|path| path.is_file())
+}
+
+/// Names of all task lists, starting with the default list
+pub fn list_names(data_dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = match fs::read_dir(data_dir.join(LISTS_DIR)) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().is_dir())
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    names.sort();
+    names.insert(0, DEFAULT_LIST.to_string());
+    names
+}
+
+/// The locked file holding the tasks, either tasks.json or the journal depending on the storage mode
+pub struct Storage {
+    mode: StorageMode,
+    // Directory of the task list, see list_dir
+    data_dir: PathBuf,
+    path: PathBuf,
+    archive_path: PathBuf,
+    // Missing if the task list was opened read-only and the file does not exist yet
+    file: Option<File>,
+    // Read-only storage is not locked exclusively and never written
+    read_only: bool,
+    // Tasks as last loaded or saved, used to compute the journal entries on save
+    saved: Vec<Task>,
+    // Whether the archived tasks were loaded from the archive file
+    archive_loaded: bool,
+    last_id: u32,
+    lock_timeout: Duration,
+    // Write the JSON files without indentation
+    compact: bool,
+    // Log each save to the write-ahead log before writing the files
+    write_ahead_log: bool,
+    // Modification time and size of the file as last loaded or saved
+    stamp: Option<(SystemTime, u64)>,
+    // How the next load recovers a broken task or archive file, see recover_with
+    recovery: Option<Recovery>,
+    // Messages for the result of the current load or save
+    notices: Vec<String>,
+}
+
+/// The tasks of Storage::load, with messages about what loading did to the files, e.g. that it
+/// finished an interrupted save
+pub struct Loaded {
+    pub tasks: Vec<Task>,
+    pub notices: Vec<String>,
+}
+
+/// The changes of Storage::save, with messages about how they were applied, e.g. to a task file
+/// another program changed
+pub struct Saved {
+    pub changes: Vec<JournalEvent>,
+    pub notices: Vec<String>,
+}
+
+// A save as it is logged before the files are written, with the encoded content of the files
+#[derive(Serialize, Deserialize)]
+struct WalEntry {
+    // Descriptions of the changes, empty for encrypted task lists
+    changes: Vec<String>,
+    tasks: String,
+    archive: Option<String>,
+}
+
+/// Time to wait for a lock held by another process unless --wait is given
+pub const DEFAULT_LOCK_TIMEOUT: Duration = Duration::from_secs(10);
+
+fn is_contended(error: &io::Error) -> bool {
+    error.raw_os_error() == fs2::lock_contended_error().raw_os_error()
+}
+
+// Retries taking the lock with increasing delays until the timeout expires
+fn lock_with_timeout(file: &File, path: &Path, shared: bool, timeout: Duration) -> Result<(), Error> {
+    let deadline = Instant::now() + timeout;
+    let mut delay = Duration::from_millis(50);
+    loop {
+        let result = if shared { FileExt::try_lock_shared(file) } else { FileExt::try_lock_exclusive(file) };
+        match result {
+            Err(e) if is_contended(&e) => {
+                let now = Instant::now();
+                if now >= deadline {
+                    return Err(Error::Locked(path.to_path_buf()));
+                }
+                thread::sleep(delay.min(deadline - now));
+                delay = (delay * 2).min(Duration::from_secs(1));
+            },
+            result => return result.map_err(Error::io("lock", path)),
+        }
+    }
+}
+
+/// Whether another process holds the lock of the file, e.g. a pt command that waits for input.
+/// A missing file is not locked.
+pub fn is_locked(path: &Path) -> Result<bool, Error> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(Error::io("open", path)(e)),
+    };
+    match FileExt::try_lock_shared(&file) {
+        Ok(()) => {
+            let _ = FileExt::unlock(&file);
+            Ok(false)
+        },
+        Err(e) if is_contended(&e) => Ok(true),
+        Err(e) => Err(Error::io("lock", path)(e)),
+    }
+}
+
+fn open_locked(path: &Path, timeout: Duration) -> Result<File, Error> {
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)
+        .map_err(Error::io("open", path))?;
+
+    lock_with_timeout(&file, path, false, timeout)?;
+
+    Ok(file)
+}
+
+// Opens the file for reading with a shared lock. The lock is skipped on file systems that don't support it.
+fn open_shared(path: &Path, timeout: Duration) -> Result<Option<File>, Error> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(Error::io("open", path)(e)),
+    };
+    if let Err(Error::Locked(path)) = lock_with_timeout(&file, path, true, timeout) {
+        return Err(Error::Locked(path));
+    }
+    Ok(Some(file))
+}
+
+// Whether the open file is still the one at the path, or another program replaced it
+fn same_file(file: &File, path: &Path) -> bool {
+    let open = file.try_clone().and_then(Handle::from_file);
+    matches!((open, Handle::from_path(path)), (Ok(open), Ok(current)) if open == current)
+}
+
+// Used to notice changes by programs that ignore the lock, like file sync tools and editors
+fn file_stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+fn is_read_only_file(path: &Path) -> bool {
+    fs::metadata(path).is_ok_and(|metadata| metadata.permissions().readonly())
+}
+
+/// File the tasks of a task list are kept in, depending on the storage mode
+pub fn task_path(data_dir: &Path, mode: StorageMode) -> PathBuf {
+    match mode {
+        StorageMode::Json => data_dir.join(TASK_FILE),
+        StorageMode::Journal => data_dir.join(JOURNAL_FILE),
+    }
+}
+
+/// Reads the tasks without locking, e.g. for a shell prompt that must never wait. Returns None if
+/// the file is encrypted, missing or just being written.
+pub fn peek(path: &Path, mode: StorageMode) -> Option<Vec<Task>> {
+    let content = fs::read(path).ok()?;
+    if crypto::is_encrypted(&content) {
+        return None;
+    }
+    let content = String::from_utf8(content).ok()?;
+    match mode {
+        StorageMode::Json => schema::parse_task_file(&content).ok().map(|task_file| task_file.tasks),
+        StorageMode::Journal => journal::replay(&content).ok(),
+    }
+}
+
+// Decrypts the content if necessary
+fn decode(content: Vec<u8>, path: &Path) -> Result<String, Error> {
+    crypto::decode(content).map_err(|e| Error::Other(format!("Failed to read {}: {}", path.display(), e)))
+}
+
+fn encode(content: String, path: &Path) -> Result<String, Error> {
+    crypto::encode(content).map_err(|e| Error::Other(format!("Failed to encrypt {}: {}", path.display(), e)))
+}
+
+fn read_content(file: &mut Option<File>, path: &Path) -> Result<String, Error> {
+    let file = match file {
+        Some(file) => file,
+        None => return Ok(String::new()),
+    };
+    let mut content = Vec::new();
+    file.seek(SeekFrom::Start(0))
+        .and_then(|_| file.read_to_end(&mut content))
+        .map_err(Error::io("read", path))?;
+    decode(content, path)
+}
+
+// Error for a file that fails to parse, what is e.g. "task file"
+fn schema_error(error: schema::SchemaError, what: &'static str, path: &Path) -> Error {
+    match error {
+        schema::SchemaError::Invalid(message) => Error::Corrupt { what, path: path.to_path_buf(), message },
+        error => Error::Other(error.to_string()),
+    }
+}
+
+impl Storage {
+    /// Opens the task list. It is opened read-only if requested or if the task file is not writable.
+    pub fn open(data_dir: &Path, config: &Config, read_only: bool, lock_timeout: Duration) -> Result<Storage, Error> {
+        // Every journal entry would need its own slow passphrase derivation
+        if config.storage == StorageMode::Journal && config.encryption.enabled {
+            return Err(Error::Other("Encryption is not supported with the journal storage mode.".to_string()));
+        }
+        let path = task_path(data_dir, config.storage);
+        let mut storage = Storage::open_files(config.storage, data_dir, path, data_dir.join(ARCHIVE_FILE), read_only, lock_timeout)?;
+        storage.compact = config.compact_json;
+        storage.write_ahead_log = config.write_ahead_log;
+        Ok(storage)
+    }
+
+    /// Opens a project task file. Projects always use the JSON storage mode, so the file can be committed.
+    pub fn open_project(path: &Path, read_only: bool, lock_timeout: Duration) -> Result<Storage, Error> {
+        let dir = path.parent().unwrap_or(Path::new("."));
+        Storage::open_files(StorageMode::Json, dir, path.to_path_buf(), dir.join(PROJECT_ARCHIVE_FILE), read_only, lock_timeout)
+    }
+
+    fn open_files(mode: StorageMode, data_dir: &Path, path: PathBuf, archive_path: PathBuf, read_only: bool, lock_timeout: Duration) -> Result<Storage, Error> {
+        let read_only = read_only || is_read_only_file(&path);
+        let file = if read_only {
+            open_shared(&path, lock_timeout)?
+        } else {
+            fs::create_dir_all(data_dir).map_err(Error::io("create directory", data_dir))?;
+            Some(open_locked(&path, lock_timeout)?)
+        };
+        Ok(Storage {
+            mode,
+            data_dir: data_dir.to_path_buf(),
+            path,
+            archive_path,
+            file,
+            read_only,
+            saved: Vec::new(),
+            archive_loaded: false,
+            last_id: 0,
+            lock_timeout,
+            compact: false,
+            write_ahead_log: false,
+            stamp: None,
+            recovery: None,
+            notices: Vec::new(),
+        })
+    }
+
+    /// Loads the tasks. In JSON mode, archived tasks are kept in a separate file and only loaded
+    /// if include_archive is set. A task or archive file that can't be parsed fails with
+    /// Error::Recoverable, unless a Recovery was chosen with recover_with.
+    pub fn load(&mut self, include_archive: bool) -> Result<Loaded, Error> {
+        let tasks = self.load_tasks(include_archive);
+        let notices = mem::take(&mut self.notices);
+        tasks.map(|tasks| Loaded { tasks, notices })
+    }
+
+    /// Makes the next load recover a broken task or archive file in the chosen way, e.g. after
+    /// load failed with Error::Recoverable. The broken file is replaced right away.
+    pub fn recover_with(&mut self, recovery: Recovery) {
+        self.recovery = Some(recovery);
+    }
+
+    fn load_tasks(&mut self, include_archive: bool) -> Result<Vec<Task>, Error> {
+        let tasks = match self.mode {
+            StorageMode::Json => {
+                let mut tasks = self.load_json()?;
+                if include_archive {
+                    tasks.extend(self.load_archive()?);
+                    tasks.sort_by_key(|task| task.id);
+                    self.archive_loaded = true;
+                }
+                tasks
+            },
+            StorageMode::Journal => self.load_journal()?,
+        };
+        self.saved = tasks.clone();
+        self.last_id = tasks.iter().map(|task| task.id).fold(self.last_id, u32::max);
+        self.stamp = file_stamp(&self.path);
+        Ok(tasks)
+    }
+
+    /// Whether another program modified the file since it was loaded or saved
+    pub fn changed_on_disk(&self) -> bool {
+        file_stamp(&self.path) != self.stamp
+    }
+
+    /// Loads the tasks again, e.g. after another program changed the file. The file is reopened,
+    /// as it may have been replaced.
+    pub fn reload(&mut self) -> Result<Loaded, Error> {
+        let tasks = self.reopen();
+        let notices = mem::take(&mut self.notices);
+        tasks.map(|tasks| Loaded { tasks, notices })
+    }
+
+    fn reopen(&mut self) -> Result<Vec<Task>, Error> {
+        if !self.read_only {
+            // A file that was replaced is locked before the lock of the old one is released, so no
+            // other process can take it in between. The old handle is kept if the file is still the
+            // same, as flock locks of the same process conflict too.
+            if !self.file.as_ref().is_some_and(|file| same_file(file, &self.path)) {
+                let file = open_locked(&self.path, self.lock_timeout)?;
+                self.file = Some(file);
+            }
+        } else {
+            self.file = open_shared(&self.path, self.lock_timeout)?;
+        }
+        self.last_id = 0;
+        self.load_tasks(self.archive_loaded)
+    }
+
+    pub fn read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Highest task ID ever assigned, including archived tasks that were not loaded
+    pub fn last_id(&self) -> u32 {
+        self.last_id
+    }
+
+    /// Describes what save would write, for --dry-run
+    pub fn preview(&self, tasks: &[Task]) -> Vec<String> {
+        journal::describe_diff(&self.saved, tasks)
+    }
+
+    /// Writes the tasks and returns the changes since they were loaded or last saved.
+    /// If another program changed the task file in the meantime, the changes are applied to its
+    /// version instead of overwriting it, and the tasks are updated accordingly.
+    pub fn save(&mut self, tasks: &mut Vec<Task>) -> Result<Saved, Error> {
+        let changes = self.save_tasks(tasks);
+        let notices = mem::take(&mut self.notices);
+        changes.map(|changes| Saved { changes, notices })
+    }
+
+    fn save_tasks(&mut self, tasks: &mut Vec<Task>) -> Result<Vec<JournalEvent>, Error> {
+        let mut changes = journal::diff(&self.saved, tasks);
+        if self.read_only {
+            if !changes.is_empty() {
+                self.notices.push("The task list is read-only, changes were not saved.".to_string());
+            }
+            return Ok(Vec::new());
+        }
+        // Commands that change nothing leave the file alone, e.g. when it is listed every second
+        if changes.is_empty() {
+            return Ok(Vec::new());
+        }
+        if self.mode == StorageMode::Json && self.changed_on_disk() {
+            self.notices.push(format!("The task file {} was changed by another program, applying the changes to the new version.", self.path.display()));
+            let mut current = self.reopen()?;
+            for event in changes {
+                // Tasks added on both sides get different IDs
+                let event = match event {
+                    JournalEvent::TaskAdded { mut task } if current.iter().any(|t| t.id == task.id) => {
+                        let id = next_task_id(&current, self.last_id);
+                        self.notices.push(format!("Task {} was renumbered to {}.", task.id, id));
+                        task.id = id;
+                        JournalEvent::TaskAdded { task }
+                    },
+                    event => event,
+                };
+                let description = event.describe();
+                if let Err(e) = journal::apply(&mut current, event) {
+                    self.notices.push(format!("Skipped change \"{}\": {}", description, e));
+                }
+            }
+            *tasks = current;
+            changes = journal::diff(&self.saved, tasks);
+        }
+        self.last_id = tasks.iter().map(|task| task.id).fold(self.last_id, u32::max);
+        // A broken state is not written, so it doesn't spread to backups, syncs and the archive.
+        // Problems the file already had are left to pt --validate.
+        let existing = schema::check_tasks(&self.saved, self.last_id);
+        let problems: Vec<String> = schema::check_tasks(tasks, self.last_id).into_iter()
+            .filter(|problem| !existing.contains(problem))
+            .collect();
+        if !problems.is_empty() {
+            return Err(Error::Invalid(problems));
+        }
+        match self.mode {
+            StorageMode::Json => {
+                let (archived, active): (Vec<Task>, Vec<Task>) = tasks.iter().cloned().partition(|task| task.archived);
+                let archive = match self.archive_loaded || !archived.is_empty() {
+                    true => Some(self.archive_content(archived)?),
+                    false => None,
+                };
+                let content = encode(schema::serialize_task_file_as(&active, self.last_id, self.compact), &self.path)?;
+                // The files are only written once the log holds everything needed to finish the save
+                if self.write_ahead_log {
+                    // The descriptions would reveal the tasks of an encrypted task list
+                    let changes = match crypto::enabled() {
+                        true => Vec::new(),
+                        false => journal::describe_diff(&self.saved, tasks),
+                    };
+                    self.write_wal(&WalEntry { changes, tasks: content.clone(), archive: archive.clone() })?;
+                }
+                if let Some(archive) = archive {
+                    self.write_archive(&archive)?;
+                }
+                self.write_json(&content)?;
+                if self.write_ahead_log {
+                    fs::remove_file(self.wal_path()).map_err(Error::io("remove", &self.wal_path()))?;
+                }
+            },
+            StorageMode::Journal => self.append_journal(&changes)?,
+        }
+        self.saved = tasks.to_vec();
+        self.stamp = file_stamp(&self.path);
+        Ok(changes)
+    }
+
+    fn load_json(&mut self) -> Result<Vec<Task>, Error> {
+        // Read-only commands see the result of an interrupted save without finishing it
+        let content = match self.read_only {
+            true => match self.logged_save()? {
+                Some(entry) => decode(entry.tasks.into_bytes(), &self.path)?,
+                None => read_content(&mut self.file, &self.path)?,
+            },
+            false => {
+                self.replay_wal()?;
+                read_content(&mut self.file, &self.path)?
+            },
+        };
+        if content.is_empty() {
+            return Ok(Vec::new());
+        }
+        match schema::parse_task_file(&content) {
+            Ok(task_file) => {
+                self.last_id = task_file.last_id;
+                Ok(task_file.tasks)
+            },
+            Err(schema::SchemaError::Invalid(e)) if !self.read_only => {
+                let tasks = self.recover(&self.path.clone(), &content, &e)?;
+                let tasks: Vec<Task> = tasks.into_iter().filter(|task| !task.archived).collect();
+                // Replace the broken file right away, even if the command doesn't modify any task
+                self.save_json(&tasks)?;
+                Ok(tasks)
+            },
+            Err(e) => Err(schema_error(e, "task file", &self.path)),
+        }
+    }
+
+    fn save_json(&mut self, tasks: &[Task]) -> Result<(), Error> {
+        let content = encode(schema::serialize_task_file_as(tasks, self.last_id, self.compact), &self.path)?;
+        self.write_json(&content)
+    }
+
+    // Overwrites the task file in place, as replacing it would lose the lock
+    fn write_json(&self, content: &str) -> Result<(), Error> {
+        let mut file = self.writable_file()?;
+        file.set_len(0)
+            .and_then(|_| file.seek(SeekFrom::Start(0)))
+            .and_then(|_| file.write_all(content.as_bytes()))
+            .and_then(|_| match self.write_ahead_log {
+                true => file.sync_data(),
+                false => Ok(()),
+            })
+            .map_err(Error::io("write", &self.path))
+    }
+
+    // Read-only storage is never saved, so writable storage always has its file open
+    fn writable_file(&self) -> Result<&File, Error> {
+        self.file.as_ref().ok_or_else(|| Error::Other(format!("The task file {} is not open for writing.", self.path.display())))
+    }
+
+    fn wal_path(&self) -> PathBuf {
+        self.path.with_file_name(WAL_FILE)
+    }
+
+    fn write_wal(&self, entry: &WalEntry) -> Result<(), Error> {
+        let path = self.wal_path();
+        let content = serde_json::to_string(entry).expect("Failed to serialize the write-ahead log.") + "\n";
+        File::create(&path)
+            .and_then(|mut file| file.write_all(content.as_bytes()).and_then(|_| file.sync_all()))
+            .map_err(Error::io("write", &path))
+    }
+
+    // The save in the write-ahead log, None if there is none or the log itself is incomplete
+    fn logged_save(&self) -> Result<Option<WalEntry>, Error> {
+        let path = self.wal_path();
+        match fs::read_to_string(&path) {
+            Ok(content) => Ok(serde_json::from_str(&content).ok()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(Error::io("read", &path)(e)),
+        }
+    }
+
+    // Finishes a save that was interrupted after it was logged, e.g. by a crash or a full disk. A
+    // log that is incomplete itself is dropped, the files were not touched yet then.
+    fn replay_wal(&mut self) -> Result<(), Error> {
+        let path = self.wal_path();
+        if !path.exists() {
+            return Ok(());
+        }
+        match self.logged_save()? {
+            Some(entry) => {
+                if let Some(archive) = &entry.archive {
+                    self.write_archive(archive)?;
+                }
+                if fs::read(&self.path).ok().as_deref() != Some(entry.tasks.as_bytes()) {
+                    self.write_json(&entry.tasks)?;
+                    self.notices.push(format!("Finished the interrupted save of {}.", self.path.display()));
+                    self.notices.extend(entry.changes.iter().map(|change| format!("  {}", change)));
+                }
+            },
+            None => self.notices.push(format!("Dropped the incomplete write-ahead log {}, the interrupted save was not applied.", path.display())),
+        }
+        fs::remove_file(&path).map_err(Error::io("remove", &path))
+    }
+
+    // Recovers a broken task or archive file in the way chosen with recover_with, without a choice
+    // the user is asked through Error::Recoverable
+    fn recover(&mut self, path: &Path, content: &str, error: &str) -> Result<Vec<Task>, Error> {
+        match self.recovery.take() {
+            Some(recovery) => {
+                let (tasks, notice) = recovery::recover_tasks(path, content, &self.data_dir, recovery)?;
+                self.notices.push(notice);
+                Ok(tasks)
+            },
+            None => Err(Error::Recoverable(Box::new(recovery::inspect(path, content, error, &self.data_dir)?))),
+        }
+    }
+
+    // The archive file is protected by the lock on the task file
+    fn load_archive(&mut self) -> Result<Vec<Task>, Error> {
+        let path = self.archive_path.clone();
+        let content = match fs::read(&path) {
+            Ok(content) => decode(content, &path)?,
+            Err(_) => return Ok(Vec::new()),
+        };
+        match schema::parse_task_file(&content) {
+            Ok(task_file) => {
+                self.last_id = self.last_id.max(task_file.last_id);
+                Ok(task_file.tasks)
+            },
+            Err(schema::SchemaError::Invalid(e)) if !self.read_only => {
+                let tasks = self.recover(&path, &content, &e)?;
+                Ok(tasks.into_iter().filter(|task| task.archived).collect())
+            },
+            Err(e) => Err(schema_error(e, "archive file", &path)),
+        }
+    }
+
+    // Content of the archive file. If the archive was not loaded, the tasks are added to the existing ones.
+    fn archive_content(&mut self, mut archived: Vec<Task>) -> Result<String, Error> {
+        if !self.archive_loaded {
+            // The archived tasks are copied as they are, without parsing years of pomodoros
+            // unless compact_json changed, then they are written again in the new format
+            let raw = match fs::read(&self.archive_path) {
+                Ok(content) => Some(decode(content, &self.archive_path)?)
+                    .filter(|content| content.starts_with("{\n") != self.compact)
+                    .and_then(|content| schema::parse_raw_tasks(&content)),
+                Err(_) => Some((0, Vec::new())),
+            };
+            if let Some((last_id, mut existing)) = raw {
+                self.last_id = self.last_id.max(last_id);
+                existing.retain(|task| !archived.iter().any(|t| t.id == task.id));
+                return encode(schema::serialize_task_file_with(&existing, &archived, self.last_id, self.compact), &self.archive_path);
+            }
+            // Older or broken archive files are migrated or recovered
+            let mut existing = self.load_archive()?;
+            existing.retain(|task| !archived.iter().any(|t| t.id == task.id));
+            existing.append(&mut archived);
+            archived = existing;
+        }
+        encode(schema::serialize_task_file_as(&archived, self.last_id, self.compact), &self.archive_path)
+    }
+
+    fn write_archive(&self, content: &str) -> Result<(), Error> {
+        let path = &self.archive_path;
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, content)
+            .and_then(|_| fs::rename(&tmp_path, path))
+            .map_err(Error::io("write", path))
+    }
+
+    fn load_journal(&mut self) -> Result<Vec<Task>, Error> {
+        let mut content = read_content(&mut self.file, &self.path)?;
+
+        // Start the journal from the existing task file when switching storage modes
+        if content.is_empty() {
+            let task_path = self.data_dir.join(TASK_FILE);
+            if let Ok(task_content) = fs::read(&task_path) {
+                let task_content = decode(task_content, &task_path)?;
+                if !task_content.is_empty() {
+                    let mut tasks = schema::parse_tasks(&task_content)
+                        .map_err(|e| Error::Other(format!("Failed to import task file {}: {}", task_path.display(), e)))?;
+                    tasks.extend(self.load_archive()?);
+                    if self.read_only {
+                        return Ok(tasks);
+                    }
+                    self.append_journal(&journal::diff(&[], &tasks))?;
+                    self.notices.push(format!("Imported {} tasks from {} into the journal.", tasks.len(), task_path.display()));
+                    return Ok(tasks);
+                }
+            }
+        }
+
+        // A last line without a newline is the result of an interrupted write and is dropped
+        if !content.is_empty() && !content.ends_with('\n') {
+            let valid_len = content.rfind('\n').map(|i| i + 1).unwrap_or(0);
+            if !self.read_only {
+                self.notices.push(format!("Dropped the incomplete last entry of the journal {}.", self.path.display()));
+                let file = self.writable_file()?;
+                file.set_len(valid_len as u64).map_err(Error::io("truncate", &self.path))?;
+            }
+            content.truncate(valid_len);
+        }
+
+        journal::replay(&content)
+            .map_err(|message| Error::Corrupt { what: "journal", path: self.path.clone(), message })
+    }
+
+    fn append_journal(&mut self, events: &[JournalEvent]) -> Result<(), Error> {
+        if events.is_empty() {
+            return Ok(());
+        }
+        let mut file = self.writable_file()?;
+        file.seek(SeekFrom::End(0))
+            .and_then(|_| file.write_all(journal::serialize_events(events).as_bytes()))
+            .and_then(|_| file.sync_data())
+            .map_err(Error::io("write", &self.path))
+    }
+}
@@ -0,0 +1,159 @@
+use std::path::Path;
+
+use chrono::Duration;
+
+use crate::config;
+use crate::storage::{self, Storage, DEFAULT_LOCK_TIMEOUT};
+use crate::{crypto, data_dir, next_task_id, Clock, Error, Pomodoro, SystemClock, Task};
+
+/// A task list of pt with its tasks, for tools that read or change them like pt does. Changes
+/// are kept in memory until `save`. Unless the task list was opened read-only, it stays locked
+/// until the store is dropped, so pt commands wait for it.
+pub struct TaskStore {
+    /// None for a store that only lives in memory
+    storage: Option<Storage>,
+    tasks: Vec<Task>,
+    clock: Box<dyn Clock + Send + Sync>,
+    // Messages of the loads and saves since the last take_notices
+    notices: Vec<String>,
+}
+
+impl TaskStore {
+    /// Opens a task list in the data directory of pt by its name, `"default"` for the one pt uses
+    /// without `--list-name`. Read-only task lists are not locked and never written, e.g. for a
+    /// status bar.
+    pub fn open(list_name: &str, read_only: bool) -> Result<TaskStore, Error> {
+        if !storage::valid_list_name(list_name) {
+            return Err(Error::Other(format!("Invalid list name {}. Use letters, digits, - and _ only.", list_name)));
+        }
+        let data_dir = data_dir().ok_or_else(|| Error::Other("Failed to find the home directory.".to_string()))?;
+        let config = config::load_config(&data_dir)?;
+        crypto::init(&config.encryption);
+        let list_dir = storage::list_dir(&data_dir, list_name);
+        TaskStore::load(Storage::open(&list_dir, &config, read_only, DEFAULT_LOCK_TIMEOUT)?)
+    }
+
+    /// Opens the task file of a project, a `.pt.json` file
+    pub fn open_project(path: &Path, read_only: bool) -> Result<TaskStore, Error> {
+        TaskStore::load(Storage::open_project(path, read_only, DEFAULT_LOCK_TIMEOUT)?)
+    }
+
+    /// A store of the tasks that is not backed by a file, `save` does nothing. For tests and for
+    /// frontends that keep the tasks themselves.
+    pub fn in_memory(tasks: Vec<Task>) -> TaskStore {
+        TaskStore { storage: None, tasks, clock: Box::new(SystemClock), notices: Vec::new() }
+    }
+
+    fn load(mut storage: Storage) -> Result<TaskStore, Error> {
+        let loaded = storage.load(false)?;
+        Ok(TaskStore { storage: Some(storage), tasks: loaded.tasks, clock: Box::new(SystemClock), notices: loaded.notices })
+    }
+
+    /// Uses the clock for the start and end of pomodoros and their remaining time, e.g. a
+    /// `FakeClock` in tests
+    pub fn with_clock(mut self, clock: impl Clock + Send + Sync + 'static) -> TaskStore {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// The tasks that are not archived, ordered by ID
+    pub fn tasks(&self) -> &[Task] {
+        &self.tasks
+    }
+
+    pub fn task(&self, id: u32) -> Option<&Task> {
+        self.tasks.iter().find(|task| task.id == id)
+    }
+
+    /// Tasks with a running pomodoro
+    pub fn running(&self) -> impl Iterator<Item = &Task> {
+        self.tasks.iter().filter(|task| task.pomodoro_active())
+    }
+
+    /// Time left in the running pomodoro of the task, negative once it is overdue
+    pub fn time_remaining(&self, id: u32) -> Option<Duration> {
+        self.task(id).and_then(|task| task.pomodoro_time_remaining_at(self.clock.now()))
+    }
+
+    /// Tasks whose running pomodoro is over and waits to be finished
+    pub fn expired(&self) -> impl Iterator<Item = &Task> {
+        let now = self.clock.now();
+        self.tasks.iter().filter(move |task| task.pomodoro_time_remaining_at(now).is_some_and(|remaining| remaining <= Duration::zero()))
+    }
+
+    /// Loads the tasks again if another program changed the task list, e.g. for a status bar that
+    /// polls it. Returns whether it changed. Changes that were not saved are lost.
+    pub fn refresh(&mut self) -> Result<bool, Error> {
+        let Some(storage) = self.storage.as_mut().filter(|storage| storage.changed_on_disk()) else {
+            return Ok(false);
+        };
+        let loaded = storage.reload()?;
+        self.tasks = loaded.tasks;
+        self.notices.extend(loaded.notices);
+        Ok(true)
+    }
+
+    /// Adds a task and returns its ID
+    pub fn add(&mut self, description: &str) -> u32 {
+        let last_id = self.storage.as_ref().map_or(0, Storage::last_id);
+        let id = next_task_id(&self.tasks, last_id);
+        self.tasks.push(Task::new(id, description.to_string()));
+        id
+    }
+
+    pub fn start_pomodoro(&mut self, id: u32) -> Result<(), Error> {
+        let now = self.clock.now();
+        let task = self.task_mut(id)?;
+        if task.pomodoro_active() {
+            return Err(Error::Conflict(format!("Pomodoro already active for task {}.", id)));
+        }
+        task.pomodoros.push(Pomodoro::new(now, None));
+        Ok(())
+    }
+
+    pub fn finish_pomodoro(&mut self, id: u32) -> Result<(), Error> {
+        let now = self.clock.now();
+        let task = self.task_mut(id)?;
+        match task.pomodoros.last_mut() {
+            Some(pomodoro) if pomodoro.end_time.is_none() => {
+                // The system clock may have gone back since the pomodoro started
+                pomodoro.end_time = Some(now.max(pomodoro.start_time));
+                pomodoro.clock_went_back = now < pomodoro.start_time;
+                Ok(())
+            },
+            _ => Err(Error::Conflict(format!("No pomodoro active for task {}.", id))),
+        }
+    }
+
+    /// Checks or unchecks the task
+    pub fn set_done(&mut self, id: u32, done: bool) -> Result<(), Error> {
+        self.task_mut(id)?.done = done;
+        Ok(())
+    }
+
+    fn task_mut(&mut self, id: u32) -> Result<&mut Task, Error> {
+        self.tasks.iter_mut().find(|task| task.id == id).ok_or(Error::NotFound(id))
+    }
+
+    /// The tasks of the store, e.g. of one that lives in memory. Changes that were not saved are
+    /// lost for a store that was opened from a file.
+    pub fn into_tasks(self) -> Vec<Task> {
+        self.tasks
+    }
+
+    /// Writes the changes. Like with pt, changes another program made since the tasks were loaded
+    /// are kept, and the tasks are updated with them.
+    pub fn save(&mut self) -> Result<(), Error> {
+        if let Some(storage) = &mut self.storage {
+            let saved = storage.save(&mut self.tasks)?;
+            self.notices.extend(saved.notices);
+        }
+        Ok(())
+    }
+
+    /// Messages about what loading and saving did since the last call, e.g. that a save was
+    /// applied to the version of the task list another program saved in the meantime
+    pub fn take_notices(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.notices)
+    }
+}
@@ -0,0 +1,153 @@
+use std::fs;
+use std::path::Path;
+
+use serde::{Serialize, Deserialize};
+
+use crate::Task;
+use crate::config::SyncConfig;
+use crate::crypto;
+use crate::http;
+use crate::s3;
+use crate::schema;
+use crate::storage::SYNC_STATE_FILE;
+
+// Remote tasks are saved here when both sides changed
+const REMOTE_COPY_FILE: &str = "tasks.remote.json";
+
+#[derive(Default)]
+#[derive(Serialize)]
+#[derive(Deserialize)]
+struct SyncState {
+    etag: Option<String>,
+    // Tasks as of the last sync
+    base: Vec<Task>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum SyncMode {
+    // Upload or download depending on which side changed
+    Auto,
+    // Overwrite the remote tasks
+    Push,
+    // Overwrite the local tasks
+    Pull,
+}
+
+struct Remote {
+    tasks: Vec<Task>,
+    etag: Option<String>,
+}
+
+fn sync_url(config: &SyncConfig, list_name: &str) -> Result<String, String> {
+    let url = config.url.as_ref().ok_or("No sync URL configured. Set sync.url in the config file.")?;
+    if url.contains("{list}") {
+        Ok(url.replace("{list}", list_name))
+    } else if list_name == crate::storage::DEFAULT_LIST {
+        Ok(url.clone())
+    } else {
+        Err("To sync named task lists, sync.url must contain {list}.".to_string())
+    }
+}
+
+fn with_auth(request: ureq::Request, config: &SyncConfig, payload: &[u8]) -> Result<ureq::Request, String> {
+    if config.s3.access_key.is_some() {
+        return s3::sign(request, &config.s3, payload);
+    }
+    Ok(match &config.username {
+        Some(username) => request.set("Authorization", &http::basic_auth(username, config.password.as_deref().unwrap_or(""))),
+        None => request,
+    })
+}
+
+fn fetch(config: &SyncConfig, url: &str) -> Result<Option<Remote>, String> {
+    let response = match with_auth(http::agent().get(url), config, &[])?.call() {
+        Ok(response) => response,
+        Err(ureq::Error::Status(404, _)) => return Ok(None),
+        Err(e) => return Err(http::error_message(e)),
+    };
+    let etag = response.header("ETag").map(str::to_string);
+    let content = response.into_string().map_err(|e| e.to_string())?;
+    let content = crypto::decode(content.into_bytes())?;
+    let tasks = schema::parse_tasks(&content).map_err(|e| format!("remote task file is invalid: {}", e))?;
+    Ok(Some(Remote { tasks, etag }))
+}
+
+// Uploads the tasks. The upload fails if the remote file changed since it was fetched.
+fn upload(config: &SyncConfig, url: &str, tasks: &[Task], etag: Option<&str>, exists: bool) -> Result<Option<String>, String> {
+    let content = crypto::encode(schema::serialize_tasks(tasks))?;
+    let mut request = with_auth(http::agent().put(url), config, content.as_bytes())?
+        .set("Content-Type", "application/json");
+    request = match (etag, exists) {
+        (Some(etag), _) => request.set("If-Match", etag),
+        (None, false) => request.set("If-None-Match", "*"),
+        (None, true) => request,
+    };
+    match request.send_string(&content) {
+        Ok(response) => Ok(response.header("ETag").map(str::to_string)),
+        Err(ureq::Error::Status(412, _)) => Err("the remote tasks changed during the sync, please try again".to_string()),
+        Err(e) => Err(http::error_message(e)),
+    }
+}
+
+fn sorted(tasks: &[Task]) -> Vec<Task> {
+    let mut tasks = tasks.to_vec();
+    tasks.sort_by_key(|task| task.id);
+    tasks
+}
+
+fn load_state(list_dir: &Path) -> SyncState {
+    fs::read(list_dir.join(SYNC_STATE_FILE))
+        .ok()
+        .and_then(|content| crypto::decode(content).ok())
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+// The state includes the tasks, so it is encrypted like the task file
+fn save_state(list_dir: &Path, state: &SyncState) -> Result<(), String> {
+    let content = crypto::encode(serde_json::to_string(state).expect("Failed to serialize sync state."))?;
+    fs::write(list_dir.join(SYNC_STATE_FILE), content).map_err(|e| e.to_string())
+}
+
+// Synchronizes the tasks with the remote file. Replaces the tasks if the remote ones are newer.
+pub fn sync(config: &SyncConfig, list_name: &str, list_dir: &Path, tasks: &mut Vec<Task>, mode: SyncMode) -> Result<(), String> {
+    let url = sync_url(config, list_name)?;
+    let mut state = load_state(list_dir);
+    let remote = fetch(config, &url)?;
+
+    let local = sorted(tasks);
+    let base = sorted(&state.base);
+
+    let remote = match remote {
+        None if mode == SyncMode::Pull => return Err(format!("There are no tasks at {}.", url)),
+        None => {
+            state.etag = upload(config, &url, &local, None, false)?;
+            state.base = local;
+            println!("Uploaded tasks to {}.", url);
+            return save_state(list_dir, &state);
+        },
+        Some(remote) => remote,
+    };
+    let remote_tasks = sorted(&remote.tasks);
+    let local_changed = local != base;
+    let remote_changed = remote_tasks != base;
+
+    if remote_tasks == local && mode != SyncMode::Push {
+        println!("Tasks are already in sync.");
+    } else if mode == SyncMode::Push || (mode == SyncMode::Auto && !remote_changed) {
+        state.etag = upload(config, &url, &local, remote.etag.as_deref(), true)?;
+        state.base = local;
+        println!("Uploaded tasks to {}.", url);
+        return save_state(list_dir, &state);
+    } else if mode == SyncMode::Pull || !local_changed {
+        *tasks = remote_tasks;
+        println!("Downloaded tasks from {}.", url);
+    } else {
+        let copy_path = list_dir.join(REMOTE_COPY_FILE);
+        fs::write(&copy_path, crypto::encode(schema::serialize_tasks(&remote_tasks))?).map_err(|e| e.to_string())?;
+        return Err(format!("Both the local and the remote tasks changed since the last sync. The remote tasks were saved to {}. Run pt --sync push or pt --sync pull to keep one side.", copy_path.display()));
+    }
+    state.etag = remote.etag;
+    state.base = sorted(tasks);
+    save_state(list_dir, &state)
+}
@@ -0,0 +1,50 @@
+use std::sync::OnceLock;
+
+use console::{measure_text_width, pad_str, truncate_str, Alignment, Term};
+
+// Descriptions are not truncated below this width, the lines wrap instead
+const MIN_DESCRIPTION_WIDTH: usize = 12;
+const ELLIPSIS: &str = "…";
+
+// Template of the lines from list.format, or None for the columns
+static FORMAT: OnceLock<Option<String>> = OnceLock::new();
+
+pub fn init(format: Option<String>) {
+    FORMAT.get_or_init(|| format);
+}
+
+pub fn format() -> Option<&'static str> {
+    FORMAT.get().and_then(Option::as_deref)
+}
+
+// Width of the terminal that stdout goes to, None if it is not a terminal
+pub fn terminal_width() -> Option<usize> {
+    Term::stdout().size_checked().map(|(_rows, columns)| columns as usize)
+}
+
+// console truncates text that fits exactly too
+fn truncate(text: &str, width: usize) -> String {
+    match measure_text_width(text) <= width {
+        true => text.to_string(),
+        false => truncate_str(text, width, ELLIPSIS).into_owned(),
+    }
+}
+
+// Lines of the rows with the descriptions padded so the last column is aligned. With a width,
+// descriptions are truncated with an ellipsis so the lines fit into it.
+pub fn lines(rows: &[(String, String, String)], width: Option<usize>) -> Vec<String> {
+    let mut description_width = rows.iter().map(|(_, description, _)| measure_text_width(description)).max().unwrap_or(0);
+    if let Some(width) = width {
+        let others = rows.iter()
+            .map(|(prefix, _, last)| measure_text_width(prefix) + measure_text_width(last) + 2)
+            .max()
+            .unwrap_or(0);
+        description_width = description_width.min(width.saturating_sub(others).max(MIN_DESCRIPTION_WIDTH));
+    }
+    rows.iter()
+        .map(|(prefix, description, last)| {
+            let description = pad_str(&truncate(description, description_width), description_width, Alignment::Left, None).into_owned();
+            format!("{} {} {}", prefix, description, last)
+        })
+        .collect()
+}
@@ -0,0 +1,172 @@
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Length of a pomodoro in minutes
+pub const POMODORO_DURATION: i64 = 25;
+
+/// A pomodoro spent on a task, running until it has an end time
+#[derive(Clone, PartialEq)]
+#[derive(Serialize)]
+#[derive(Deserialize)]
+#[non_exhaustive]
+pub struct Pomodoro {
+    pub start_time: DateTime<Utc>,
+    pub end_time: Option<DateTime<Utc>>,
+    /// Set if the system clock went back while the pomodoro ran, so it was ended at its start.
+    /// Cleared when its times are checked with pt --edit-times.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub clock_went_back: bool,
+}
+
+impl Pomodoro {
+    /// A pomodoro that ran from the start to the end time, or is still running without one
+    pub fn new(start_time: DateTime<Utc>, end_time: Option<DateTime<Utc>>) -> Pomodoro {
+        Pomodoro { start_time, end_time, clock_went_back: false }
+    }
+
+    /// Time from the start to the end, or to the specified time while it runs. Never negative,
+    /// even if the system clock went back since the pomodoro started.
+    pub fn duration_at(&self, now: DateTime<Utc>) -> Duration {
+        (self.end_time.unwrap_or(now) - self.start_time).max(Duration::zero())
+    }
+}
+
+/// A task of a task list. Archived tasks are only loaded on request, see `Storage::load`.
+#[derive(Clone, PartialEq)]
+#[derive(Serialize)]
+#[derive(Deserialize)]
+#[non_exhaustive]
+pub struct Task {
+    pub id: u32,
+    pub description: String,
+    pub done: bool,
+    pub archived: bool,
+    pub pomodoros: Vec<Pomodoro>,
+    /// Priority from A (highest) to Z
+    #[serde(default)]
+    pub priority: Option<char>,
+    #[serde(default)]
+    pub projects: Vec<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub due: Option<NaiveDate>,
+    /// Number of pomodoros the task is expected to take
+    #[serde(default)]
+    pub estimate: Option<u32>,
+    /// Key of the Jira issue that pt --sync jira logs the pomodoros on
+    #[serde(default)]
+    pub jira: Option<String>,
+    /// Web page of the task, e.g. the issue it was imported from
+    #[serde(default)]
+    pub url: Option<String>,
+    /// Git branch that pt --here starts pomodoros for this task on
+    #[serde(default)]
+    pub branch: Option<String>,
+    /// UUID of the Taskwarrior task this task mirrors
+    #[serde(default)]
+    pub taskwarrior: Option<String>,
+}
+
+impl Task {
+    pub fn new(id: u32, description: String) -> Task {
+        Task {
+            id,
+            description,
+            done: false,
+            archived: false,
+            pomodoros: Vec::new(),
+            priority: None,
+            projects: Vec::new(),
+            tags: Vec::new(),
+            due: None,
+            estimate: None,
+            jira: None,
+            url: None,
+            branch: None,
+            taskwarrior: None,
+        }
+    }
+
+    /// Description with priority, projects and tags in todo.txt notation
+    pub fn display_description(&self) -> String {
+        let mut description = String::new();
+        if let Some(priority) = self.priority {
+            description.push_str(&format!("({}) ", priority));
+        }
+        description.push_str(&self.description);
+        for project in &self.projects {
+            description.push_str(&format!(" +{}", project));
+        }
+        for tag in &self.tags {
+            description.push_str(&format!(" @{}", tag));
+        }
+        if let Some(due) = self.due {
+            description.push_str(&format!(" due:{}", due));
+        }
+        if let Some(jira) = &self.jira {
+            description.push_str(&format!(" jira:{}", jira));
+        }
+        if let Some(branch) = &self.branch {
+            description.push_str(&format!(" branch:{}", branch));
+        }
+        description
+    }
+
+    /// Time spent in the pomodoros of the task, a running one counts up to now
+    pub fn time_spent(&self) -> Duration {
+        self.time_spent_at(Utc::now())
+    }
+
+    /// Time spent in the pomodoros of the task, a running one counts up to the specified time
+    pub fn time_spent_at(&self, now: DateTime<Utc>) -> Duration {
+        self.pomodoros.iter().map(|pomodoro| pomodoro.duration_at(now)).fold(Duration::zero(), |total, time| total + time)
+    }
+
+    /// Time left in the running pomodoro, negative once it is overdue
+    pub fn pomodoro_time_remaining(&self) -> Option<Duration> {
+        self.pomodoro_time_remaining_at(Utc::now())
+    }
+
+    /// Time left in the running pomodoro at the specified time, at most the length of a pomodoro
+    pub fn pomodoro_time_remaining_at(&self, now: DateTime<Utc>) -> Option<Duration> {
+        match self.pomodoros.last() {
+            Some(pomodoro) => {
+                match pomodoro.end_time {
+                    Some(_end_time) => None,
+                    None => Some(Duration::minutes(POMODORO_DURATION) - pomodoro.duration_at(now)),
+                }
+            },
+            None => None,
+        }
+    }
+
+    /// Whether the running pomodoro started after now, which happens when the system clock went
+    /// back, e.g. with an NTP correction, or a finished one was ended at its start because of it.
+    /// Its times should be checked with pt --edit-times.
+    pub fn clock_went_back(&self) -> bool {
+        self.clock_went_back_at(Utc::now())
+    }
+
+    pub fn clock_went_back_at(&self, now: DateTime<Utc>) -> bool {
+        self.pomodoros.iter().any(|pomodoro| pomodoro.clock_went_back)
+            || self.pomodoros.last().is_some_and(|pomodoro| pomodoro.end_time.is_none() && pomodoro.start_time > now)
+    }
+
+    pub fn pomodoro_active(&self) -> bool {
+        match self.pomodoros.last() {
+            Some(pomodoro) => {
+                match pomodoro.end_time {
+                    Some(_end_time) => false,
+                    None => true,
+                }
+            },
+            None => false,
+        }
+    }
+}
+
+/// ID for a new task. IDs are never reused, so the highest ID ever assigned is taken into account.
+pub fn next_task_id(tasks: &[Task], last_id: u32) -> u32 {
+    tasks.iter().map(|task| task.id).fold(last_id, u32::max) + 1
+}
@@ -0,0 +1,74 @@
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, Utc};
+use serde::Deserialize;
+
+use crate::{finish_pomodoro, next_task_id, start_pomodoro, Clock, Task};
+
+// A task as Taskwarrior passes it to hooks, with dates like 20240501T120000Z
+#[derive(Deserialize)]
+struct TaskwarriorTask {
+    uuid: String,
+    description: String,
+    status: String,
+    #[serde(default)]
+    project: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    priority: Option<String>,
+    #[serde(default)]
+    due: Option<String>,
+    // Set while the task is started with task start
+    #[serde(default)]
+    start: Option<String>,
+}
+
+fn parse_date(value: &str) -> Option<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%SZ").ok().map(|time| time.and_utc())
+}
+
+// Taskwarrior stores due dates as the local midnight in UTC
+fn parse_due(value: &str) -> Option<NaiveDate> {
+    parse_date(value).map(|time| time.with_timezone(&Local).date_naive())
+}
+
+// H, M and L become the priorities A, B and C
+fn parse_priority(value: &str) -> Option<char> {
+    match value {
+        "H" => Some('A'),
+        "M" => Some('B'),
+        "L" => Some('C'),
+        _ => None,
+    }
+}
+
+// Mirrors a task that was added or modified in Taskwarrior, which stays the source of truth.
+// Starting and stopping the task starts and finishes a pomodoro.
+pub fn apply(line: &str, last_id: u32, tasks: &mut Vec<Task>, clock: &dyn Clock) -> Result<(), String> {
+    let source: TaskwarriorTask = serde_json::from_str(line).map_err(|e| e.to_string())?;
+    let id = match tasks.iter().find(|task| task.taskwarrior.as_deref() == Some(&source.uuid)) {
+        Some(task) => task.id,
+        // Tasks deleted before they reached pt are not added
+        None if source.status == "deleted" => return Ok(()),
+        None => {
+            let mut task = Task::new(next_task_id(tasks, last_id), source.description.clone());
+            task.taskwarrior = Some(source.uuid.clone());
+            tasks.push(task);
+            tasks.last().unwrap().id
+        },
+    };
+    let task = tasks.iter_mut().find(|task| task.id == id).unwrap();
+    task.description = source.description;
+    task.done = source.status == "completed";
+    task.archived = source.status == "deleted";
+    task.priority = source.priority.as_deref().and_then(parse_priority);
+    task.projects = source.project.into_iter().collect();
+    task.tags = source.tags;
+    task.due = source.due.as_deref().and_then(parse_due);
+    let active = task.pomodoro_active();
+    match source.start.as_deref().and_then(parse_date) {
+        Some(_) if !active => start_pomodoro(id, tasks, clock),
+        None if active => finish_pomodoro(id, tasks, clock),
+        _ => (),
+    }
+    Ok(())
+}
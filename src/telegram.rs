@@ -0,0 +1,138 @@
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::config::TelegramConfig;
+use crate::control::run_pt;
+use crate::{http, task_line, Error, Task};
+
+const API_URL: &str = "https://api.telegram.org";
+// Seconds Telegram holds a request for updates open, below the timeout of the HTTP client
+const POLL_TIMEOUT: u32 = 25;
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+const HELP: &str = "/list - active tasks\n/status - running pomodoros\n/add <description> - add a task\n/pomodoro <id> - start a pomodoro\n/finish <id> - finish the pomodoro\n/check <id> - check the task";
+
+#[derive(Deserialize)]
+struct Updates {
+    result: Vec<Update>,
+}
+
+#[derive(Deserialize)]
+struct Update {
+    update_id: i64,
+    message: Option<Message>,
+}
+
+#[derive(Deserialize)]
+struct Message {
+    chat: Chat,
+    text: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Chat {
+    id: i64,
+}
+
+// The URL contains the token, so errors are reported without it
+fn method_url(token: &str, method: &str) -> String {
+    format!("{}/bot{}/{}", API_URL, token, method)
+}
+
+pub fn send_message(token: &str, chat_id: i64, text: &str) -> Result<(), String> {
+    http::agent().post(&method_url(token, "sendMessage"))
+        .send_json(json!({ "chat_id": chat_id, "text": text }))
+        .map(|_| ())
+        .map_err(http::error_message_without_url)
+}
+
+fn status(tasks: &[Task]) -> String {
+    let running: Vec<String> = tasks.iter()
+        .filter_map(|task| task.pomodoro_time_remaining().map(|remaining| (task, remaining)))
+        .map(|(task, remaining)| format!("Task {}: {}, {} min left", task.id, task.description, (remaining.num_seconds().max(0) + 59) / 60))
+        .collect();
+    match running.is_empty() {
+        true => "No pomodoro running.".to_string(),
+        false => running.join("\n"),
+    }
+}
+
+fn list(tasks: &[Task]) -> String {
+    let lines: Vec<String> = tasks.iter().filter(|task| !task.archived).map(task_line).collect();
+    match lines.is_empty() {
+        true => "No tasks found.".to_string(),
+        false => lines.join("\n"),
+    }
+}
+
+// Runs pt for a command that changes the tasks and answers with the first line it printed
+fn change(exe: &Path, list_args: &[String], args: &[String]) -> String {
+    let (_, result) = run_pt(exe, list_args, args);
+    result["message"].as_str().or(result["error"].as_str()).unwrap_or_default().to_string()
+}
+
+// Answers a message like "/pomodoro 3"
+fn handle(text: &str, load: &dyn Fn() -> Result<Vec<Task>, Error>, exe: &Path, list_args: &[String]) -> String {
+    let (command, argument) = text.trim().split_once(' ').unwrap_or((text.trim(), ""));
+    // Commands in groups are addressed like /list@my_pt_bot
+    let command = command.split('@').next().unwrap_or_default();
+    let argument = argument.trim();
+    let pt_command = match command {
+        "/list" => return load().map_or_else(|e| e.to_string(), |tasks| list(&tasks)),
+        "/status" => return load().map_or_else(|e| e.to_string(), |tasks| status(&tasks)),
+        "/add" if argument.is_empty() || argument.starts_with('-') => return "Specify a description that doesn't start with -.".to_string(),
+        "/add" => return change(exe, list_args, &["--add".to_string(), argument.to_string()]),
+        "/pomodoro" => "--pomodoro",
+        "/finish" => "--finish-pomodoro",
+        "/check" => "--check",
+        _ => return HELP.to_string(),
+    };
+    match argument.parse::<u32>() {
+        Ok(id) => change(exe, list_args, &[pt_command.to_string(), id.to_string()]),
+        Err(_) => format!("Specify the task ID, e.g. {} 3.", command),
+    }
+}
+
+// Answers messages from the configured chat until the process ends. Messages are fetched with
+// long polling, so no public address is needed.
+pub fn run(config: &TelegramConfig, load: &dyn Fn() -> Result<Vec<Task>, Error>, exe: &Path, list_args: &[String]) {
+    let token = match &config.token {
+        Some(token) => token,
+        None => {
+            println!("Set telegram.token in the config file to the token of your bot.");
+            return;
+        }
+    };
+    println!("Answering Telegram messages. Press Ctrl+C to stop.");
+    let mut offset = 0;
+    loop {
+        let url = format!("{}?offset={}&timeout={}", method_url(token, "getUpdates"), offset, POLL_TIMEOUT);
+        let updates: Updates = match http::agent().get(&url).call().map_err(http::error_message_without_url)
+            .and_then(|response| response.into_json().map_err(|e| e.to_string())) {
+            Ok(updates) => updates,
+            Err(e) => {
+                println!("Failed to fetch Telegram messages: {}", e);
+                thread::sleep(RETRY_DELAY);
+                continue;
+            }
+        };
+        for update in updates.result {
+            offset = offset.max(update.update_id + 1);
+            let (chat_id, text) = match update.message {
+                Some(Message { chat, text: Some(text) }) => (chat.id, text),
+                _ => continue,
+            };
+            // Anyone can write to a bot, only the owner may control pt
+            if config.chat_id != Some(chat_id) {
+                println!("Ignored a message from chat {}. Set telegram.chat_id to {} if it is yours.", chat_id, chat_id);
+                continue;
+            }
+            if let Err(e) = send_message(token, chat_id, &handle(&text, load, exe, list_args)) {
+                println!("Failed to answer on Telegram: {}", e);
+            }
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use std::path::Path;
+use chrono::NaiveDate;
+
+use crate::{next_task_id, Task};
+
+// Todoist exports each project as a CSV file with the columns
+// TYPE, CONTENT, DESCRIPTION, PRIORITY, INDENT, AUTHOR, RESPONSIBLE, DATE, DATE_LANG, TIMEZONE, ...
+// Labels are part of the content as @label, the project is the name of the file.
+
+// Todoist priorities go from 1 (highest) to 4 (none)
+fn map_priority(priority: &str) -> Option<char> {
+    match priority.trim() {
+        "1" => Some('A'),
+        "2" => Some('B'),
+        "3" => Some('C'),
+        _ => None,
+    }
+}
+
+// Due dates are free text in Todoist, only absolute dates are imported
+fn parse_due(date: &str) -> Option<NaiveDate> {
+    let date = date.trim();
+    ["%Y-%m-%d", "%d %b %Y", "%b %d %Y"]
+        .iter()
+        .find_map(|format| NaiveDate::parse_from_str(date, format).ok())
+}
+
+fn column(headers: &csv::StringRecord, name: &str) -> Result<usize, String> {
+    headers.iter()
+        .position(|header| header.eq_ignore_ascii_case(name))
+        .ok_or(format!("column {} is missing", name))
+}
+
+// Adds all tasks of a Todoist CSV export, returns the number of imported tasks
+pub fn import(path: &Path, content: &str, last_id: u32, tasks: &mut Vec<Task>) -> Result<usize, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(content.as_bytes());
+    let headers = reader.headers().map_err(|e| e.to_string())?.clone();
+    let type_column = column(&headers, "TYPE")?;
+    let content_column = column(&headers, "CONTENT")?;
+    let priority_column = column(&headers, "PRIORITY").ok();
+    let date_column = column(&headers, "DATE").ok();
+
+    // Projects are single words, like in todo.txt
+    let project = path.file_stem().map(|stem| stem.to_string_lossy().split_whitespace().collect::<Vec<_>>().join("_"));
+    let mut count = 0;
+    let mut skipped_dates = 0;
+    for record in reader.records() {
+        let record = record.map_err(|e| e.to_string())?;
+        if record.get(type_column) != Some("task") {
+            continue;
+        }
+
+        let mut description = Vec::new();
+        let mut labels = Vec::new();
+        for word in record.get(content_column).unwrap_or("").split_whitespace() {
+            match word.strip_prefix('@') {
+                Some(label) if !label.is_empty() => labels.push(label.to_string()),
+                _ => description.push(word),
+            }
+        }
+
+        let mut task = Task::new(next_task_id(tasks, last_id), description.join(" "));
+        task.priority = priority_column.and_then(|c| record.get(c)).and_then(map_priority);
+        task.projects = project.iter().cloned().collect();
+        task.tags = labels;
+        if let Some(date) = date_column.and_then(|c| record.get(c)).filter(|date| !date.trim().is_empty()) {
+            task.due = parse_due(date);
+            if task.due.is_none() {
+                skipped_dates += 1;
+            }
+        }
+        tasks.push(task);
+        count += 1;
+    }
+    if skipped_dates > 0 {
+        println!("Skipped {} due dates that are not absolute dates.", skipped_dates);
+    }
+    Ok(count)
+}
@@ -0,0 +1,130 @@
+use chrono::NaiveDate;
+
+use crate::{next_task_id, Task};
+
+// A task as read from a line of a todo.txt file, see https://github.com/todotxt/todo.txt
+pub struct TodoTxtTask {
+    pub done: bool,
+    pub priority: Option<char>,
+    // Description without priority, dates, projects, contexts and the tags read into fields
+    pub description: String,
+    pub projects: Vec<String>,
+    pub contexts: Vec<String>,
+    pub due: Option<NaiveDate>,
+}
+
+fn is_date(word: &str) -> bool {
+    NaiveDate::parse_from_str(word, "%Y-%m-%d").is_ok()
+}
+
+fn parse_priority(word: &str) -> Option<char> {
+    let chars: Vec<char> = word.chars().collect();
+    match chars[..] {
+        ['(', p, ')'] if p.is_ascii_uppercase() => Some(p),
+        _ => None,
+    }
+}
+
+// Parses a single line, returns None for empty lines
+pub fn parse_line(line: &str) -> Option<TodoTxtTask> {
+    let mut words = line.split_whitespace().peekable();
+    words.peek()?;
+
+    let mut task = TodoTxtTask {
+        done: false,
+        priority: None,
+        description: String::new(),
+        projects: Vec::new(),
+        contexts: Vec::new(),
+        due: None,
+    };
+
+    if words.peek() == Some(&"x") {
+        task.done = true;
+        words.next();
+        // Completion date
+        if words.peek().is_some_and(|w| is_date(w)) {
+            words.next();
+        }
+    } else if let Some(priority) = words.peek().and_then(|w| parse_priority(w)) {
+        task.priority = Some(priority);
+        words.next();
+    }
+    // Creation date
+    if words.peek().is_some_and(|w| is_date(w)) {
+        words.next();
+    }
+
+    let mut description = Vec::new();
+    for word in words {
+        if word.len() > 1 && word.starts_with('+') {
+            task.projects.push(word[1..].to_string());
+        } else if word.len() > 1 && word.starts_with('@') {
+            task.contexts.push(word[1..].to_string());
+        } else if let Some(priority) = word.strip_prefix("pri:") {
+            // Completed tasks keep their priority as pri:A
+            task.priority = priority.chars().next().filter(|p| p.is_ascii_uppercase());
+        } else if let Some(due) = word.strip_prefix("due:").filter(|due| is_date(due)) {
+            task.due = NaiveDate::parse_from_str(due, "%Y-%m-%d").ok();
+        } else {
+            description.push(word);
+        }
+    }
+    task.description = description.join(" ");
+    Some(task)
+}
+
+// Adds all tasks of a todo.txt file, returns the number of imported tasks
+pub fn import(content: &str, last_id: u32, tasks: &mut Vec<Task>) -> usize {
+    let mut count = 0;
+    for line in content.lines() {
+        if let Some(parsed) = parse_line(line) {
+            let mut task = Task::new(next_task_id(tasks, last_id), parsed.description);
+            task.done = parsed.done;
+            task.priority = parsed.priority;
+            task.projects = parsed.projects;
+            task.tags = parsed.contexts;
+            task.due = parsed.due;
+            tasks.push(task);
+            count += 1;
+        }
+    }
+    count
+}
+
+// Formats a task as a todo.txt line
+pub fn format_task(task: &Task) -> String {
+    let mut words = Vec::new();
+    if task.done {
+        words.push("x".to_string());
+    } else if let Some(priority) = task.priority {
+        words.push(format!("({})", priority));
+    }
+    if !task.description.is_empty() {
+        words.push(task.description.clone());
+    }
+    for project in &task.projects {
+        words.push(format!("+{}", project));
+    }
+    for context in &task.tags {
+        words.push(format!("@{}", context));
+    }
+    if let Some(due) = task.due {
+        words.push(format!("due:{}", due));
+    }
+    // Completed tasks lose the priority prefix, so it is kept as a key:value tag
+    if let (true, Some(priority)) = (task.done, task.priority) {
+        words.push(format!("pri:{}", priority));
+    }
+    words.join(" ")
+}
+
+// Formats all active tasks as a todo.txt file
+pub fn export(tasks: &[Task]) -> String {
+    let mut content = String::new();
+    for task in tasks.iter().filter(|task| !task.archived) {
+        content.push_str(&format_task(task));
+        content.push('\n');
+    }
+    content
+}
@@ -0,0 +1,56 @@
+use std::fs;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use chrono::Local;
+
+use crate::status::{self, Format};
+use crate::{color, data_dir, pomodoros_today, state, table, task_columns, Error, Task};
+
+const UPDATE_INTERVAL: Duration = Duration::from_secs(1);
+// Moves the cursor home and clears the screen
+const CLEAR: &str = "\x1b[H\x1b[2J";
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+// Shows the active tasks and the running pomodoro, updated every second until the process ends.
+// The task list is read without locking and only when its file changed, as decrypting takes a while.
+pub fn run(load: &dyn Fn() -> Result<Vec<Task>, Error>, task_file: &Path) {
+    let mut tasks: Vec<Task> = Vec::new();
+    let mut loaded: Option<Option<SystemTime>> = None;
+    let mut error = None;
+    loop {
+        let current = modified(task_file);
+        // A task list that can't be read, e.g. because it is locked, is read again with the next update
+        if loaded != Some(current) {
+            match load() {
+                Ok(current_tasks) => {
+                    tasks = current_tasks;
+                    loaded = Some(current);
+                    error = None;
+                },
+                Err(e) => error = Some(e.to_string()),
+            }
+        }
+        let state = state::load_state(&data_dir());
+        let mut screen = String::from(CLEAR);
+        screen.push_str(&format!("{}  {}\n\n", Local::now().format("%H:%M:%S"), status::render(&tasks, &state, pomodoros_today(&tasks), Format::Plain)));
+        if let Some(error) = &error {
+            screen.push_str(&format!("{}\n\n", error));
+        }
+        let active: Vec<&Task> = tasks.iter().filter(|task| !task.archived).collect();
+        let rows: Vec<_> = active.iter().map(|task| task_columns(task)).collect();
+        let active: Vec<String> = active.iter().zip(table::lines(&rows, table::terminal_width()))
+            .map(|(task, line)| color::task(task, line))
+            .collect();
+        match active.is_empty() {
+            true => screen.push_str("No tasks found.\n"),
+            false => screen.push_str(&active.join("\n")),
+        }
+        println!("{}", screen);
+        thread::sleep(UPDATE_INTERVAL);
+    }
+}
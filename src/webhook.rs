@@ -0,0 +1,18 @@
+use crate::config::WebhookConfig;
+use crate::event::Event;
+use crate::http;
+
+// POSTs each event as JSON to the webhooks that want it
+pub fn send(webhooks: &[WebhookConfig], events: &[Event], list_name: &str) {
+    for event in events {
+        let payload = event.payload(list_name);
+        for webhook in webhooks {
+            if !webhook.events.is_empty() && !webhook.events.iter().any(|name| name == event.name()) {
+                continue;
+            }
+            if let Err(e) = http::agent().post(&webhook.url).send_json(&payload) {
+                println!("Failed to call webhook {}: {}", webhook.url, http::error_message(e));
+            }
+        }
+    }
+}
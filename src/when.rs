@@ -0,0 +1,240 @@
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, NaiveTime, TimeZone, Weekday};
+
+// Shared parser for the dates, times and durations of all commands. Besides 2024-03-01 and 14:30
+// it understands inputs like tomorrow 9am, in 2h, 30m ago or last friday.
+
+const DATE_EXAMPLES: &str = "e.g. 2024-03-01, today, tomorrow, friday, last friday, march 1 or in 3 days";
+const TIME_EXAMPLES: &str = "e.g. 9am, 14:30, tomorrow 9am, friday 14:00, in 2h or 30m ago";
+const DURATION_EXAMPLES: &str = "e.g. 25, 30s, 10m, 1h or 1h30m";
+
+const MONTHS: [&str; 12] = ["january", "february", "march", "april", "may", "june", "july", "august", "september", "october", "november", "december"];
+
+fn normalize(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<&str>>().join(" ").to_lowercase()
+}
+
+fn unit(name: &str) -> Option<Duration> {
+    match name {
+        "s" | "sec" | "secs" | "second" | "seconds" => Some(Duration::seconds(1)),
+        "m" | "min" | "mins" | "minute" | "minutes" => Some(Duration::minutes(1)),
+        "h" | "hr" | "hrs" | "hour" | "hours" => Some(Duration::hours(1)),
+        "d" | "day" | "days" => Some(Duration::days(1)),
+        "w" | "week" | "weeks" => Some(Duration::weeks(1)),
+        _ => None,
+    }
+}
+
+/// Durations like 30s, 10m, 1h30m or 2 hours. A plain number is minutes.
+pub fn parse_duration(text: &str) -> Result<Duration, String> {
+    let compact: String = normalize(text).chars().filter(|c| !c.is_whitespace()).collect();
+    let invalid = || format!("Invalid duration {}. Use {}.", text.trim(), DURATION_EXAMPLES);
+    if let Ok(minutes) = compact.parse::<i64>() {
+        return Duration::try_minutes(minutes).ok_or_else(invalid);
+    }
+    let mut total = Duration::zero();
+    let mut rest = compact.as_str();
+    if rest.is_empty() {
+        return Err(invalid());
+    }
+    while !rest.is_empty() {
+        let digits = rest.find(|c: char| !c.is_ascii_digit()).ok_or_else(invalid)?;
+        let number = rest[..digits].parse::<i32>().map_err(|_| invalid())?;
+        rest = &rest[digits..];
+        let letters = rest.find(|c: char| c.is_ascii_digit()).unwrap_or(rest.len());
+        let part = unit(&rest[..letters]).ok_or_else(invalid)?.checked_mul(number).ok_or_else(invalid)?;
+        total = total.checked_add(&part).ok_or_else(invalid)?;
+        rest = &rest[letters..];
+    }
+    Ok(total)
+}
+
+fn weekday(name: &str) -> Option<Weekday> {
+    let days = [Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri, Weekday::Sat, Weekday::Sun];
+    days.into_iter().find(|day| {
+        let full = match day {
+            Weekday::Mon => "monday",
+            Weekday::Tue => "tuesday",
+            Weekday::Wed => "wednesday",
+            Weekday::Thu => "thursday",
+            Weekday::Fri => "friday",
+            Weekday::Sat => "saturday",
+            Weekday::Sun => "sunday",
+        };
+        name == full || (name.len() >= 3 && full.starts_with(name))
+    })
+}
+
+fn month(name: &str) -> Option<u32> {
+    MONTHS.iter().position(|month| name.len() >= 3 && month.starts_with(name)).map(|index| index as u32 + 1)
+}
+
+// Days from today to the weekday: 0 to 6 ahead, or 1 to 7 ahead or back if today doesn't count
+fn days_to(today: NaiveDate, day: Weekday) -> i64 {
+    (day.num_days_from_monday() as i64 - today.weekday().num_days_from_monday() as i64).rem_euclid(7)
+}
+
+/// Dates like 2024-03-01, today, tomorrow, friday, next friday, last friday, march 1, in 3 days or
+/// 2 weeks ago. A weekday alone is the next one, today included.
+pub fn parse_date(text: &str, today: NaiveDate) -> Result<NaiveDate, String> {
+    let text = normalize(text);
+    let invalid = || format!("Invalid date {}. Use {}.", text, DATE_EXAMPLES);
+    let words: Vec<&str> = text.split(' ').collect();
+    let date = match words.as_slice() {
+        ["today"] => today,
+        ["tomorrow"] => today + Duration::days(1),
+        ["yesterday"] => today - Duration::days(1),
+        [name] if weekday(name).is_some() => today + Duration::days(days_to(today, weekday(name).unwrap())),
+        ["next", "week"] => today + Duration::weeks(1),
+        ["last", "week"] => today - Duration::weeks(1),
+        ["next", name] => {
+            let day = weekday(name).ok_or_else(invalid)?;
+            today + Duration::days(match days_to(today, day) { 0 => 7, days => days })
+        },
+        ["last", name] => {
+            let day = weekday(name).ok_or_else(invalid)?;
+            today - Duration::days(match days_to(today, day) { 0 => 7, days => 7 - days })
+        },
+        ["in", ..] => {
+            let duration = parse_duration(&words[1..].join(" ")).map_err(|_| invalid())?;
+            today.checked_add_signed(Duration::days(duration.num_days())).ok_or_else(invalid)?
+        },
+        [.., "ago"] => {
+            let duration = parse_duration(&words[..words.len() - 1].join(" ")).map_err(|_| invalid())?;
+            today.checked_sub_signed(Duration::days(duration.num_days())).ok_or_else(invalid)?
+        },
+        [first, second] => {
+            // march 1 or 1 march, in the current year
+            let (month, day) = match (month(first), month(second)) {
+                (Some(month), None) => (month, second),
+                (None, Some(month)) => (month, first),
+                _ => return Err(invalid()),
+            };
+            let day = day.trim_end_matches(|c: char| c.is_ascii_alphabetic()).parse::<u32>().map_err(|_| invalid())?;
+            NaiveDate::from_ymd_opt(today.year(), month, day).ok_or_else(invalid)?
+        },
+        _ => NaiveDate::parse_from_str(&text, "%Y-%m-%d").map_err(|_| invalid())?,
+    };
+    Ok(date)
+}
+
+// Times of day like 9am, 9:30pm, 14:30, noon or midnight
+fn parse_clock(text: &str) -> Option<NaiveTime> {
+    match text {
+        "noon" => return NaiveTime::from_hms_opt(12, 0, 0),
+        "midnight" => return NaiveTime::from_hms_opt(0, 0, 0),
+        _ => (),
+    }
+    let (clock, offset) = match (text.strip_suffix("am"), text.strip_suffix("pm")) {
+        (Some(clock), _) => (clock.trim(), Some(0)),
+        (_, Some(clock)) => (clock.trim(), Some(12)),
+        _ => (text, None),
+    };
+    let (hour, minute) = match clock.split_once(':') {
+        Some((hour, minute)) if minute.len() == 2 => (hour.parse::<u32>().ok()?, minute.parse::<u32>().ok()?),
+        Some(_) => return None,
+        // A plain number is only a time with am or pm, otherwise it could be a duration
+        None if offset.is_some() => (clock.parse::<u32>().ok()?, 0),
+        None => return None,
+    };
+    let hour = match offset {
+        Some(_) if hour == 0 || hour > 12 => return None,
+        Some(offset) => hour % 12 + offset,
+        None => hour,
+    };
+    NaiveTime::from_hms_opt(hour, minute, 0)
+}
+
+/// Points in time like 14:30, tomorrow 9am, friday 14:00, 2024-03-01 9:30, in 2h or 30m ago. Dates
+/// and times without a date are relative to now.
+pub fn parse_time(text: &str, now: DateTime<Local>) -> Result<DateTime<Local>, String> {
+    let text = normalize(text);
+    let invalid = || format!("Invalid time {}. Use {}.", text, TIME_EXAMPLES);
+    if text == "now" {
+        return Ok(now);
+    }
+    if let Some(duration) = text.strip_prefix("in ") {
+        return parse_duration(duration).ok().and_then(|duration| now.checked_add_signed(duration)).ok_or_else(invalid);
+    }
+    if let Some(duration) = text.strip_suffix(" ago") {
+        if let Ok(duration) = parse_duration(duration) {
+            return now.checked_sub_signed(duration).ok_or_else(invalid);
+        }
+    }
+    let words: Vec<&str> = text.split(' ').collect();
+    // The time of day is the last word, or the last two for 9 am
+    let (date_words, time) = match words.as_slice() {
+        [rest @ .., number, suffix @ ("am" | "pm")] => (rest, parse_clock(&format!("{}{}", number, suffix))),
+        [rest @ .., clock] => (rest, parse_clock(clock)),
+        [] => return Err(invalid()),
+    };
+    let time = match time {
+        Some(time) => time,
+        None if parse_date(&text, now.date_naive()).is_ok() => {
+            return Err(format!("{} has no time of day. Add one, e.g. {} 9am.", text, text));
+        },
+        None => return Err(invalid()),
+    };
+    let date = match date_words.is_empty() {
+        true => now.date_naive(),
+        false => parse_date(&date_words.join(" "), now.date_naive()).map_err(|_| invalid())?,
+    };
+    Local.from_local_datetime(&date.and_time(time)).earliest()
+        .ok_or_else(|| format!("{} doesn't exist in the local time zone.", text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("25"), Ok(Duration::minutes(25)));
+        assert_eq!(parse_duration("30s"), Ok(Duration::seconds(30)));
+        assert_eq!(parse_duration("1h30m"), Ok(Duration::minutes(90)));
+        assert_eq!(parse_duration("2 hours"), Ok(Duration::hours(2)));
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("10 parsecs").is_err());
+    }
+
+    #[test]
+    fn dates() {
+        // A wednesday
+        let today = date(2024, 5, 1);
+        assert_eq!(parse_date("tomorrow", today), Ok(date(2024, 5, 2)));
+        assert_eq!(parse_date("friday", today), Ok(date(2024, 5, 3)));
+        assert_eq!(parse_date("wednesday", today), Ok(date(2024, 5, 1)));
+        assert_eq!(parse_date("next wednesday", today), Ok(date(2024, 5, 8)));
+        assert_eq!(parse_date("last friday", today), Ok(date(2024, 4, 26)));
+        assert_eq!(parse_date("in 3 days", today), Ok(date(2024, 5, 4)));
+        assert_eq!(parse_date("2 weeks ago", today), Ok(date(2024, 4, 17)));
+        assert_eq!(parse_date("march 1", today), Ok(date(2024, 3, 1)));
+        assert_eq!(parse_date("2024-12-24", today), Ok(date(2024, 12, 24)));
+        assert!(parse_date("someday", today).is_err());
+    }
+
+    #[test]
+    fn times() {
+        let now = Local.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+        assert_eq!(parse_time("14:30", now), Ok(Local.with_ymd_and_hms(2024, 5, 1, 14, 30, 0).unwrap()));
+        assert_eq!(parse_time("tomorrow 9am", now), Ok(Local.with_ymd_and_hms(2024, 5, 2, 9, 0, 0).unwrap()));
+        assert_eq!(parse_time("in 2h", now), Ok(now + Duration::hours(2)));
+        assert_eq!(parse_time("30m ago", now), Ok(now - Duration::minutes(30)));
+        assert!(parse_time("tomorrow", now).unwrap_err().contains("has no time of day"));
+    }
+
+    #[test]
+    fn overflows_are_errors() {
+        let today = date(2024, 5, 1);
+        let now = Local.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+        assert!(parse_duration("99999999999999999").is_err());
+        assert!(parse_duration(&"2147483647w".repeat(8)).is_err());
+        assert!(parse_date("in 999999999 weeks", today).is_err());
+        assert!(parse_date("999999999 weeks ago", today).is_err());
+        assert!(parse_time("in 99999999999999", now).is_err());
+        assert!(parse_time("999999999 weeks ago", now).is_err());
+    }
+}
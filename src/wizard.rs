@@ -0,0 +1,60 @@
+use std::io::{self, IsTerminal};
+
+use chrono::Local;
+use dialoguer::theme::ColorfulTheme;
+use dialoguer::Input;
+
+use crate::{when, Task};
+
+fn ask(theme: &ColorfulTheme, prompt: &str, initial: &str, validate: impl Fn(&str) -> Result<(), String>) -> Result<String, String> {
+    Input::<String>::with_theme(theme)
+        .with_prompt(prompt)
+        .with_initial_text(initial)
+        .allow_empty(true)
+        .validate_with(|input: &String| validate(input.trim()))
+        .interact_text()
+        .map(|answer| answer.trim().to_string())
+        .map_err(|e| e.to_string())
+}
+
+fn words(answer: &str) -> Vec<String> {
+    answer.split(|c: char| c == ',' || c.is_whitespace())
+        .map(|word| word.trim_start_matches(['@', '+']))
+        .filter(|word| !word.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+// Asks for the fields of a new task one after the other, for pt add -i. Empty answers leave
+// the field unset, the description is the only one required.
+pub fn new_task(id: u32, description: &str) -> Result<Task, String> {
+    if !io::stdin().is_terminal() || !io::stderr().is_terminal() {
+        return Err("pt add -i needs a terminal to ask in.".to_string());
+    }
+    let theme = ColorfulTheme::default();
+    let description = ask(&theme, "Description", description, |answer| match answer.is_empty() {
+        true => Err("The task needs a description.".to_string()),
+        false => Ok(()),
+    })?;
+    let mut task = Task::new(id, description);
+    task.projects = words(&ask(&theme, "Projects (e.g. work, home)", "", |_| Ok(()))?);
+    task.tags = words(&ask(&theme, "Tags (e.g. phone, errand)", "", |_| Ok(()))?);
+    let estimate = ask(&theme, "Estimate in pomodoros", "", |answer| match answer.is_empty() || answer.parse::<u32>().is_ok_and(|estimate| estimate > 0) {
+        true => Ok(()),
+        false => Err("Enter a number of pomodoros, e.g. 3.".to_string()),
+    })?;
+    task.estimate = estimate.parse().ok();
+    let today = Local::now().date_naive();
+    let due = ask(&theme, "Due date (e.g. friday, in 2 weeks)", "", |answer| match answer.is_empty() {
+        true => Ok(()),
+        false => when::parse_date(answer, today).map(|_| ()),
+    })?;
+    task.due = when::parse_date(&due, today).ok();
+    let priority = ask(&theme, "Priority (A is the highest)", "", |answer| match answer.len() {
+        0 => Ok(()),
+        1 if answer.chars().all(|c| c.is_ascii_alphabetic()) => Ok(()),
+        _ => Err("Enter a letter from A to Z.".to_string()),
+    })?;
+    task.priority = priority.chars().next().map(|priority| priority.to_ascii_uppercase());
+    Ok(task)
+}
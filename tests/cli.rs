@@ -0,0 +1,600 @@
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::process::{Child, Command, Output, Stdio};
+use std::thread;
+use std::time::Duration;
+
+use pt_core::config::Config;
+use pt_core::storage::{Storage, DEFAULT_LOCK_TIMEOUT};
+use serde_json::Value;
+
+// A home directory of its own for each test, so pt keeps its data in <home>/.pt
+struct Sandbox {
+    home: PathBuf,
+}
+
+impl Sandbox {
+    fn new(name: &str) -> Sandbox {
+        let home = env::temp_dir().join(format!("pt-test-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&home);
+        fs::create_dir_all(&home).unwrap();
+        Sandbox { home }
+    }
+
+    fn command(&self, args: &[&str]) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_pt"));
+        command.current_dir(&self.home)
+            .env("HOME", &self.home)
+            .env("LC_ALL", "C")
+            .env("NO_COLOR", "1")
+            .env_remove("PT_PAGER")
+            .args(["--no-notify", "--silent", "--no-pager"])
+            .args(args);
+        command
+    }
+
+    fn run(&self, args: &[&str]) -> Output {
+        self.command(args).output().unwrap()
+    }
+
+    // Runs pt and checks that it succeeded, returns its output
+    fn ok(&self, args: &[&str]) -> String {
+        let output = self.run(args);
+        let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+        assert!(output.status.success(), "pt {} failed: {}{}", args.join(" "), stdout, String::from_utf8_lossy(&output.stderr));
+        stdout
+    }
+
+    fn exit_code(&self, args: &[&str]) -> i32 {
+        self.run(args).status.code().unwrap()
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.home.join(name)
+    }
+
+    fn task_file(&self) -> Value {
+        serde_json::from_str(&fs::read_to_string(self.path(".pt/tasks.json")).unwrap()).unwrap()
+    }
+
+    fn tasks(&self) -> Vec<Value> {
+        self.task_file()["tasks"].as_array().unwrap().clone()
+    }
+}
+
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.home);
+    }
+}
+
+#[test]
+fn add_and_list() {
+    let sandbox = Sandbox::new("add");
+    assert!(sandbox.ok(&["Write report"]).contains("Task 1 added."));
+    assert!(sandbox.ok(&["add", "Review +work @office"]).contains("Task 2 added."));
+    let list = sandbox.ok(&["--list"]);
+    assert!(list.contains("001 [ ]: Write report"));
+    assert!(list.contains("002 [ ]: Review +work @office"));
+    assert_eq!(sandbox.tasks().len(), 2);
+    assert_eq!(sandbox.ok(&["list", "--format", "{id}:{title}"]), "1:Write report\n2:Review +work @office\n");
+}
+
+#[test]
+fn json_output() {
+    let sandbox = Sandbox::new("json");
+    sandbox.ok(&["Write report"]);
+    let output: Value = serde_json::from_str(&sandbox.ok(&["--json", "--list"])).unwrap();
+    assert_eq!(output["ok"], true);
+    assert_eq!(output["tasks"][0]["description"], "Write report");
+    // Commands without JSON output refuse --json instead of printing text
+    for command in [&["--lists"][..], &["doctor"], &["help"], &["list", "--archivd"]] {
+        let output = sandbox.run(&[&["--json"][..], command].concat());
+        assert_eq!(output.status.code(), Some(3));
+        let output: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(output["ok"], false);
+    }
+}
+
+#[test]
+fn pomodoros() {
+    let sandbox = Sandbox::new("pomodoros");
+    sandbox.ok(&["Write report"]);
+    assert!(sandbox.ok(&["-p", "1"]).contains("Pomodoro started for task 1."));
+    assert!(sandbox.ok(&["--status"]).contains("Write report"));
+    assert_eq!(sandbox.exit_code(&["-p", "1"]), 5);
+    assert!(sandbox.ok(&["-f", "1"]).contains("Pomodoro finished for task 1."));
+    assert_eq!(sandbox.exit_code(&["-f", "1"]), 5);
+    assert!(sandbox.ok(&["start", "1"]).contains("Pomodoro started for task 1."));
+    sandbox.ok(&["finish", "1"]);
+    let pomodoros = sandbox.tasks()[0]["pomodoros"].as_array().unwrap().clone();
+    assert_eq!(pomodoros.len(), 2);
+    assert!(pomodoros.iter().all(|pomodoro| !pomodoro["end_time"].is_null()));
+}
+
+#[test]
+fn unknown_task() {
+    let sandbox = Sandbox::new("unknown");
+    sandbox.ok(&["Write report"]);
+    assert_eq!(sandbox.exit_code(&["-p", "9"]), 4);
+    assert_eq!(sandbox.exit_code(&["-c", "9"]), 4);
+}
+
+#[test]
+fn track_time() {
+    let sandbox = Sandbox::new("track");
+    sandbox.ok(&["Write report"]);
+    assert!(sandbox.ok(&["-t", "1", "30"]).contains("Tracked 30 minutes for task 1."));
+    assert!(sandbox.ok(&["--list"]).contains("Σ30 min"));
+    assert!(sandbox.ok(&["report"]).contains("Write report: 1 pomodoros, 30 min"));
+}
+
+#[test]
+fn reports_count_archived_tasks() {
+    let sandbox = Sandbox::new("report-archived");
+    sandbox.ok(&["Write report"]);
+    sandbox.ok(&["-t", "1", "30"]);
+    sandbox.ok(&["-a", "1"]);
+    assert_eq!(sandbox.tasks().len(), 0);
+    assert!(sandbox.ok(&["report"]).contains("Write report: 1 pomodoros, 30 min"));
+    let output: Value = serde_json::from_str(&sandbox.ok(&["--json", "--report"])).unwrap();
+    assert!(output["report"].to_string().contains("Write report"));
+}
+
+#[test]
+fn check_and_due() {
+    let sandbox = Sandbox::new("check");
+    sandbox.ok(&["Write report"]);
+    sandbox.ok(&["-c", "1"]);
+    assert_eq!(sandbox.tasks()[0]["done"], true);
+    sandbox.ok(&["-u", "1"]);
+    assert_eq!(sandbox.tasks()[0]["done"], false);
+    sandbox.ok(&["done", "1"]);
+    assert_eq!(sandbox.tasks()[0]["done"], true);
+    assert!(sandbox.ok(&["--due", "1", "2030-01-02"]).contains("Task 1 is due on 2030-01-02."));
+    assert_eq!(sandbox.tasks()[0]["due"], "2030-01-02");
+    sandbox.ok(&["due", "1"]);
+    assert!(sandbox.tasks()[0]["due"].is_null());
+}
+
+#[test]
+fn archive() {
+    let sandbox = Sandbox::new("archive");
+    sandbox.ok(&["Write report"]);
+    sandbox.ok(&["Review"]);
+    sandbox.ok(&["-a", "1"]);
+    assert!(!sandbox.ok(&["--list"]).contains("Write report"));
+    assert!(sandbox.ok(&["--list-archived"]).contains("001 [ ]: Write report"));
+    sandbox.ok(&["--unarchive", "1"]);
+    assert!(sandbox.ok(&["--list"]).contains("Write report"));
+    sandbox.ok(&["-c", "2"]);
+    sandbox.ok(&["--archive-checked"]);
+    assert!(sandbox.ok(&["list", "--archived"]).contains("002 [x]: Review"));
+    assert_eq!(sandbox.tasks().len(), 1);
+    // IDs of archived tasks are not reused
+    assert!(sandbox.ok(&["Plan"]).contains("Task 3 added."));
+}
+
+#[test]
+fn dry_run_and_read_only() {
+    let sandbox = Sandbox::new("dry-run");
+    sandbox.ok(&["Write report"]);
+    let before = fs::read(sandbox.path(".pt/tasks.json")).unwrap();
+    assert!(sandbox.ok(&["--dry-run", "Not saved"]).contains("Add task 2: Not saved"));
+    assert!(sandbox.ok(&["--read-only", "-p", "1"]).contains("changes were not saved"));
+    // The editor would open, although nothing could be saved
+    assert_eq!(sandbox.exit_code(&["--dry-run", "--edit-times", "1"]), 3);
+    assert_eq!(fs::read(sandbox.path(".pt/tasks.json")).unwrap(), before);
+}
+
+#[test]
+fn task_lists() {
+    let sandbox = Sandbox::new("lists");
+    sandbox.ok(&["Write report"]);
+    sandbox.ok(&["-n", "work", "Review"]);
+    assert_eq!(sandbox.ok(&["--lists"]), "* default\n  work\n");
+    assert!(sandbox.ok(&["-n", "work", "--list"]).contains("001 [ ]: Review"));
+    assert!(!sandbox.ok(&["--list"]).contains("Review"));
+}
+
+#[test]
+fn project_file() {
+    let sandbox = Sandbox::new("project");
+    sandbox.ok(&["Global task"]);
+    sandbox.ok(&["--init"]);
+    sandbox.ok(&["Project task"]);
+    let project: Value = serde_json::from_str(&fs::read_to_string(sandbox.path(".pt.json")).unwrap()).unwrap();
+    assert_eq!(project["tasks"][0]["description"], "Project task");
+    assert!(sandbox.ok(&["--global", "--list"]).contains("Global task"));
+}
+
+#[test]
+fn export_and_import() {
+    let sandbox = Sandbox::new("export");
+    sandbox.ok(&["Write report +work"]);
+    let file = sandbox.path("tasks.txt");
+    sandbox.ok(&["--export", "todotxt", file.to_str().unwrap()]);
+    assert_eq!(fs::read_to_string(&file).unwrap(), "Write report +work\n");
+    assert!(sandbox.ok(&["-n", "imported", "--import", "todotxt", file.to_str().unwrap()]).contains("Imported 1 tasks"));
+    assert!(sandbox.ok(&["-n", "imported", "--list"]).contains("Write report +work"));
+}
+
+#[test]
+fn backup_and_restore() {
+    let sandbox = Sandbox::new("backup");
+    sandbox.ok(&["Write report"]);
+    let backup = sandbox.path("backup.zip");
+    sandbox.ok(&["--backup", backup.to_str().unwrap()]);
+    sandbox.ok(&["Review"]);
+    sandbox.ok(&["--restore", backup.to_str().unwrap()]);
+    assert_eq!(sandbox.tasks().len(), 1);
+}
+
+#[test]
+fn notify_expired_pomodoro() {
+    let sandbox = Sandbox::new("notify");
+    sandbox.ok(&["Write report"]);
+    // A pomodoro that started long ago is over
+    let mut task_file = sandbox.task_file();
+    task_file["tasks"][0]["pomodoros"] = serde_json::json!([{ "start_time": "2020-01-01T00:00:00Z", "end_time": null }]);
+    fs::write(sandbox.path(".pt/tasks.json"), task_file.to_string()).unwrap();
+    assert!(sandbox.ok(&["--notify"]).contains("Pomodoro finished for task 1."));
+    // Each notification is only shown once
+    assert_eq!(sandbox.exit_code(&["--notify", "--quiet"]), 2);
+}
+
+#[test]
+fn validate_and_compact() {
+    let sandbox = Sandbox::new("validate");
+    sandbox.ok(&["Write report"]);
+    assert!(sandbox.ok(&["--validate"]).contains("1 tasks, no problems found."));
+    assert!(sandbox.ok(&["--compact"]).contains("Nothing to clean up."));
+    fs::write(sandbox.path(".pt/tasks.json"), "{").unwrap();
+    assert_eq!(sandbox.exit_code(&["--validate"]), 1);
+}
+
+#[test]
+fn failed_commands_exit_with_their_code() {
+    let sandbox = Sandbox::new("exit-codes");
+    sandbox.ok(&["Write report"]);
+    assert_eq!(sandbox.exit_code(&["--import", "bogus", "tasks.txt"]), 3);
+    assert_eq!(sandbox.exit_code(&["--import", "bogus"]), 3);
+    assert_eq!(sandbox.exit_code(&["--import", "todotxt"]), 3);
+    assert_eq!(sandbox.exit_code(&["--import", "github"]), 3);
+    assert_eq!(sandbox.exit_code(&["--import", "todotxt", "missing.txt"]), 1);
+    assert_eq!(sandbox.exit_code(&["--email", "bogus"]), 3);
+    assert_eq!(sandbox.exit_code(&["--email"]), 3);
+    assert_eq!(sandbox.exit_code(&["--merge-files", "missing.json", "other.json"]), 1);
+    assert_eq!(sandbox.exit_code(&["--notify", "--bogus"]), 3);
+    // The data directory is no git repository
+    assert_eq!(sandbox.exit_code(&["--git", "status"]), 1);
+}
+
+#[test]
+fn doctor_checks_the_task_file() {
+    let sandbox = Sandbox::new("doctor");
+    sandbox.ok(&["Write report"]);
+    let output = String::from_utf8_lossy(&sandbox.run(&["doctor"]).stdout).into_owned();
+    assert!(output.contains("ok       Config file: no config file, using the defaults"));
+    assert!(output.contains("ok       Lock: not locked"));
+}
+
+#[test]
+fn help() {
+    let sandbox = Sandbox::new("help");
+    assert!(sandbox.ok(&["--help"]).contains("Usage:"));
+}
+
+#[test]
+fn broken_task_file_is_copied_once() {
+    let sandbox = Sandbox::new("corrupt");
+    sandbox.ok(&["Write report"]);
+    fs::write(sandbox.path(".pt/tasks.json"), "{").unwrap();
+    fs::write(sandbox.path(".pt/tasks.json.corrupt-20240501-090000"), "{").unwrap();
+    assert!(!sandbox.run(&["Review"]).status.success());
+    let copies = fs::read_dir(sandbox.path(".pt")).unwrap()
+        .filter(|entry| entry.as_ref().unwrap().file_name().to_string_lossy().starts_with("tasks.json.corrupt-"))
+        .count();
+    assert_eq!(copies, 1);
+}
+
+// Serves a sync URL without tasks that accepts one upload
+fn sync_server() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/tasks.json", listener.local_addr().unwrap());
+    thread::spawn(move || {
+        for stream in listener.incoming().take(2) {
+            let mut reader = BufReader::new(stream.unwrap());
+            let mut request = String::new();
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+                    length = value.trim().parse().unwrap();
+                }
+                if line.trim().is_empty() {
+                    break;
+                }
+                request.push_str(&line);
+            }
+            reader.by_ref().take(length).read_to_end(&mut Vec::new()).unwrap();
+            let response = if request.starts_with("GET") { "404 Not Found" } else { "201 Created\r\nETag: \"1\"" };
+            write!(reader.get_mut(), "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", response).unwrap();
+        }
+    });
+    url
+}
+
+#[test]
+fn encrypted_files_stay_encrypted() {
+    let sandbox = Sandbox::new("encrypted");
+    let config = serde_json::json!({ "encryption": { "enabled": true }, "sync": { "url": sync_server() } });
+    fs::create_dir_all(sandbox.path(".pt")).unwrap();
+    fs::write(sandbox.path(".pt/config.json"), config.to_string()).unwrap();
+    let run = |args: &[&str]| sandbox.command(args).env("PT_PASSPHRASE", "secret").output().unwrap();
+
+    assert!(run(&["Secret plan"]).status.success());
+    assert!(run(&["--sync"]).status.success());
+    fs::write(sandbox.path(".pt/tasks.json"), "{\"tasks\": [{\"description\": \"Secret plan\"").unwrap();
+    assert!(!run(&["Review"]).status.success());
+
+    for entry in fs::read_dir(sandbox.path(".pt")).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_file() && path != sandbox.path(".pt/tasks.json") {
+            let content = String::from_utf8_lossy(&fs::read(&path).unwrap()).into_owned();
+            assert!(!content.contains("Secret plan"), "{} contains plain text", path.display());
+        }
+    }
+    assert!(fs::read_dir(sandbox.path(".pt")).unwrap().any(|entry| entry.unwrap().file_name().to_string_lossy().starts_with("tasks.json.corrupt-")));
+}
+
+#[test]
+fn descriptions_starting_with_a_subcommand() {
+    let sandbox = Sandbox::new("collision");
+    assert!(sandbox.ok(&["list", "groceries"]).contains("Task 1 added."));
+    assert!(sandbox.ok(&["start", "writing"]).contains("Task 2 added."));
+    assert!(sandbox.ok(&["add", "report"]).contains("Task 3 added."));
+    assert!(sandbox.ok(&["--add", "doctor"]).contains("Task 4 added."));
+    let descriptions: Vec<Value> = sandbox.tasks().iter().map(|task| task["description"].clone()).collect();
+    assert_eq!(descriptions, ["list groceries", "start writing", "report", "doctor"]);
+    // The subcommand itself, and options that don't fit it, are not taken for a task
+    assert!(sandbox.ok(&["report"]).starts_with("Pomodoros on "));
+    assert_eq!(sandbox.exit_code(&["list", "--archivd"]), 3);
+    assert_eq!(sandbox.tasks().len(), 4);
+}
+
+#[test]
+fn listing_a_broken_task_file_recovers_it() {
+    let sandbox = Sandbox::new("list-corrupt");
+    sandbox.ok(&["Write report"]);
+    fs::write(sandbox.path(".pt/tasks.json"), "{").unwrap();
+    let copies = || fs::read_dir(sandbox.path(".pt")).unwrap()
+        .filter(|entry| entry.as_ref().unwrap().file_name().to_string_lossy().starts_with("tasks.json.corrupt-"))
+        .count();
+    // --read-only leaves everything as it is
+    assert!(!sandbox.run(&["--read-only", "--list"]).status.success());
+    assert_eq!(copies(), 0);
+    // Recovering needs a terminal to ask how
+    let output = sandbox.run(&["--list"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Run pt in an interactive terminal to recover the task file."));
+    assert_eq!(copies(), 1);
+}
+
+#[test]
+fn breaks_and_status() {
+    let sandbox = Sandbox::new("break");
+    sandbox.ok(&["Write report"]);
+    assert_eq!(sandbox.exit_code(&["-b", "0"]), 3);
+    assert!(sandbox.ok(&["-b", "10"]).contains("Break started for 10 minutes."));
+    let status: Value = serde_json::from_str(&sandbox.ok(&["--status", "--format", "json"])).unwrap();
+    assert_eq!(status["state"], "break");
+    sandbox.ok(&["-p", "1"]);
+    let output: Value = serde_json::from_str(&sandbox.ok(&["--json", "--status"])).unwrap();
+    assert_eq!(output["status"]["state"], "pomodoro");
+    assert_eq!(output["status"]["task"]["id"], 1);
+}
+
+#[test]
+fn json_changes() {
+    let sandbox = Sandbox::new("json-changes");
+    sandbox.ok(&["Write report"]);
+    let output: Value = serde_json::from_str(&sandbox.ok(&["--json", "start", "1"])).unwrap();
+    assert_eq!(output["messages"][0], "Pomodoro started for task 1.");
+    assert!(output["tasks"][0]["pomodoros"][0]["end_time"].is_null());
+    let output = sandbox.run(&["--json", "start", "1"]);
+    assert_eq!(output.status.code(), Some(5));
+    assert_eq!(serde_json::from_slice::<Value>(&output.stdout).unwrap()["ok"], false);
+    let output: Value = serde_json::from_str(&sandbox.ok(&["--json", "finish", "1"])).unwrap();
+    assert!(!output["tasks"][0]["pomodoros"][0]["end_time"].is_null());
+}
+
+#[test]
+fn track_ranges_and_reports() {
+    let sandbox = Sandbox::new("track-range");
+    sandbox.ok(&["Write report"]);
+    assert!(sandbox.ok(&["-t", "1", "yesterday", "9am", "to", "9:25am"]).contains("Tracked 25 minutes for task 1."));
+    assert!(sandbox.ok(&["report", "--date", "yesterday"]).contains("Write report: 1 pomodoros, 25 min"));
+    assert!(sandbox.ok(&["report"]).contains("No pomodoros."));
+    assert_eq!(sandbox.exit_code(&["-t", "1", "10am", "to", "9am"]), 3);
+    assert_eq!(sandbox.exit_code(&["-t", "1", "99999999999999"]), 3);
+    assert_eq!(sandbox.tasks()[0]["pomodoros"].as_array().unwrap().len(), 1);
+}
+
+#[test]
+fn jira_links() {
+    let sandbox = Sandbox::new("jira");
+    sandbox.ok(&["Write report"]);
+    assert!(sandbox.ok(&["--jira", "1", "PROJ-123"]).contains("Task 1 linked to PROJ-123."));
+    assert_eq!(sandbox.tasks()[0]["jira"], "PROJ-123");
+    sandbox.ok(&["--jira", "1"]);
+    assert!(sandbox.tasks()[0]["jira"].is_null());
+}
+
+#[test]
+fn org_export_and_import() {
+    let sandbox = Sandbox::new("org");
+    sandbox.ok(&["Write report"]);
+    sandbox.ok(&["-t", "1", "30"]);
+    let file = sandbox.path("tasks.org");
+    sandbox.ok(&["--export", "org", file.to_str().unwrap()]);
+    assert!(fs::read_to_string(&file).unwrap().starts_with("* TODO Write report\n  :LOGBOOK:\n  CLOCK: ["));
+    sandbox.ok(&["-n", "imported", "--import", "org", file.to_str().unwrap()]);
+    let list = sandbox.ok(&["-n", "imported", "--list"]);
+    assert!(list.contains("001 [ ]: Write report"));
+    assert!(list.contains("Σ30 min"));
+}
+
+#[test]
+fn snooze_and_acknowledge() {
+    let sandbox = Sandbox::new("snooze");
+    sandbox.ok(&["Write report"]);
+    assert!(sandbox.ok(&["--snooze"]).contains("There is no alert to snooze."));
+    let mut task_file = sandbox.task_file();
+    task_file["tasks"][0]["pomodoros"] = serde_json::json!([{ "start_time": "2020-01-01T00:00:00Z", "end_time": null }]);
+    fs::write(sandbox.path(".pt/tasks.json"), task_file.to_string()).unwrap();
+    sandbox.ok(&["--notify"]);
+    assert!(sandbox.ok(&["--snooze", "10m"]).contains("Alert for task 1 snoozed until"));
+    assert_eq!(sandbox.exit_code(&["--snooze", "99999999999999"]), 3);
+    assert!(sandbox.ok(&["--ack"]).contains("There is no alert to acknowledge."));
+}
+
+#[cfg(unix)]
+#[test]
+fn plugins() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let sandbox = Sandbox::new("plugins");
+    let bin = sandbox.path("bin");
+    fs::create_dir_all(&bin).unwrap();
+    let plugin = bin.join("pt-hello");
+    fs::write(&plugin, "#!/bin/sh\necho \"hello $1 from $PT_LIST\"\n").unwrap();
+    fs::set_permissions(&plugin, fs::Permissions::from_mode(0o755)).unwrap();
+    let path = format!("{}:{}", bin.display(), env::var("PATH").unwrap_or_default());
+    let run = |args: &[&str]| sandbox.command(args).env("PATH", &path).output().unwrap();
+    assert_eq!(String::from_utf8_lossy(&run(&["hello", "world"]).stdout), "hello world from default\n");
+    assert!(String::from_utf8_lossy(&run(&["--plugins"]).stdout).contains("hello"));
+    // --add takes the name for a description
+    assert!(run(&["--add", "hello"]).status.success());
+    assert_eq!(sandbox.tasks()[0]["description"], "hello");
+}
+
+// A pt that keeps running, e.g. pt --serve, until it is dropped
+struct Background(Child);
+
+impl Background {
+    fn start(sandbox: &Sandbox, args: &[&str]) -> Background {
+        Background(sandbox.command(args).stdout(Stdio::null()).stderr(Stdio::null()).spawn().unwrap())
+    }
+}
+
+impl Drop for Background {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+// pt --serve on a free port
+struct ApiServer {
+    _process: Background,
+    port: u16,
+}
+
+impl ApiServer {
+    fn start(sandbox: &Sandbox) -> ApiServer {
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let process = Background::start(sandbox, &["--wait", "0", "--serve", "--port", &port.to_string()]);
+        for _ in 0..100 {
+            if TcpStream::connect(("127.0.0.1", port)).is_ok() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+        ApiServer { _process: process, port }
+    }
+
+    // Sends a request without a body, returns the status code and the JSON it was answered with
+    fn request(&self, method: &str, path: &str) -> (u16, Value) {
+        self.request_with_token(method, path, "")
+    }
+
+    fn request_with_token(&self, method: &str, path: &str, token: &str) -> (u16, Value) {
+        let mut stream = TcpStream::connect(("127.0.0.1", self.port)).unwrap();
+        write!(stream, "{} {} HTTP/1.1\r\nHost: localhost\r\nAuthorization: Bearer {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", method, path, token).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        (head[9..12].parse().unwrap(), serde_json::from_str(body).unwrap())
+    }
+}
+
+#[test]
+fn api_survives_a_locked_task_list() {
+    let sandbox = Sandbox::new("api-locked");
+    sandbox.ok(&["Write report"]);
+    let server = ApiServer::start(&sandbox);
+    // Like a pt command that waits for input
+    let lock = Storage::open(&sandbox.path(".pt"), &Config::default(), false, DEFAULT_LOCK_TIMEOUT).unwrap();
+    let (code, body) = server.request("GET", "/status");
+    assert_eq!(code, 423);
+    assert_eq!(body["ok"], false);
+    drop(lock);
+    assert_eq!(server.request("GET", "/tasks").1["tasks"][0]["description"], "Write report");
+}
+
+#[test]
+fn api_answers_failed_changes_with_their_status() {
+    let sandbox = Sandbox::new("api-status");
+    sandbox.ok(&["Write report"]);
+    fs::write(sandbox.path(".pt/config.json"), r#"{"server": {"token": "secret"}}"#).unwrap();
+    let server = ApiServer::start(&sandbox);
+    assert_eq!(server.request("GET", "/status").0, 401);
+    assert_eq!(server.request_with_token("GET", "/status", "secreT").0, 401);
+    assert_eq!(server.request_with_token("POST", "/tasks/1/start", "secret").0, 200);
+    let (code, body) = server.request_with_token("POST", "/tasks/1/start", "secret");
+    assert_eq!(code, 409);
+    assert_eq!(body["ok"], false);
+    assert_eq!(server.request_with_token("POST", "/tasks/9/start", "secret").0, 404);
+    assert_eq!(server.request_with_token("POST", "/tasks/1/finish", "secret").0, 200);
+    assert_eq!(server.request_with_token("POST", "/tasks/1/finish", "secret").0, 409);
+}
+
+#[cfg(unix)]
+#[test]
+fn control_socket() {
+    use std::os::unix::net::UnixStream;
+
+    let sandbox = Sandbox::new("socket");
+    sandbox.ok(&["Write report"]);
+    let _daemon = Background::start(&sandbox, &["--daemon"]);
+    let mut stream = None;
+    for _ in 0..100 {
+        stream = UnixStream::connect(sandbox.path(".pt/pt.sock")).ok();
+        if stream.is_some() {
+            break;
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    let stream = stream.expect("the daemon didn't open its socket");
+    let mut lines = BufReader::new(stream.try_clone().unwrap()).lines();
+    let mut request = |line: &str| -> Value {
+        writeln!(&stream, "{}", line).unwrap();
+        serde_json::from_str(&lines.next().unwrap().unwrap()).unwrap()
+    };
+
+    let started = request(r#"{"command": "start", "task_id": 1}"#);
+    assert_eq!(started["message"], "Pomodoro started for task 1.");
+    assert!(started["task"]["pomodoros"][0]["end_time"].is_null());
+    assert!(sandbox.tasks()[0]["pomodoros"][0]["end_time"].is_null());
+    assert_eq!(request(r#"{"command": "status"}"#)["active"][0]["task"]["id"], 1);
+    assert_eq!(request(r#"{"command": "start", "task_id": 1}"#)["error"], "Pomodoro already active for task 1.");
+    assert_eq!(request(r#"{"command": "finish", "task_id": 1}"#)["ok"], true);
+    assert!(!sandbox.tasks()[0]["pomodoros"][0]["end_time"].is_null());
+    assert_eq!(request(r#"{"command": "finish", "task_id": 9}"#)["error"], "Task 9 not found.");
+    assert_eq!(request(r#"{"command": "list"}"#)["tasks"][0]["description"], "Write report");
+}
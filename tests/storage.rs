@@ -0,0 +1,158 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::{Duration, Utc};
+use pt_core::config::Config;
+use pt_core::recovery::Recovery;
+use pt_core::storage::{Storage, DEFAULT_LOCK_TIMEOUT, TASK_FILE, WAL_FILE};
+use pt_core::{Error, Pomodoro, Task};
+
+// A project task file of its own for each test
+fn project_file(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("pt-test-{}-{}", std::process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir.join(".pt.json")
+}
+
+fn open(path: &Path) -> (Storage, Vec<Task>) {
+    let mut storage = Storage::open_project(path, false, DEFAULT_LOCK_TIMEOUT).unwrap();
+    let tasks = storage.load(false).unwrap().tasks;
+    (storage, tasks)
+}
+
+fn saved(path: &Path) -> Vec<Task> {
+    open(path).1
+}
+
+#[test]
+fn refuses_to_save_broken_tasks() {
+    let path = project_file("invariants");
+    let (mut storage, mut tasks) = open(&path);
+    tasks.push(Task::new(1, "Write report".to_string()));
+    storage.save(&mut tasks).unwrap();
+
+    let now = Utc::now();
+    let mut broken = tasks.clone();
+    broken.push(Task::new(1, "Same ID".to_string()));
+    broken[0].pomodoros.push(Pomodoro::new(now, Some(now - Duration::minutes(5))));
+    match storage.save(&mut broken) {
+        Err(Error::Invalid(problems)) => assert_eq!(problems, vec![
+            "pomodoro 0 of task 1 ends before it starts".to_string(),
+            "task ID 1 is used more than once".to_string(),
+        ]),
+        _ => panic!("broken tasks were saved"),
+    }
+
+    let mut running = tasks.clone();
+    running[0].pomodoros.push(Pomodoro::new(now, None));
+    running[0].pomodoros.push(Pomodoro::new(now, None));
+    assert!(matches!(storage.save(&mut running), Err(Error::Invalid(_))));
+    drop(storage);
+    assert!(saved(&path) == tasks, "the broken tasks were written");
+    let _ = fs::remove_dir_all(path.parent().unwrap());
+}
+
+#[test]
+fn saves_despite_problems_the_file_already_had() {
+    let path = project_file("existing-problems");
+    let now = Utc::now();
+    let mut task = Task::new(1, "Write report".to_string());
+    task.pomodoros.push(Pomodoro::new(now, Some(now - Duration::minutes(5))));
+    fs::write(&path, pt_core::schema::serialize_task_file(&[task], 1)).unwrap();
+
+    let (mut storage, mut tasks) = open(&path);
+    tasks.push(Task::new(2, "Review".to_string()));
+    storage.save(&mut tasks).unwrap();
+    drop(storage);
+    assert_eq!(saved(&path).len(), 2);
+    let _ = fs::remove_dir_all(path.parent().unwrap());
+}
+
+#[test]
+fn finishes_a_logged_save() {
+    let dir = project_file("wal").parent().unwrap().to_path_buf();
+    let config = Config { write_ahead_log: true, ..Config::default() };
+    let open = || {
+        let mut storage = Storage::open(&dir, &config, false, DEFAULT_LOCK_TIMEOUT).unwrap();
+        let loaded = storage.load(false).unwrap();
+        (storage, loaded)
+    };
+    let (mut storage, loaded) = open();
+    let mut tasks = loaded.tasks;
+    tasks.push(Task::new(1, "Write report".to_string()));
+    storage.save(&mut tasks).unwrap();
+    drop(storage);
+    assert!(!dir.join(WAL_FILE).exists());
+
+    // A crash after the save was logged, while the task file was written
+    let logged = pt_core::schema::serialize_task_file(&[Task::new(1, "Write report".to_string()), Task::new(2, "Review".to_string())], 2);
+    fs::write(dir.join(WAL_FILE), serde_json::json!({ "changes": [], "tasks": logged, "archive": null }).to_string()).unwrap();
+    fs::write(dir.join(TASK_FILE), "{\"version\": 2, \"la").unwrap();
+    let loaded = open().1;
+    assert_eq!(loaded.tasks.len(), 2);
+    assert!(loaded.notices[0].starts_with("Finished the interrupted save"));
+    assert!(!dir.join(WAL_FILE).exists());
+
+    // A crash while the save was logged, the task file is unchanged
+    fs::write(dir.join(WAL_FILE), "{\"changes\": [], \"ta").unwrap();
+    let loaded = open().1;
+    assert_eq!(loaded.tasks.len(), 2);
+    assert!(loaded.notices[0].starts_with("Dropped the incomplete write-ahead log"));
+    assert!(!dir.join(WAL_FILE).exists());
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[cfg(unix)]
+#[test]
+fn reload_keeps_the_file_until_the_new_one_is_locked() {
+    let path = project_file("reload");
+    let (mut storage, mut tasks) = open(&path);
+    tasks.push(Task::new(1, "Write report".to_string()));
+    storage.save(&mut tasks).unwrap();
+    assert_eq!(storage.reload().unwrap().tasks.len(), 1);
+
+    // Another program replaces the file
+    let replacement = path.with_extension("new");
+    fs::write(&replacement, pt_core::schema::serialize_task_file(&[Task::new(1, "Write report".to_string()), Task::new(2, "Review".to_string())], 2)).unwrap();
+    fs::rename(&replacement, &path).unwrap();
+    let mut tasks = storage.reload().unwrap().tasks;
+    assert_eq!(tasks.len(), 2);
+
+    // The file can't be opened for a moment, the store keeps the one it has
+    let moved = path.with_extension("moved");
+    fs::rename(&path, &moved).unwrap();
+    std::os::unix::fs::symlink(path.with_file_name("missing/.pt.json"), &path).unwrap();
+    assert!(storage.reload().is_err());
+    fs::remove_file(&path).unwrap();
+    fs::rename(&moved, &path).unwrap();
+    tasks.push(Task::new(3, "Plan".to_string()));
+    storage.save(&mut tasks).unwrap();
+    drop(storage);
+    assert_eq!(saved(&path).len(), 3);
+    let _ = fs::remove_dir_all(path.parent().unwrap());
+}
+
+#[test]
+fn recovers_a_broken_file_the_chosen_way() {
+    let path = project_file("recovery");
+    let task = serde_json::to_string(&Task::new(1, "Write report".to_string())).unwrap();
+    fs::write(&path, format!("{{\"version\": 2, \"last_id\": 2, \"tasks\": [{}, {{\"id\": ", task)).unwrap();
+
+    let mut storage = Storage::open_project(&path, false, DEFAULT_LOCK_TIMEOUT).unwrap();
+    match storage.load(false) {
+        Err(Error::Recoverable(damage)) => {
+            assert_eq!((damage.salvaged, damage.lost, damage.backup), (1, 1, None));
+            assert!(damage.copy_saved && damage.copy.exists());
+        },
+        _ => panic!("the broken file was loaded without a choice"),
+    }
+    storage.recover_with(Recovery::Salvage);
+    let loaded = storage.load(false).unwrap();
+    assert_eq!(loaded.tasks.len(), 1);
+    assert_eq!(loaded.notices, vec!["Salvaged 1 tasks.".to_string()]);
+    drop(storage);
+    assert_eq!(saved(&path).len(), 1);
+    let _ = fs::remove_dir_all(path.parent().unwrap());
+}
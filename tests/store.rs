@@ -0,0 +1,88 @@
+use std::sync::Arc;
+
+use chrono::{Duration, TimeZone, Utc};
+use pt_core::{Clock, Error, FakeClock, Task, TaskStore, POMODORO_DURATION};
+
+fn store() -> (TaskStore, Arc<FakeClock>) {
+    let clock = Arc::new(FakeClock::new(Utc.with_ymd_and_hms(2024, 5, 1, 9, 0, 0).unwrap()));
+    let tasks = vec![Task::new(1, "Write report".to_string()), Task::new(2, "Review".to_string())];
+    (TaskStore::in_memory(tasks).with_clock(clock.clone()), clock)
+}
+
+#[test]
+fn add_assigns_the_next_id() {
+    let (mut store, _) = store();
+    assert_eq!(store.add("Plan"), 3);
+    assert_eq!(store.task(3).unwrap().description, "Plan");
+    assert_eq!(store.tasks().len(), 3);
+}
+
+#[test]
+fn pomodoro_expires_after_its_duration() {
+    let (mut store, clock) = store();
+    store.start_pomodoro(1).unwrap();
+    assert_eq!(store.time_remaining(1), Some(Duration::minutes(POMODORO_DURATION)));
+    assert_eq!(store.expired().count(), 0);
+
+    clock.advance(Duration::minutes(POMODORO_DURATION - 1));
+    assert_eq!(store.time_remaining(1), Some(Duration::minutes(1)));
+    assert_eq!(store.expired().count(), 0);
+
+    clock.advance(Duration::minutes(1));
+    assert_eq!(store.expired().map(|task| task.id).collect::<Vec<_>>(), vec![1]);
+    assert_eq!(store.running().count(), 1);
+}
+
+#[test]
+fn finish_records_the_time_of_the_clock() {
+    let (mut store, clock) = store();
+    let start = clock.now();
+    store.start_pomodoro(2).unwrap();
+    clock.advance(Duration::minutes(10));
+    store.finish_pomodoro(2).unwrap();
+
+    let task = store.task(2).unwrap();
+    assert_eq!(task.pomodoros[0].start_time, start);
+    assert_eq!(task.pomodoros[0].end_time, Some(start + Duration::minutes(10)));
+    assert_eq!(task.time_spent_at(start + Duration::hours(1)), Duration::minutes(10));
+    assert_eq!(store.time_remaining(2), None);
+}
+
+#[test]
+fn pomodoros_conflict_with_their_state() {
+    let (mut store, _) = store();
+    assert!(matches!(store.finish_pomodoro(1), Err(Error::Conflict(_))));
+    store.start_pomodoro(1).unwrap();
+    assert!(matches!(store.start_pomodoro(1), Err(Error::Conflict(_))));
+    assert!(matches!(store.start_pomodoro(9), Err(Error::NotFound(9))));
+}
+
+#[test]
+fn in_memory_store_saves_nothing() {
+    let (mut store, _) = store();
+    store.set_done(1, true).unwrap();
+    store.save().unwrap();
+    assert!(!store.refresh().unwrap());
+    assert!(store.task(1).unwrap().done);
+}
+
+#[test]
+fn clock_going_back_is_clamped() {
+    let (mut store, clock) = store();
+    let start = clock.now();
+    store.start_pomodoro(1).unwrap();
+    clock.advance(Duration::minutes(-10));
+    assert_eq!(store.time_remaining(1), Some(Duration::minutes(POMODORO_DURATION)));
+    assert!(store.task(1).unwrap().clock_went_back_at(clock.now()));
+    assert_eq!(store.task(1).unwrap().time_spent_at(clock.now()), Duration::zero());
+
+    store.finish_pomodoro(1).unwrap();
+    assert_eq!(store.task(1).unwrap().pomodoros[0].end_time, Some(start));
+    assert!(store.task(1).unwrap().pomodoros[0].clock_went_back);
+    assert!(store.task(1).unwrap().clock_went_back_at(start + Duration::hours(1)));
+
+    store.start_pomodoro(2).unwrap();
+    clock.advance(Duration::minutes(5));
+    store.finish_pomodoro(2).unwrap();
+    assert!(!store.task(2).unwrap().pomodoros[0].clock_went_back);
+}